@@ -0,0 +1,176 @@
+//! Compact binary serialization of quote batches
+//! The encoded data starts with a short header consisting of a magic tag and
+//! a format version, followed by the bincode encoded quotes.
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::quote::Quote;
+use super::DataError;
+
+const MAGIC: &[u8; 4] = b"FQLQ";
+const FORMAT_VERSION: u8 = 2;
+/// Format version without bid and ask prices
+const FORMAT_VERSION_1: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 1;
+
+/// Binary representation of a quote, storing the time as timestamp
+#[derive(Serialize, Deserialize)]
+struct BinaryQuote {
+    id: Option<usize>,
+    ticker: usize,
+    price: f64,
+    seconds: i64,
+    nanos: u32,
+    volume: Option<f64>,
+    bid: Option<f64>,
+    ask: Option<f64>,
+}
+
+/// Binary representation of a quote in format version 1
+#[derive(Serialize, Deserialize)]
+struct BinaryQuoteV1 {
+    id: Option<usize>,
+    ticker: usize,
+    price: f64,
+    seconds: i64,
+    nanos: u32,
+    volume: Option<f64>,
+}
+
+impl From<BinaryQuoteV1> for BinaryQuote {
+    fn from(quote: BinaryQuoteV1) -> Self {
+        BinaryQuote {
+            id: quote.id,
+            ticker: quote.ticker,
+            price: quote.price,
+            seconds: quote.seconds,
+            nanos: quote.nanos,
+            volume: quote.volume,
+            bid: None,
+            ask: None,
+        }
+    }
+}
+
+impl From<&Quote> for BinaryQuote {
+    fn from(quote: &Quote) -> Self {
+        BinaryQuote {
+            id: quote.id,
+            ticker: quote.ticker,
+            price: quote.price,
+            seconds: quote.time.timestamp(),
+            nanos: quote.time.timestamp_subsec_nanos(),
+            volume: quote.volume,
+            bid: quote.bid,
+            ask: quote.ask,
+        }
+    }
+}
+
+impl BinaryQuote {
+    fn into_quote(self) -> Result<Quote, DataError> {
+        let time = Local.timestamp_opt(self.seconds, self.nanos).single()
+            .ok_or_else(|| DataError::DataAccessFailure("invalid quote time".to_string()))?;
+        Ok(Quote {
+            id: self.id,
+            ticker: self.ticker,
+            price: self.price,
+            time,
+            volume: self.volume,
+            bid: self.bid,
+            ask: self.ask,
+        })
+    }
+}
+
+/// Serialize a batch of quotes into a compact binary format
+pub fn serialize_quotes(quotes: &[Quote]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(FORMAT_VERSION);
+    let quotes: Vec<BinaryQuote> = quotes.iter().map(BinaryQuote::from).collect();
+    // Serializing into a vector can't fail for quotes
+    bincode::serialize_into(&mut bytes, &quotes).unwrap();
+    bytes
+}
+
+/// Read a batch of quotes serialized with `serialize_quotes`
+pub fn deserialize_quotes(bytes: &[u8]) -> Result<Vec<Quote>, DataError> {
+    if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(DataError::DataAccessFailure(
+            "invalid quote batch header".to_string(),
+        ));
+    }
+    match bytes[MAGIC.len()] {
+        FORMAT_VERSION => {
+            let quotes: Vec<BinaryQuote> = bincode::deserialize(&bytes[HEADER_LEN..])
+                .map_err(|e| DataError::DataAccessFailure(e.to_string()))?;
+            quotes.into_iter().map(BinaryQuote::into_quote).collect()
+        }
+        FORMAT_VERSION_1 => {
+            let quotes: Vec<BinaryQuoteV1> = bincode::deserialize(&bytes[HEADER_LEN..])
+                .map_err(|e| DataError::DataAccessFailure(e.to_string()))?;
+            quotes.into_iter().map(|q| BinaryQuote::from(q).into_quote()).collect()
+        }
+        version => Err(DataError::DataAccessFailure(format!(
+            "unsupported quote batch format version {}",
+            version
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use crate::date_time_helper::make_time;
+
+    #[test]
+    fn quotes_round_trip() {
+        let start = make_time(2021, 1, 4, 18, 0, 0).unwrap();
+        let quotes: Vec<Quote> = (0..1000)
+            .map(|i| Quote {
+                id: Some(i),
+                ticker: 1 + i % 3,
+                price: 100.0 + i as f64 * 0.25,
+                time: start + Duration::hours(i as i64),
+                volume: if i % 2 == 0 { Some(1000.0) } else { None },
+                bid: if i % 3 == 0 { Some(99.5 + i as f64 * 0.25) } else { None },
+                ask: if i % 3 == 0 { Some(100.5 + i as f64 * 0.25) } else { None },
+            })
+            .collect();
+
+        let bytes = serialize_quotes(&quotes);
+        let decoded = deserialize_quotes(&bytes).unwrap();
+        assert_eq!(decoded.len(), quotes.len());
+        for (q, d) in quotes.iter().zip(decoded.iter()) {
+            assert_eq!(q.id, d.id);
+            assert_eq!(q.ticker, d.ticker);
+            assert_eq!(q.price, d.price);
+            assert_eq!(q.time, d.time);
+            assert_eq!(q.volume, d.volume);
+            assert_eq!(q.bid, d.bid);
+            assert_eq!(q.ask, d.ask);
+        }
+
+        let json = serde_json::to_vec(&quotes).unwrap();
+        assert!(bytes.len() < json.len() * 2 / 3);
+
+        assert!(deserialize_quotes(&json).is_err());
+        let mut future_version = bytes.clone();
+        future_version[MAGIC.len()] = FORMAT_VERSION + 1;
+        assert!(deserialize_quotes(&future_version).is_err());
+
+        // batches in the previous format are still readable
+        let mut version_1 = MAGIC.to_vec();
+        version_1.push(FORMAT_VERSION_1);
+        let legacy = vec![BinaryQuoteV1 { id: Some(1), ticker: 2, price: 3.5, seconds: start.timestamp(), nanos: 0, volume: None }];
+        bincode::serialize_into(&mut version_1, &legacy).unwrap();
+        let decoded = deserialize_quotes(&version_1).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].time, start);
+        assert_eq!(decoded[0].price, 3.5);
+        assert_eq!(decoded[0].bid, None);
+    }
+}
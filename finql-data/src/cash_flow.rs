@@ -1,6 +1,6 @@
 use std::{fmt,fmt::{Display, Formatter}};
 use std::collections::BTreeMap;
-use std::ops::Neg;
+use std::ops::{Add, Neg, Sub};
 
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Local, NaiveDate};
@@ -14,8 +14,21 @@ pub struct CashAmount {
     pub currency: Currency,
 }
 
+/// Range of digits supported by `round2digits`
+const ROUNDING_DIGITS: std::ops::RangeInclusive<i32> = -10..=15;
+
+/// Round `x` to the given number of decimal digits. Negative digits round to
+/// tens, hundreds, etc., e.g. `round2digits(1234., -2)` gives `1200.`.
+/// The number of digits is clamped to the range -10 to 15. If scaling `x`
+/// would overflow, `x` is returned unchanged.
 pub fn round2digits(x: f64, digits: i32) -> f64 {
-    (x * 10.0_f64.powi(digits)).round() / 10.0_f64.powi(digits)
+    let digits = digits.clamp(*ROUNDING_DIGITS.start(), *ROUNDING_DIGITS.end());
+    let scale = 10.0_f64.powi(digits);
+    let scaled = x * scale;
+    if !scaled.is_finite() {
+        return x;
+    }
+    scaled.round() / scale
 }
 
 impl CashAmount {
@@ -85,6 +98,23 @@ impl CashAmount {
         }
     }
 
+    /// Add a cash amount in the same currency, without any currency conversion
+    pub fn try_add(&self, cash_amount: CashAmount) -> Result<CashAmount, CurrencyError> {
+        if self.currency == cash_amount.currency {
+            Ok(CashAmount {
+                amount: self.amount + cash_amount.amount,
+                currency: self.currency,
+            })
+        } else {
+            Err(CurrencyError::CurrencyMismatch)
+        }
+    }
+
+    /// Subtract a cash amount in the same currency, without any currency conversion
+    pub fn try_sub(&self, cash_amount: CashAmount) -> Result<CashAmount, CurrencyError> {
+        self.try_add(-cash_amount)
+    }
+
     /// Round a cash amount to that number of decimals
     pub fn round(&self, digits: i32) -> CashAmount {
         CashAmount {
@@ -102,11 +132,35 @@ impl CashAmount {
             None => self.round(2),
         }
     }
+
+    /// Compare two cash amounts for equality within a given absolute tolerance
+    pub fn fuzzy_eq(&self, other: &CashAmount, tol: f64) -> bool {
+        self.currency == other.currency
+            && !self.amount.is_nan()
+            && !other.amount.is_nan()
+            && (self.amount - other.amount).abs() <= tol
+    }
+
+    /// Compare two cash amounts for equality after rounding both according to rounding conventions
+    pub fn eq_by_convention(&self, other: &CashAmount, rounding_conventions: &BTreeMap<String, i32>) -> bool {
+        self.currency == other.currency
+            && self.round_by_convention(rounding_conventions).amount
+                == other.round_by_convention(rounding_conventions).amount
+    }
+
+    /// Format the cash amount with given width and number of decimals
+    pub fn display_with(&self, width: usize, digits: usize) -> String {
+        format!("{:width$.digits$} {}", self.amount, self.currency, width = width, digits = digits)
+    }
 }
 
+/// Display the amount with the currency's number of rounding digits, unless
+/// width or precision are given explicitly in the format string
 impl Display for CashAmount {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{:16.4} {}", self.amount, self.currency)
+        let width = f.width().unwrap_or(16);
+        let digits = f.precision().unwrap_or_else(|| self.currency.rounding_digits().max(0) as usize);
+        write!(f, "{}", self.display_with(width, digits))
     }
 }
 
@@ -121,6 +175,30 @@ impl Neg for CashAmount {
     }
 }
 
+/// Add cash amounts in the same currency
+/// Panics if the currencies differ, use `CashAmount::try_add` or `CashAmount::add` instead
+/// if the currencies are not known to match.
+impl Add for CashAmount {
+    type Output = CashAmount;
+
+    fn add(self, rhs: CashAmount) -> Self::Output {
+        self.try_add(rhs)
+            .expect("cash amounts to be added must be in the same currency")
+    }
+}
+
+/// Subtract cash amounts in the same currency
+/// Panics if the currencies differ, use `CashAmount::try_sub` or `CashAmount::sub` instead
+/// if the currencies are not known to match.
+impl Sub for CashAmount {
+    type Output = CashAmount;
+
+    fn sub(self, rhs: CashAmount) -> Self::Output {
+        self.try_sub(rhs)
+            .expect("cash amounts to be subtracted must be in the same currency")
+    }
+}
+
 /// Container for a single cash flow
 #[derive(Deserialize, Serialize, Debug, Clone, Copy)]
 pub struct CashFlow {
@@ -166,3 +244,73 @@ impl Neg for CashFlow {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn rounding_digits() {
+        assert_eq!(round2digits(1.23456, 2), 1.23);
+        assert_eq!(round2digits(1234., -2), 1200.);
+        assert_eq!(round2digits(1.23456, 100), 1.23456);
+        assert!(!round2digits(1.23456, 400).is_nan());
+        assert_eq!(round2digits(1e300, 15), 1e300);
+        assert_eq!(round2digits(1234., -100), 0.);
+    }
+
+    #[test]
+    fn display_cash_amount() {
+        let jpy = CashAmount { amount: 1234.56, currency: Currency::from_str("JPY").unwrap() };
+        let usd = CashAmount { amount: 1234.567, currency: Currency::from_str("USD").unwrap() };
+        assert_eq!(format!("{}", jpy), "            1235 JPY");
+        assert_eq!(format!("{}", usd), "         1234.57 USD");
+        assert_eq!(format!("{:10.1}", usd), "    1234.6 USD");
+        assert_eq!(usd.display_with(0, 3), "1234.567 USD");
+    }
+
+    #[test]
+    fn same_currency_arithmetic() {
+        let eur = Currency::from_str("EUR").unwrap();
+        let a = CashAmount { amount: 100., currency: eur };
+        let b = CashAmount { amount: 30., currency: eur };
+        assert_eq!(a + b, CashAmount { amount: 130., currency: eur });
+        assert_eq!(a - b, CashAmount { amount: 70., currency: eur });
+        assert_eq!(a.try_sub(b), Ok(CashAmount { amount: 70., currency: eur }));
+
+        let usd = CashAmount { amount: 30., currency: Currency::from_str("USD").unwrap() };
+        assert_eq!(a.try_add(usd), Err(CurrencyError::CurrencyMismatch));
+        assert_eq!(a.try_sub(usd), Err(CurrencyError::CurrencyMismatch));
+    }
+
+    #[test]
+    fn compare_cash_amounts() {
+        let eur = Currency::from_str("EUR").unwrap();
+        let jpy = Currency::from_str("JPY").unwrap();
+        let a = CashAmount { amount: 100.004, currency: eur };
+        let b = CashAmount { amount: 100.001, currency: eur };
+        assert!(a.fuzzy_eq(&b, 0.01));
+        assert!(!a.fuzzy_eq(&b, 0.001));
+        assert!(!a.fuzzy_eq(&CashAmount { amount: 100.004, currency: jpy }, 0.01));
+        assert!(!a.fuzzy_eq(&CashAmount { amount: f64::NAN, currency: eur }, 0.01));
+
+        let mut rounding_conventions = BTreeMap::new();
+        rounding_conventions.insert("JPY".to_string(), 0);
+        rounding_conventions.insert("EUR".to_string(), 2);
+        assert!(a.eq_by_convention(&b, &rounding_conventions));
+        assert!(!a.eq_by_convention(&CashAmount { amount: 100.01, currency: eur }, &rounding_conventions));
+        let c = CashAmount { amount: 1234.4, currency: jpy };
+        let d = CashAmount { amount: 1233.6, currency: jpy };
+        assert!(c.eq_by_convention(&d, &rounding_conventions));
+        assert!(!c.eq_by_convention(&a, &rounding_conventions));
+    }
+
+    #[test]
+    #[should_panic]
+    fn add_amounts_in_different_currencies() {
+        let eur = CashAmount { amount: 100., currency: Currency::from_str("EUR").unwrap() };
+        let usd = CashAmount { amount: 30., currency: Currency::from_str("USD").unwrap() };
+        let _ = eur + usd;
+    }
+}
@@ -15,6 +15,8 @@ pub enum CurrencyError {
     InvalidCharacter,
     DeserializationFailed,
     ConversionFailed,
+    StaleRate,
+    CurrencyMismatch,
 }
 
 impl fmt::Display for CurrencyError {
@@ -29,6 +31,10 @@ impl fmt::Display for CurrencyError {
             ),
             CurrencyError::DeserializationFailed => write!(f, "currency deserialization failed"),
             CurrencyError::ConversionFailed => write!(f, "currency conversion failed"),
+            CurrencyError::StaleRate => write!(f, "the most recent fx rate is too old"),
+            CurrencyError::CurrencyMismatch => {
+                write!(f, "amounts in different currencies can't be combined without conversion")
+            }
         }
     }
 }
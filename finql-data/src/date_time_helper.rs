@@ -1,4 +1,4 @@
-use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use chrono::{DateTime, Local, LocalResult, NaiveDate, NaiveDateTime, TimeZone};
 use chrono_tz::Tz;
 use std::time::{Duration, UNIX_EPOCH};
 use thiserror::Error;
@@ -10,7 +10,78 @@ pub enum DateTimeError {
     #[error("Conversion of date-time failed")]
     DateTimeConversionFailed,
     #[error("Failed to parse (date-)time from string")]
-    StringParseError
+    StringParseError,
+    #[error("Local time '{0}' is ambiguous in the given time zone")]
+    AmbiguousTime(String),
+    #[error("Local time '{0}' does not exist in the given time zone")]
+    NonExistentTime(String),
+}
+
+/// Date formats accepted by `parse_date`
+const DATE_FORMATS: [&str; 4] = ["%Y-%m-%d", "%Y%m%d", "%Y/%m/%d", "%d.%m.%Y"];
+
+/// Date-time formats without time zone information accepted by `parse_date_time`
+const DATE_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Parse a date given in ISO-8601 format (`2020-03-08`) or one of the common vendor
+/// formats `20200308`, `2020/03/08` or `08.03.2020`.
+pub fn parse_date(date_str: &str) -> Result<NaiveDate, DateTimeError> {
+    let date_str = date_str.trim();
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date_str, format).ok())
+        .ok_or(DateTimeError::StringParseError)
+}
+
+/// Parse a date-time string and convert it to local time.
+/// Strings with explicit offset (RFC 3339, e.g. `2020-03-08T12:00:00-04:00`) are
+/// converted as given. Otherwise, the time is interpreted in time zone `zone`, or in
+/// the local time zone if no zone is given. Date-only strings (see `parse_date`) refer to
+/// the start of the day. Local times that are ambiguous or skipped due to daylight
+/// saving time changes are reported as error.
+pub fn parse_date_time(
+    time_str: &str,
+    zone: Option<String>,
+) -> Result<DateTime<Local>, DateTimeError> {
+    let time_str = time_str.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(time_str) {
+        return Ok(time.with_timezone(&Local));
+    }
+    let time = DATE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(time_str, format).ok())
+        .map_or_else(
+            || {
+                parse_date(time_str)?
+                    .and_hms_opt(0, 0, 0)
+                    .ok_or(DateTimeError::DateTimeConversionFailed)
+            },
+            Ok,
+        )?;
+    match zone {
+        None => unique_local_time(Local.from_local_datetime(&time), &time),
+        Some(zone) => {
+            let tz: Tz = zone.parse().map_err(|_| DateTimeError::StringParseError)?;
+            let time = unique_local_time(tz.from_local_datetime(&time), &time)?;
+            Ok(time.with_timezone(&Local))
+        }
+    }
+}
+
+fn unique_local_time<Tz: TimeZone>(
+    result: LocalResult<DateTime<Tz>>,
+    time: &NaiveDateTime,
+) -> Result<DateTime<Tz>, DateTimeError> {
+    match result {
+        LocalResult::Single(time) => Ok(time),
+        LocalResult::Ambiguous(_, _) => Err(DateTimeError::AmbiguousTime(time.to_string())),
+        LocalResult::None => Err(DateTimeError::NonExistentTime(time.to_string())),
+    }
 }
 
 
@@ -158,4 +229,34 @@ mod tests {
         let date_string = date.format("%Y-%m-%d %H:%M:%S").to_string();
         assert_eq!("2020-02-10 18:00:00", &date_string);
     }
+
+    #[test]
+    fn test_parse_date() {
+        let date = NaiveDate::from_ymd(2020, 3, 8);
+        assert_eq!(parse_date("2020-03-08").unwrap(), date);
+        assert_eq!(parse_date("20200308").unwrap(), date);
+        assert_eq!(parse_date("2020/03/08").unwrap(), date);
+        assert_eq!(parse_date("08.03.2020").unwrap(), date);
+        assert!(parse_date("03/08/2020").is_err());
+    }
+
+    #[test]
+    fn test_parse_date_time_dst() {
+        let zone = Some("America/New_York".to_string());
+        // DST starts on 2020-03-08 at 2am in US Eastern
+        let time: DateTime<Utc> = parse_date_time("2020-03-08", zone.clone()).unwrap().into();
+        assert_eq!("2020-03-08 05:00:00", time.format("%Y-%m-%d %H:%M:%S").to_string());
+        let time: DateTime<Utc> = parse_date_time("2020-03-08 18:00:00", zone.clone()).unwrap().into();
+        assert_eq!("2020-03-08 22:00:00", time.format("%Y-%m-%d %H:%M:%S").to_string());
+        assert!(matches!(
+            parse_date_time("2020-03-08 02:30:00", zone.clone()),
+            Err(DateTimeError::NonExistentTime(_))
+        ));
+        assert!(matches!(
+            parse_date_time("2020-11-01T01:30", zone.clone()),
+            Err(DateTimeError::AmbiguousTime(_))
+        ));
+        let time: DateTime<Utc> = parse_date_time("2020-11-01T01:30:00-04:00", zone).unwrap().into();
+        assert_eq!("2020-11-01 05:30:00", time.format("%Y-%m-%d %H:%M:%S").to_string());
+    }
 }
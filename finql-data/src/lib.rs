@@ -11,6 +11,8 @@ pub mod quote_handler;
 pub mod transaction_handler;
 pub mod transaction;
 pub mod object_handler;
+#[cfg(feature = "binary")]
+pub mod binary;
 
 pub use asset::Asset;
 pub use asset_handler::AssetHandler;
@@ -30,6 +32,10 @@ pub enum DataError {
     DeleteFailed(String),
     InsertFailed(String),
     InvalidTransaction(String),
+    Timeout(String),
+    FxRateMissing(String),
+    InvalidData(String),
+    Stale(String),
 }
 
 impl std::error::Error for DataError {
@@ -47,6 +53,10 @@ impl fmt::Display for DataError {
             Self::DeleteFailed(err) => write!(f, "removing object from database failed: {}", err),
             Self::InsertFailed(err) => write!(f, "inserting object to database failed: {}", err),
             Self::InvalidTransaction(err) => write!(f, "invalid transaction type: {}", err),
+            Self::Timeout(err) => write!(f, "database query timed out: {}", err),
+            Self::FxRateMissing(err) => write!(f, "no fx rate available: {}", err),
+            Self::InvalidData(err) => write!(f, "invalid data stored in database: {}", err),
+            Self::Stale(err) => write!(f, "stored data is too old: {}", err),
         }
     }
 }
@@ -15,6 +15,8 @@ pub struct Ticker {
     pub currency: Currency,
     pub source: String,
     pub priority: i32,
+    /// Factor converting prices as delivered by the market data provider into the ticker's
+    /// currency, e.g. 0.01 for shares quoted in pence whose ticker currency is GBP
     pub factor: f64,
     pub tz: Option<String>,
     pub cal: Option<String>
@@ -27,6 +29,22 @@ pub struct Quote {
     pub price: f64,
     pub time: DateTime<Local>,
     pub volume: Option<f64>,
+    #[serde(default)]
+    pub bid: Option<f64>,
+    #[serde(default)]
+    pub ask: Option<f64>,
+}
+
+impl Quote {
+    /// Mid price between bid and ask, falling back to the quoted price if bid or ask are unknown.
+    /// Returns `None` if bid and ask are crossed, i.e. the bid exceeds the ask.
+    pub fn mid(&self) -> Option<f64> {
+        match (self.bid, self.ask) {
+            (Some(bid), Some(ask)) if bid > ask => None,
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+            _ => Some(self.price),
+        }
+    }
 }
 
 impl Ord for Quote {
@@ -1,7 +1,8 @@
 ///! Data handler trait for market quotes
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local};
 use async_trait::async_trait;
+use futures::stream::BoxStream;
 use std::sync::Arc;
 
 use super::AssetHandler;
@@ -19,6 +20,8 @@ pub trait QuoteHandler: AssetHandler {
     async fn get_ticker_id(&self, ticker: &str) -> Option<usize>;
     async fn insert_if_new_ticker(&self, ticker: &Ticker) -> Result<usize, DataError>;
     async fn get_ticker_by_id(&self, id: usize) -> Result<Ticker, DataError>;
+    /// Get a ticker by its name, returns `DataError::NotFound` if there is no such ticker
+    async fn get_ticker_by_name(&self, name: &str) -> Result<Ticker, DataError>;
     async fn get_all_ticker(&self) -> Result<Vec<Ticker>, DataError>;
     async fn get_all_ticker_for_source(
         &self,
@@ -32,11 +35,28 @@ pub trait QuoteHandler: AssetHandler {
     ) -> Result<Vec<Ticker>, DataError>;
 
     async fn update_ticker(&self, ticker: &Ticker) -> Result<(), DataError>;
+    /// Delete a ticker together with all its quotes within a single transaction
     async fn delete_ticker(&self, id: usize) -> Result<(), DataError>;
+    /// Number of quotes stored for a ticker, e.g. to check what `delete_ticker` would remove
+    async fn count_quotes_for_ticker(&self, ticker_id: usize) -> Result<usize, DataError>;
 
     /// Insert, get, update and delete for market data sources
     async fn insert_quote(&self, quote: &Quote) -> Result<usize, DataError>;
 
+    /// Insert several quotes at once within a single transaction, e.g. for backfills.
+    /// Returns the ids of the new quotes in the order of `quotes`. If any quote could not
+    /// be inserted, none of the quotes is stored.
+    async fn insert_quotes(&self, quotes: &[Quote]) -> Result<Vec<usize>, DataError>;
+
+    /// Insert a quote or, if a quote for the same ticker and time is already stored,
+    /// overwrite its price, volume, bid and ask. Returns the id of the inserted or updated quote.
+    async fn upsert_quote(&self, quote: &Quote) -> Result<usize, DataError>;
+
+    /// Upsert several quotes at once within a single transaction like `upsert_quote`, e.g. for
+    /// quote histories overlapping the stored ones. Returns the ids of the inserted or updated
+    /// quotes in the order of `quotes`. If any quote could not be stored, none of the quotes is stored.
+    async fn upsert_quotes(&self, quotes: &[Quote]) -> Result<Vec<usize>, DataError>;
+
     /// Get the last quote in database for a specific asset name on or before the given time
     async fn get_last_quote_before(
         &self,
@@ -44,6 +64,13 @@ pub trait QuoteHandler: AssetHandler {
         time: DateTime<Local>,
     ) -> Result<(Quote, Currency), DataError>;
 
+    /// Get the first quote in database for a specific asset name on or after the given time
+    async fn get_first_quote_after(
+        &self,
+        asset_name: &str,
+        time: DateTime<Local>,
+    ) -> Result<(Quote, Currency), DataError>;
+
     /// Get the last quote in database for a specific asset id on or before the given time
     async fn get_last_quote_before_by_id(
         &self,
@@ -51,7 +78,37 @@ pub trait QuoteHandler: AssetHandler {
         time: DateTime<Local>,
     ) -> Result<(Quote, Currency), DataError>;
 
+    /// Get the last quote in database of a specific ticker on or before the given time
+    async fn get_last_quote_before_for_ticker(
+        &self,
+        ticker_id: usize,
+        time: DateTime<Local>,
+    ) -> Result<Quote, DataError>;
+
+    /// Get the most recent quote in database for a specific asset id, regardless of its time.
+    /// Among quotes at the same time, the ticker with the highest priority wins.
+    async fn get_latest_quote(&self, asset_id: usize) -> Result<(Quote, Currency), DataError>;
+
+    /// Get the quote of a specific ticker closest to the given time, either before or after.
+    /// Returns the quote and its absolute distance to `time`, or `None` if no quote
+    /// lies within `max_gap` of the given time.
+    async fn get_nearest_quote(
+        &self,
+        ticker_id: usize,
+        time: DateTime<Local>,
+        max_gap: Duration,
+    ) -> Result<Option<(Quote, Duration)>, DataError>;
+
     async fn get_all_quotes_for_ticker(&self, ticker_id: usize) -> Result<Vec<Quote>, DataError>;
+    /// Get all quotes of a ticker between `start` and `end` (both inclusive), sorted by time
+    async fn get_quotes_for_ticker_in_range(
+        &self,
+        ticker_id: usize,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<Quote>, DataError>;
+    /// Stream all quotes of a ticker in time order, without loading the full history into memory
+    fn stream_quotes_for_ticker(&self, ticker_id: usize) -> BoxStream<'_, Result<Quote, DataError>>;
     async fn update_quote(&self, quote: &Quote) -> Result<(), DataError>;
     async fn delete_quote(&self, id: usize) -> Result<(), DataError>;
     async fn remove_duplicates(&self) -> Result<(), DataError>;
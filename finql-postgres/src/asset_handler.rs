@@ -5,7 +5,7 @@ use finql_data::asset::Asset;
 use finql_data::{AssetHandler, DataError};
 use finql_data::currency::Currency;
 
-use super::PostgresDB;
+use super::{map_sqlx_error, PostgresDB};
 
 /// helper struct
 struct ID { id: i32, }
@@ -19,7 +19,7 @@ impl AssetHandler for PostgresDB {
                 "INSERT INTO assets (name, wkn, isin, note) VALUES ($1, $2, $3, $4) RETURNING id",
                 asset.name, asset.wkn, asset.isin, asset.note,
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         let id = row.id;
         Ok(id as usize)
     }
@@ -67,7 +67,7 @@ impl AssetHandler for PostgresDB {
                 "SELECT name, wkn, isin, note FROM assets WHERE id=$1",
                 (id as i32),
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
         Ok(Asset {
             id: Some(id),
             name: row.name,
@@ -82,7 +82,7 @@ impl AssetHandler for PostgresDB {
                 "SELECT id, name, wkn, note FROM assets WHERE isin=$1",
                 isin.to_string(),
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
         let id: i32 = row.id;
         Ok(Asset {
             id: Some(id as usize),
@@ -97,7 +97,7 @@ impl AssetHandler for PostgresDB {
         let mut assets = Vec::new();
         for row in sqlx::query!("SELECT id, name, wkn, isin, note FROM assets ORDER BY name")
             .fetch_all(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?
         {
             let id: i32 = row.id;
             let id = Some(id as usize);
@@ -124,14 +124,14 @@ impl AssetHandler for PostgresDB {
                 WHERE id=$1;",
                 id, asset.name, asset.wkn, asset.isin, asset.note,
             ).execute(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         Ok(())
     }
 
     async fn delete_asset(&self, id: usize) -> Result<(), DataError> {
         sqlx::query!("DELETE FROM assets WHERE id=$1;", (id as i32))
             .execute(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         Ok(())
     }
 
@@ -139,11 +139,11 @@ impl AssetHandler for PostgresDB {
         let mut currencies = Vec::new();
         for row in sqlx::query!("SELECT name FROM assets WHERE isin IS NULL AND wkn IS NULL AND length(name)=3")
             .fetch_all(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?
         {
             let currency = row.name;
             let currency =
-                Currency::from_str(&currency).map_err(|e| DataError::NotFound(e.to_string()))?;
+                Currency::from_str(&currency).map_err(|e| DataError::InvalidData(e.to_string()))?;
             currencies.push(currency);
         }
         Ok(currencies)
@@ -1,6 +1,11 @@
 ///! Implementation of PostgreSQL data handler
+use std::time::Duration;
+
+use sqlx::Executor;
 use sqlx::postgres::{PgPoolOptions, Postgres};
 
+use finql_data::DataError;
+
 pub mod asset_handler;
 pub mod quote_handler;
 pub mod transaction_handler;
@@ -21,6 +26,24 @@ impl PostgresDB {
         Ok(PostgresDB { pool })
     }
 
+    /// Connect to database with an upper limit on the execution time of each query.
+    /// Queries exceeding the limit are cancelled and return `DataError::Timeout`.
+    pub async fn new_with_timeout(connection_string: &str, timeout: Duration) -> Result<PostgresDB, sqlx::Error> {
+        let statement_timeout = format!("SET statement_timeout = {}", timeout.as_millis());
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .after_connect(move |conn| {
+                let statement_timeout = statement_timeout.clone();
+                Box::pin(async move {
+                    conn.execute(statement_timeout.as_str()).await?;
+                    Ok(())
+                })
+            })
+            .connect(connection_string)
+            .await?;
+        Ok(PostgresDB { pool })
+    }
+
     /// Clean database by dropping all tables and than run init
     pub async fn clean(&self) -> Result<(), sqlx::Error> {
         sqlx::query!("DROP TABLE IF EXISTS transactions")
@@ -98,11 +121,33 @@ impl PostgresDB {
                 price FLOAT8 NOT NULL,
                 time TIMESTAMP WITH TIME ZONE NOT NULL,
                 volume FLOAT8,
+                bid FLOAT8,
+                ask FLOAT8,
                 FOREIGN KEY(ticker_id) REFERENCES ticker(id) 
             )"
         )
         .execute(&self.pool)
         .await?;
+        // Databases created before bid and ask prices were supported lack these columns
+        sqlx::query!("ALTER TABLE quotes ADD COLUMN IF NOT EXISTS bid FLOAT8")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query!("ALTER TABLE quotes ADD COLUMN IF NOT EXISTS ask FLOAT8")
+            .execute(&self.pool)
+            .await?;
+        // Required by `upsert_quote`. Existing databases may contain several quotes of the
+        // same ticker at the same time, of which only the latest inserted one is kept.
+        sqlx::query!(
+            "DELETE FROM quotes q1 USING quotes q2
+            WHERE q1.id < q2.id AND q1.ticker_id = q2.ticker_id AND q1.time = q2.time"
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            "CREATE UNIQUE INDEX IF NOT EXISTS quotes_ticker_id_time_idx ON quotes (ticker_id, time)"
+        )
+        .execute(&self.pool)
+        .await?;
 
         sqlx::query!(
             "CREATE TABLE IF NOT EXISTS rounding_digits (
@@ -125,3 +170,184 @@ impl PostgresDB {
         Ok(())
     }
 }
+
+/// PostgreSQL error code for queries cancelled due to `statement_timeout`
+const QUERY_CANCELED: &str = "57014";
+
+/// Convert sqlx errors to `DataError`, mapping timeouts to `DataError::Timeout`
+/// and all other errors with the given `default` constructor
+pub(crate) fn map_sqlx_error(err: sqlx::Error, default: fn(String) -> DataError) -> DataError {
+    match &err {
+        sqlx::Error::PoolTimedOut => DataError::Timeout(err.to_string()),
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some(QUERY_CANCELED) => {
+            DataError::Timeout(err.to_string())
+        }
+        _ => default(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_error_mapping() {
+        assert!(matches!(map_sqlx_error(sqlx::Error::PoolTimedOut, DataError::NotFound), DataError::Timeout(_)));
+        assert!(matches!(map_sqlx_error(sqlx::Error::RowNotFound, DataError::NotFound), DataError::NotFound(_)));
+    }
+
+    /// Requires a running PostgreSQL database given by the environment variable `DATABASE_URL`
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_query_timeout() {
+        let url = std::env::var("DATABASE_URL").unwrap();
+        let db = PostgresDB::new_with_timeout(&url, Duration::from_millis(100)).await.unwrap();
+        let result = sqlx::query("SELECT pg_sleep(1)")
+            .execute(&db.pool).await
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound));
+        assert!(matches!(result, Err(DataError::Timeout(_))));
+    }
+
+    /// Requires a running PostgreSQL database given by the environment variable `DATABASE_URL`
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_init_adds_missing_quote_columns() {
+        let url = std::env::var("DATABASE_URL").unwrap();
+        let db = PostgresDB::new(&url).await.unwrap();
+        db.clean().await.unwrap();
+        // quotes table as created before bid and ask prices were added
+        sqlx::query("DROP TABLE quotes").execute(&db.pool).await.unwrap();
+        sqlx::query(
+            "CREATE TABLE quotes (
+                id SERIAL PRIMARY KEY,
+                ticker_id INTEGER NOT NULL,
+                price FLOAT8 NOT NULL,
+                time TIMESTAMP WITH TIME ZONE NOT NULL,
+                volume FLOAT8,
+                FOREIGN KEY(ticker_id) REFERENCES ticker(id)
+            )",
+        ).execute(&db.pool).await.unwrap();
+        db.init().await.unwrap();
+        db.init().await.unwrap();
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT column_name::TEXT FROM information_schema.columns
+            WHERE table_name = 'quotes' AND column_name IN ('bid', 'ask') ORDER BY column_name",
+        ).fetch_all(&db.pool).await.unwrap();
+        assert_eq!(columns, vec![("ask".to_string(),), ("bid".to_string(),)]);
+    }
+
+    /// Requires a running PostgreSQL database given by the environment variable `DATABASE_URL`
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_init_removes_duplicate_quotes() {
+        let url = std::env::var("DATABASE_URL").unwrap();
+        let db = PostgresDB::new(&url).await.unwrap();
+        db.clean().await.unwrap();
+        // quotes table as created before quotes were unique per ticker and time
+        sqlx::query("DROP TABLE quotes").execute(&db.pool).await.unwrap();
+        sqlx::query(
+            "CREATE TABLE quotes (
+                id SERIAL PRIMARY KEY,
+                ticker_id INTEGER NOT NULL,
+                price FLOAT8 NOT NULL,
+                time TIMESTAMP WITH TIME ZONE NOT NULL,
+                volume FLOAT8,
+                bid FLOAT8,
+                ask FLOAT8
+            )",
+        ).execute(&db.pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO quotes (ticker_id, price, time) VALUES
+                (1, 1.0, '2021-12-06 12:00:00+01'),
+                (1, 2.0, '2021-12-06 12:00:00+01'),
+                (1, 3.0, '2021-12-07 12:00:00+01'),
+                (2, 4.0, '2021-12-06 12:00:00+01')",
+        ).execute(&db.pool).await.unwrap();
+        db.init().await.unwrap();
+        let prices: Vec<(f64,)> = sqlx::query_as("SELECT price FROM quotes ORDER BY id")
+            .fetch_all(&db.pool).await.unwrap();
+        assert_eq!(prices, vec![(2.0,), (3.0,), (4.0,)]);
+    }
+
+    /// Requires a running PostgreSQL database given by the environment variable `DATABASE_URL`
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_insert_quotes() {
+        use std::str::FromStr;
+        use finql_data::{Asset, AssetHandler, Currency, Quote, QuoteHandler, Ticker};
+        use finql_data::date_time_helper::make_time;
+
+        let url = std::env::var("DATABASE_URL").unwrap();
+        let db = PostgresDB::new(&url).await.unwrap();
+        db.clean().await.unwrap();
+        let asset = db.insert_asset(&Asset { id: None, name: "A".to_string(), wkn: None, isin: None, note: None })
+            .await.unwrap();
+        let ticker = db.insert_ticker(&Ticker {
+            id: None,
+            asset,
+            name: "A".to_string(),
+            currency: Currency::from_str("EUR").unwrap(),
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        }).await.unwrap();
+        assert_eq!(db.get_ticker_by_name("A").await.unwrap().id, Some(ticker));
+        assert!(matches!(db.get_ticker_by_name("B").await, Err(DataError::NotFound(_))));
+        let quotes: Vec<Quote> = [6, 7, 8].iter().map(|day| Quote {
+            id: None,
+            ticker,
+            price: *day as f64,
+            time: make_time(2021, 12, *day, 12, 0, 0).unwrap(),
+            volume: Some(100.0),
+            bid: None,
+            ask: None,
+        }).collect();
+        let ids = db.insert_quotes(&quotes).await.unwrap();
+        let stored = db.get_all_quotes_for_ticker(ticker).await.unwrap();
+        assert_eq!(ids, stored.iter().map(|q| q.id.unwrap()).collect::<Vec<usize>>());
+        assert!(db.insert_quotes(&[]).await.unwrap().is_empty());
+
+        let mut quote = quotes[1].clone();
+        quote.price = 7.5;
+        assert_eq!(db.upsert_quote(&quote).await.unwrap(), ids[1]);
+        quote.time = make_time(2021, 12, 9, 12, 0, 0).unwrap();
+        let new_id = db.upsert_quote(&quote).await.unwrap();
+        let stored = db.get_all_quotes_for_ticker(ticker).await.unwrap();
+        assert_eq!(stored.len(), 4);
+        assert_eq!(stored[1].price, 7.5);
+        assert_eq!(stored[3].id, Some(new_id));
+        assert_eq!(db.get_latest_quote(asset).await.unwrap().0.id, Some(new_id));
+
+        // batch overlapping the stored quotes
+        let mut next = quote.clone();
+        next.time = make_time(2021, 12, 10, 12, 0, 0).unwrap();
+        quote.price = 9.5;
+        let upserted = db.upsert_quotes(&[quote, next]).await.unwrap();
+        assert_eq!(upserted[0], new_id);
+        let stored = db.get_all_quotes_for_ticker(ticker).await.unwrap();
+        assert_eq!(stored.len(), 5);
+        assert_eq!(stored[3].price, 9.5);
+        assert_eq!(stored[4].id, Some(upserted[1]));
+
+        assert_eq!(db.count_quotes_for_ticker(ticker).await.unwrap(), 5);
+        db.delete_ticker(ticker).await.unwrap();
+        assert_eq!(db.count_quotes_for_ticker(ticker).await.unwrap(), 0);
+    }
+
+    /// Requires a running PostgreSQL database given by the environment variable `DATABASE_URL`
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_rounding_digits() {
+        use std::str::FromStr;
+        use finql_data::{Currency, QuoteHandler};
+
+        let url = std::env::var("DATABASE_URL").unwrap();
+        let db = PostgresDB::new(&url).await.unwrap();
+        db.clean().await.unwrap();
+        db.set_rounding_digits(Currency::from_str("JPY").unwrap(), 0).await.unwrap();
+        assert_eq!(db.get_rounding_digits(Currency::from_str("JPY").unwrap()).await, 0);
+        assert_eq!(db.get_rounding_digits(Currency::from_str("EUR").unwrap()).await, 2);
+    }
+}
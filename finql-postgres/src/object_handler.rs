@@ -5,7 +5,7 @@ use serde::{de::DeserializeOwned, Serialize};
 use serde_json;
 
 use finql_data::{ObjectHandler, DataError};
-use super::PostgresDB;
+use super::{map_sqlx_error, PostgresDB};
 
 /// Handler for globally available Asset data
 #[async_trait]
@@ -18,7 +18,7 @@ impl ObjectHandler for PostgresDB {
             "INSERT INTO objects (id, object) VALUES ($1, $2)",
             id, object_json
         ).execute(&self.pool).await
-        .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+        .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         Ok(())
     }
 
@@ -27,7 +27,7 @@ impl ObjectHandler for PostgresDB {
                 "SELECT object FROM objects WHERE id=$1",
                 id
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
         let object: T = serde_json::from_value(row.object)
         .map_err(|_| DataError::DataAccessFailure("Failed to deserialize string to object".to_string()))?;
         Ok(object)
@@ -1,14 +1,15 @@
 ///! Implementation for quote handler with Sqlite3 database as backend
 use std::str::FromStr;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local, Utc};
 use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
 use std::sync::Arc;
 
 use finql_data::currency::Currency;
 use finql_data::{DataError, QuoteHandler, AssetHandler};
 use finql_data::quote::{Quote, Ticker};
 
-use super::PostgresDB;
+use super::{map_sqlx_error, PostgresDB};
 
 /// PostgreSQL implementation of quote handler
 #[async_trait]
@@ -31,7 +32,7 @@ impl QuoteHandler for PostgresDB {
                 ticker.tz,
                 ticker.cal
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         let id: i32 = row.id;
         Ok(id as usize)
     }
@@ -60,13 +61,13 @@ impl QuoteHandler for PostgresDB {
                 "SELECT name, asset_id, source, priority, currency, factor, tz, cal FROM ticker WHERE id=$1",
                 (id as i32),
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
         let name = row.name;
         let asset = row.asset_id;
         let source = row.source;
         let currency = row.currency;
         let currency =
-            Currency::from_str(&currency).map_err(|e| DataError::NotFound(e.to_string()))?;
+            Currency::from_str(&currency).map_err(|e| DataError::InvalidData(e.to_string()))?;
         Ok(Ticker {
             id: Some(id),
             name,
@@ -80,19 +81,40 @@ impl QuoteHandler for PostgresDB {
         })
     }
 
+    async fn get_ticker_by_name(&self, name: &str) -> Result<Ticker, DataError> {
+        let row = sqlx::query!(
+                "SELECT id, name, asset_id, source, priority, currency, factor, tz, cal FROM ticker WHERE name=$1",
+                name,
+            ).fetch_one(&self.pool).await
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
+        let currency =
+            Currency::from_str(&row.currency).map_err(|e| DataError::InvalidData(e.to_string()))?;
+        Ok(Ticker {
+            id: Some(row.id as usize),
+            name: row.name,
+            asset: row.asset_id as usize,
+            source: row.source,
+            priority: row.priority,
+            currency,
+            factor: row.factor,
+            tz: row.tz,
+            cal: row.cal,
+        })
+    }
+
     async fn get_all_ticker(&self) -> Result<Vec<Ticker>, DataError> {
         let mut all_ticker = Vec::new();
         for row in sqlx::query!(
                 "SELECT id, name, asset_id, priority, source, currency, factor, tz, cal FROM ticker",
             ).fetch_all(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?
         {
             let id = row.id;
             let asset = row.asset_id;
             let source = row.source;
             let currency = row.currency;
             let currency =
-                Currency::from_str(&currency).map_err(|e| DataError::NotFound(e.to_string()))?;
+                Currency::from_str(&currency).map_err(|e| DataError::InvalidData(e.to_string()))?;
             let factor = row.factor;
             all_ticker.push(Ticker {
                 id: Some(id as usize),
@@ -118,13 +140,13 @@ impl QuoteHandler for PostgresDB {
                 "SELECT id, name, asset_id, priority, currency, factor, tz, cal FROM ticker WHERE source=$1",
                 (source.to_string()),
             ).fetch_all(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?
         {
             let id = row.id;
             let asset = row.asset_id;
             let currency = row.currency;
             let currency =
-                Currency::from_str(&currency).map_err(|e| DataError::NotFound(e.to_string()))?;
+                Currency::from_str(&currency).map_err(|e| DataError::InvalidData(e.to_string()))?;
             let factor = row.factor;
             all_ticker.push(Ticker {
                 id: Some(id as usize),
@@ -150,13 +172,13 @@ impl QuoteHandler for PostgresDB {
                 "SELECT id, name, source, priority, currency, factor, tz, cal FROM ticker WHERE asset_id=$1",
                 (asset_id as i32),
             ).fetch_all(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?
         {
             let id = row.id;
             let source = row.source;
             let currency = row.currency;
             let currency =
-                Currency::from_str(&currency).map_err(|e| DataError::NotFound(e.to_string()))?;
+                Currency::from_str(&currency).map_err(|e| DataError::InvalidData(e.to_string()))?;
             let factor: f64 = row.factor;
             all_ticker.push(Ticker {
                 id: Some(id as usize),
@@ -195,54 +217,188 @@ impl QuoteHandler for PostgresDB {
                 ticker.cal
             )
             .execute(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         Ok(())
     }
 
     async fn delete_ticker(&self, id: usize) -> Result<(), DataError> {
+        let mut tx = self.pool.begin().await
+            .map_err(|e| map_sqlx_error(e, DataError::DeleteFailed))?;
+        sqlx::query!("DELETE FROM quotes WHERE ticker_id=$1;", (id as i32))
+            .execute(&mut tx).await
+            .map_err(|e| map_sqlx_error(e, DataError::DeleteFailed))?;
         sqlx::query!("DELETE FROM ticker WHERE id=$1;", (id as i32))
-            .execute(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .execute(&mut tx).await
+            .map_err(|e| map_sqlx_error(e, DataError::DeleteFailed))?;
+        tx.commit().await
+            .map_err(|e| map_sqlx_error(e, DataError::DeleteFailed))?;
         Ok(())
     }
 
+    async fn count_quotes_for_ticker(&self, ticker_id: usize) -> Result<usize, DataError> {
+        let row = sqlx::query!(
+                "SELECT COUNT(*) AS \"count!\" FROM quotes WHERE ticker_id=$1",
+                (ticker_id as i32),
+            ).fetch_one(&self.pool).await
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
+        Ok(row.count as usize)
+    }
+
     // insert, get, update and delete for market data sources
     async fn insert_quote(&self, quote: &Quote) -> Result<usize, DataError> {
         let row = sqlx::query!(
-                "INSERT INTO quotes (ticker_id, price, time, volume) 
-                VALUES ($1, $2, $3, $4) RETURNING id",
+                "INSERT INTO quotes (ticker_id, price, time, volume, bid, ask) 
+                VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
                 (quote.ticker as i32),
                 quote.price,
                 quote.time,
                 quote.volume,
+                quote.bid,
+                quote.ask,
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         let id = row.id;
         Ok(id as usize)
     }
 
+    async fn upsert_quote(&self, quote: &Quote) -> Result<usize, DataError> {
+        let row = sqlx::query!(
+                "INSERT INTO quotes (ticker_id, price, time, volume, bid, ask) 
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (ticker_id, time) DO UPDATE
+                SET price=EXCLUDED.price, volume=EXCLUDED.volume, bid=EXCLUDED.bid, ask=EXCLUDED.ask
+                RETURNING id",
+                (quote.ticker as i32),
+                quote.price,
+                quote.time,
+                quote.volume,
+                quote.bid,
+                quote.ask,
+            ).fetch_one(&self.pool).await
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
+        Ok(row.id as usize)
+    }
+
+    async fn insert_quotes(&self, quotes: &[Quote]) -> Result<Vec<usize>, DataError> {
+        if quotes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let tickers: Vec<i32> = quotes.iter().map(|q| q.ticker as i32).collect();
+        let prices: Vec<f64> = quotes.iter().map(|q| q.price).collect();
+        let times: Vec<DateTime<Utc>> = quotes.iter().map(|q| q.time.with_timezone(&Utc)).collect();
+        let volumes: Vec<Option<f64>> = quotes.iter().map(|q| q.volume).collect();
+        let bids: Vec<Option<f64>> = quotes.iter().map(|q| q.bid).collect();
+        let asks: Vec<Option<f64>> = quotes.iter().map(|q| q.ask).collect();
+        let mut tx = self.pool.begin().await
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
+        // ids are returned in the order of the unnested arrays
+        let rows = sqlx::query!(
+                "INSERT INTO quotes (ticker_id, price, time, volume, bid, ask) 
+                SELECT * FROM UNNEST($1::INTEGER[], $2::FLOAT8[], $3::TIMESTAMPTZ[],
+                    $4::FLOAT8[], $5::FLOAT8[], $6::FLOAT8[])
+                RETURNING id",
+                &tickers,
+                &prices,
+                &times,
+                &volumes as &[Option<f64>],
+                &bids as &[Option<f64>],
+                &asks as &[Option<f64>],
+            ).fetch_all(&mut tx).await
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
+        tx.commit().await
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
+        Ok(rows.into_iter().map(|row| row.id as usize).collect())
+    }
+
+    async fn upsert_quotes(&self, quotes: &[Quote]) -> Result<Vec<usize>, DataError> {
+        let mut tx = self.pool.begin().await
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
+        // quotes are upserted one by one, since a single statement can't update the same row twice
+        let mut ids = Vec::with_capacity(quotes.len());
+        for quote in quotes {
+            let row = sqlx::query!(
+                    "INSERT INTO quotes (ticker_id, price, time, volume, bid, ask) 
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    ON CONFLICT (ticker_id, time) DO UPDATE
+                    SET price=EXCLUDED.price, volume=EXCLUDED.volume, bid=EXCLUDED.bid, ask=EXCLUDED.ask
+                    RETURNING id",
+                    (quote.ticker as i32),
+                    quote.price,
+                    quote.time,
+                    quote.volume,
+                    quote.bid,
+                    quote.ask,
+                ).fetch_one(&mut tx).await
+                .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
+            ids.push(row.id as usize);
+        }
+        tx.commit().await
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
+        Ok(ids)
+    }
+
     async fn get_last_quote_before(
         &self,
         asset_name: &str,
         time: DateTime<Local>,
     ) -> Result<(Quote, Currency), DataError> {
         let row = sqlx::query!(
-                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, t.currency, t.priority
+                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, q.bid, q.ask, t.currency, t.priority
                 FROM quotes q, ticker t, assets a 
                 WHERE a.name=$1 AND t.asset_id=a.id AND t.id=q.ticker_id AND q.time<= $2
                 ORDER BY q.time DESC, t.priority ASC LIMIT 1",
                 asset_name, time,
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
+
+        let id = row.id;
+        let ticker = row.ticker_id;
+        let price = row.price;
+        let time: DateTime<Local> = row.time.into();
+        let volume = row.volume;
+        let bid = row.bid;
+        let ask = row.ask;
+        let currency = row.currency;
+        let currency =
+            Currency::from_str(&currency).map_err(|e| DataError::InvalidData(e.to_string()))?;
+        Ok((
+            Quote {
+                id: Some(id as usize),
+                ticker: ticker as usize,
+                price,
+                time,
+                volume,
+                bid,
+                ask,
+            },
+            currency,
+        ))
+    }
+
+    async fn get_first_quote_after(
+        &self,
+        asset_name: &str,
+        time: DateTime<Local>,
+    ) -> Result<(Quote, Currency), DataError> {
+        let row = sqlx::query!(
+                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, q.bid, q.ask, t.currency, t.priority
+                FROM quotes q, ticker t, assets a 
+                WHERE a.name=$1 AND t.asset_id=a.id AND t.id=q.ticker_id AND q.time>= $2
+                ORDER BY q.time ASC, t.priority ASC LIMIT 1",
+                asset_name, time,
+            ).fetch_one(&self.pool).await
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
 
         let id = row.id;
         let ticker = row.ticker_id;
         let price = row.price;
         let time: DateTime<Local> = row.time.into();
         let volume = row.volume;
+        let bid = row.bid;
+        let ask = row.ask;
         let currency = row.currency;
         let currency =
-            Currency::from_str(&currency).map_err(|e| DataError::NotFound(e.to_string()))?;
+            Currency::from_str(&currency).map_err(|e| DataError::InvalidData(e.to_string()))?;
         Ok((
             Quote {
                 id: Some(id as usize),
@@ -250,6 +406,8 @@ impl QuoteHandler for PostgresDB {
                 price,
                 time,
                 volume,
+                bid,
+                ask,
             },
             currency,
         ))
@@ -261,22 +419,24 @@ impl QuoteHandler for PostgresDB {
         time: DateTime<Local>,
     ) -> Result<(Quote, Currency), DataError> {
         let row = sqlx::query!(
-                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, t.currency, t.priority
+                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, q.bid, q.ask, t.currency, t.priority
                 FROM quotes q, ticker t
                 WHERE t.asset_id=$1 AND t.id=q.ticker_id AND q.time<= $2
                 ORDER BY q.time DESC, t.priority ASC LIMIT 1",
                 (asset_id as i32), time,
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
 
         let id = row.id;
         let ticker = row.ticker_id;
         let price = row.price;
         let time: DateTime<Local> = row.time.into();
         let volume = row.volume;
+        let bid = row.bid;
+        let ask = row.ask;
         let currency = row.currency;
         let currency =
-            Currency::from_str(&currency).map_err(|e| DataError::NotFound(e.to_string()))?;
+            Currency::from_str(&currency).map_err(|e| DataError::InvalidData(e.to_string()))?;
         Ok((
             Quote {
                 id: Some(id as usize),
@@ -284,19 +444,119 @@ impl QuoteHandler for PostgresDB {
                 price,
                 time,
                 volume,
+                bid,
+                ask,
             },
             currency,
         ))
     }
 
+    async fn get_last_quote_before_for_ticker(
+        &self,
+        ticker_id: usize,
+        time: DateTime<Local>,
+    ) -> Result<Quote, DataError> {
+        let row = sqlx::query!(
+                "SELECT id, price, time, volume, bid, ask FROM quotes
+                WHERE ticker_id=$1 AND time<=$2 ORDER BY time DESC LIMIT 1",
+                (ticker_id as i32), time,
+            ).fetch_one(&self.pool).await
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
+        Ok(Quote {
+            id: Some(row.id as usize),
+            ticker: ticker_id,
+            price: row.price,
+            time: row.time.into(),
+            volume: row.volume,
+            bid: row.bid,
+            ask: row.ask,
+        })
+    }
+
+    async fn get_latest_quote(&self, asset_id: usize) -> Result<(Quote, Currency), DataError> {
+        let row = sqlx::query!(
+                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, q.bid, q.ask, t.currency, t.priority
+                FROM quotes q, ticker t
+                WHERE t.asset_id=$1 AND t.id=q.ticker_id
+                ORDER BY q.time DESC, t.priority ASC LIMIT 1",
+                (asset_id as i32),
+            ).fetch_one(&self.pool).await
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
+        let currency =
+            Currency::from_str(&row.currency).map_err(|e| DataError::InvalidData(e.to_string()))?;
+        Ok((
+            Quote {
+                id: Some(row.id as usize),
+                ticker: row.ticker_id as usize,
+                price: row.price,
+                time: row.time.into(),
+                volume: row.volume,
+                bid: row.bid,
+                ask: row.ask,
+            },
+            currency,
+        ))
+    }
+
+    async fn get_nearest_quote(
+        &self,
+        ticker_id: usize,
+        time: DateTime<Local>,
+        max_gap: Duration,
+    ) -> Result<Option<(Quote, Duration)>, DataError> {
+        let mut quotes = Vec::new();
+        let before = sqlx::query!(
+                "SELECT id, price, time, volume, bid, ask FROM quotes
+                WHERE ticker_id=$1 AND time<=$2 ORDER BY time DESC LIMIT 1",
+                (ticker_id as i32), time,
+            ).fetch_optional(&self.pool).await
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
+        if let Some(row) = before {
+            quotes.push(Quote {
+                id: Some(row.id as usize),
+                ticker: ticker_id,
+                price: row.price,
+                time: row.time.into(),
+                volume: row.volume,
+                bid: row.bid,
+                ask: row.ask,
+            });
+        }
+        let after = sqlx::query!(
+                "SELECT id, price, time, volume, bid, ask FROM quotes
+                WHERE ticker_id=$1 AND time>=$2 ORDER BY time ASC LIMIT 1",
+                (ticker_id as i32), time,
+            ).fetch_optional(&self.pool).await
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
+        if let Some(row) = after {
+            quotes.push(Quote {
+                id: Some(row.id as usize),
+                ticker: ticker_id,
+                price: row.price,
+                time: row.time.into(),
+                volume: row.volume,
+                bid: row.bid,
+                ask: row.ask,
+            });
+        }
+
+        let nearest = quotes.into_iter()
+            .map(|quote| {
+                let gap = if quote.time > time { quote.time - time } else { time - quote.time };
+                (quote, gap)
+            })
+            .min_by_key(|(_, gap)| *gap);
+        Ok(nearest.filter(|(_, gap)| *gap <= max_gap))
+    }
+
     async fn get_all_quotes_for_ticker(&self, ticker_id: usize) -> Result<Vec<Quote>, DataError> {
         let mut quotes = Vec::new();
         for row in sqlx::query!(
-                "SELECT id, price, time, volume FROM quotes 
+                "SELECT id, price, time, volume, bid, ask FROM quotes 
                 WHERE ticker_id=$1 ORDER BY time ASC;",
                 (ticker_id as i32),
             ).fetch_all(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?
         {
             let id = row.id;
             let time = row.time.into();
@@ -306,11 +566,62 @@ impl QuoteHandler for PostgresDB {
                 price: row.price,
                 time,
                 volume: row.volume,
+                bid: row.bid,
+                ask: row.ask,
+            });
+        }
+        Ok(quotes)
+    }
+
+    async fn get_quotes_for_ticker_in_range(
+        &self,
+        ticker_id: usize,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<Quote>, DataError> {
+        let mut quotes = Vec::new();
+        for row in sqlx::query!(
+                "SELECT id, price, time, volume, bid, ask FROM quotes 
+                WHERE ticker_id=$1 AND time BETWEEN $2 AND $3 ORDER BY time ASC;",
+                (ticker_id as i32), start, end,
+            ).fetch_all(&self.pool).await
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?
+        {
+            quotes.push(Quote {
+                id: Some(row.id as usize),
+                ticker: ticker_id,
+                price: row.price,
+                time: row.time.into(),
+                volume: row.volume,
+                bid: row.bid,
+                ask: row.ask,
             });
         }
         Ok(quotes)
     }
 
+    fn stream_quotes_for_ticker(&self, ticker_id: usize) -> BoxStream<'_, Result<Quote, DataError>> {
+        sqlx::query!(
+                "SELECT id, price, time, volume, bid, ask FROM quotes 
+                WHERE ticker_id=$1 ORDER BY time ASC",
+                (ticker_id as i32),
+            )
+            .fetch(&self.pool)
+            .map(move |row| {
+                let row = row.map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
+                Ok(Quote {
+                    id: Some(row.id as usize),
+                    ticker: ticker_id,
+                    price: row.price,
+                    time: row.time.into(),
+                    volume: row.volume,
+                    bid: row.bid,
+                    ask: row.ask,
+                })
+            })
+            .boxed()
+    }
+
     async fn update_quote(&self, quote: &Quote) -> Result<(), DataError> {
         if quote.id.is_none() {
             return Err(DataError::NotFound(
@@ -319,23 +630,25 @@ impl QuoteHandler for PostgresDB {
         }
         let id = quote.id.unwrap() as i32;
         sqlx::query!(
-                "UPDATE quotes SET ticker_id=$2, price=$3, time=$4, volume=$5
+                "UPDATE quotes SET ticker_id=$2, price=$3, time=$4, volume=$5, bid=$6, ask=$7
                 WHERE id=$1",
                 id,
                 (quote.ticker as i32),
                 quote.price,
                 quote.time,
                 quote.volume,
+                quote.bid,
+                quote.ask,
             )
             .execute(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         Ok(())
     }
 
     async fn delete_quote(&self, id: usize) -> Result<(), DataError> {
         sqlx::query!("DELETE FROM quotes WHERE id=$1;", (id as i32))
             .execute(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         Ok(())
     }
 
@@ -354,7 +667,7 @@ impl QuoteHandler for PostgresDB {
             and q1.price = q2.price) 
             ")
             .execute(&self.pool).await
-            .map_err(|e| DataError::DeleteFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::DeleteFailed))?;
         Ok(())
     }
 
@@ -364,14 +677,7 @@ impl QuoteHandler for PostgresDB {
             currency.to_string(),
         ).fetch_all(&self.pool).await;
         match rows {
-            Ok(row_vec) => {
-                if row_vec.is_empty() {
-                    let digits: i32 = row_vec[0].digits;
-                    digits
-                } else {
-                    2
-                }
-            }
+            Ok(row_vec) => row_vec.first().map_or(2, |row| row.digits),
             Err(_) => 2,
         }
     }
@@ -381,7 +687,7 @@ impl QuoteHandler for PostgresDB {
                 "INSERT INTO rounding_digits (currency, digits) VALUES ($1, $2)",
                 currency.to_string(), digits,
             ).execute(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         Ok(())
     }
 }
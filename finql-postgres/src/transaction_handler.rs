@@ -7,7 +7,7 @@ use finql_data::{DataError, TransactionHandler};
 use finql_data::cash_flow::{CashAmount, CashFlow};
 use finql_data::transaction::{Transaction, TransactionType};
 
-use super::PostgresDB;
+use super::{map_sqlx_error, PostgresDB};
 
 
 pub struct RawTransaction {
@@ -33,7 +33,7 @@ const FEE: &str = "f";
 impl RawTransaction {
     pub fn to_transaction(&self) -> Result<Transaction, DataError> {
         let currency = Currency::from_str(&self.cash_currency)
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| DataError::InvalidData(e.to_string()))?;
         let id = self.id.map(|x| x as usize);
         let cash_flow = CashFlow {
             amount: CashAmount {
@@ -145,7 +145,7 @@ impl TransactionHandler for PostgresDB {
                 transaction.position,
                 transaction.note,
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         let id= row.id;
         Ok(id as usize)
     }
@@ -157,7 +157,7 @@ impl TransactionHandler for PostgresDB {
         FROM transactions
         WHERE id=$1", (id as i32),
             ).fetch_one(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?;
         let transaction = RawTransaction {
             id: Some(id as i32),
             trans_type: row.trans_type,
@@ -179,7 +179,7 @@ impl TransactionHandler for PostgresDB {
         cash_amount, cash_currency, cash_date, related_trans, position, note 
         FROM transactions",
             ).fetch_all(&self.pool).await
-            .map_err(|e| DataError::NotFound(e.to_string()))?
+            .map_err(|e| map_sqlx_error(e, DataError::NotFound))?
         {
             let transaction = RawTransaction {
                 id: Some(row.id),
@@ -226,14 +226,14 @@ impl TransactionHandler for PostgresDB {
                 transaction.position,
                 transaction.note,
             ).execute(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         Ok(())
     }
 
     async fn delete_transaction(&self, id: usize) -> Result<(), DataError> {
         sqlx::query!("DELETE FROM transactions WHERE id=$1;", (id as i32))
             .execute(&self.pool).await
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| map_sqlx_error(e, DataError::InsertFailed))?;
         Ok(())
     }
 }
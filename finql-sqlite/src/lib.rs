@@ -128,8 +128,28 @@ impl SqliteDB {
                 price REAL NOT NULL,
                 time TEXT NOT NULL,
                 volume REAL,
+                bid REAL,
+                ask REAL,
                 FOREIGN KEY(ticker_id) REFERENCES ticker(id) 
             )", [])?;
+            // Databases created before bid and ask prices were supported lack these columns
+            let columns = conn
+                .prepare("PRAGMA table_info(quotes)")?
+                .query_map([], |row| row.get::<_, String>(1))?
+                .collect::<Result<Vec<String>, _>>()?;
+            for column in ["bid", "ask"] {
+                if !columns.iter().any(|c| c == column) {
+                    conn.execute(&format!("ALTER TABLE quotes ADD COLUMN {} REAL", column), [])?;
+                }
+            }
+            // Required by `upsert_quote`. Existing databases may contain several quotes of the
+            // same ticker at the same time, of which only the latest inserted one is kept.
+            conn.execute(
+                "DELETE FROM quotes WHERE id IN \
+                (SELECT q1.id FROM quotes q1, quotes q2 \
+                WHERE q1.id < q2.id AND q1.ticker_id = q2.ticker_id AND q1.time = q2.time)", [])?;
+            conn.execute(
+                "CREATE UNIQUE INDEX IF NOT EXISTS quotes_ticker_id_time_idx ON quotes (ticker_id, time)", [])?;
             conn.execute(
                 "CREATE TABLE IF NOT EXISTS rounding_digits (
                 id INTEGER PRIMARY KEY,
@@ -161,4 +181,81 @@ mod test {
         let db = db_pool.get_conection().await.unwrap();
         assert!(db.clean().await.is_ok());
     }
+
+    #[tokio::test]
+    async fn init_adds_missing_quote_columns() {
+        use std::str::FromStr;
+        use finql_data::{Asset, AssetHandler, Currency, Quote, QuoteHandler, Ticker};
+        use finql_data::date_time_helper::make_time;
+
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        // quotes table as created before bid and ask prices were added
+        db.conn.interact(|conn| conn.execute(
+            "CREATE TABLE quotes (
+                id INTEGER PRIMARY KEY,
+                ticker_id INTEGER NOT NULL,
+                price REAL NOT NULL,
+                time TEXT NOT NULL,
+                volume REAL,
+                FOREIGN KEY(ticker_id) REFERENCES ticker(id)
+            )", [])).await.unwrap().unwrap();
+        db.init().await.unwrap();
+        // running init again must not fail
+        db.init().await.unwrap();
+
+        let asset = db.insert_asset(&Asset { id: None, name: "A".to_string(), wkn: None, isin: None, note: None })
+            .await.unwrap();
+        let ticker = db.insert_ticker(&Ticker {
+            id: None,
+            asset,
+            name: "A".to_string(),
+            currency: Currency::from_str("EUR").unwrap(),
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        }).await.unwrap();
+        db.insert_quote(&Quote {
+            id: None,
+            ticker,
+            price: 10.0,
+            time: make_time(2021, 12, 6, 12, 0, 0).unwrap(),
+            volume: None,
+            bid: Some(9.9),
+            ask: Some(10.1),
+        }).await.unwrap();
+        let quotes = db.get_all_quotes_for_ticker(ticker).await.unwrap();
+        assert_eq!((quotes[0].bid, quotes[0].ask), (Some(9.9), Some(10.1)));
+    }
+
+    #[tokio::test]
+    async fn init_removes_duplicate_quotes() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        // quotes table as created before quotes were unique per ticker and time
+        db.conn.interact(|conn| conn.execute_batch(
+            "CREATE TABLE quotes (
+                id INTEGER PRIMARY KEY,
+                ticker_id INTEGER NOT NULL,
+                price REAL NOT NULL,
+                time TEXT NOT NULL,
+                volume REAL,
+                bid REAL,
+                ask REAL
+            );
+            INSERT INTO quotes (ticker_id, price, time) VALUES (1, 1.0, '2021-12-06 12:00:00+01:00');
+            INSERT INTO quotes (ticker_id, price, time) VALUES (1, 2.0, '2021-12-06 12:00:00+01:00');
+            INSERT INTO quotes (ticker_id, price, time) VALUES (1, 3.0, '2021-12-07 12:00:00+01:00');
+            INSERT INTO quotes (ticker_id, price, time) VALUES (2, 4.0, '2021-12-06 12:00:00+01:00');"
+        )).await.unwrap().unwrap();
+        db.init().await.unwrap();
+        let prices = db.conn.interact(|conn| conn
+            .prepare("SELECT price FROM quotes ORDER BY id")?
+            .query_map([], |row| row.get::<_, f64>(0))?
+            .collect::<Result<Vec<f64>, _>>()
+        ).await.unwrap().unwrap();
+        assert_eq!(prices, vec![2.0, 3.0, 4.0]);
+    }
 }
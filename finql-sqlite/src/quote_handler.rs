@@ -2,8 +2,9 @@
 
 use std::str::FromStr;
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local};
 use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
 use std::sync::Arc;
 
 use finql_data::{DataError, QuoteHandler, AssetHandler,
@@ -12,6 +13,37 @@ use finql_data::{DataError, QuoteHandler, AssetHandler,
 use super::{SqliteDB, SQLiteError};
 use deadpool_sqlite::rusqlite::params;
 use deadpool_sqlite::rusqlite;
+
+/// Number of quotes fetched per query when streaming quotes
+const QUOTE_STREAM_PAGE_SIZE: usize = 1000;
+
+/// Parse a currency code read from the given column
+fn parse_currency(currency: &str, column: usize) -> Result<Currency, rusqlite::Error> {
+    Currency::from_str(currency).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+/// Report invalid column values as `InvalidData`, all other errors as access failure
+fn invalid_data_or_failure(err: SQLiteError) -> DataError {
+    match err {
+        SQLiteError::QueryError(rusqlite::Error::FromSqlConversionFailure(_, _, ref cause)) => {
+            DataError::InvalidData(cause.to_string())
+        }
+        _ => DataError::DataAccessFailure(err.to_string()),
+    }
+}
+
+/// Report a missing quote or ticker as `NotFound`, invalid column values as `InvalidData`,
+/// all other errors as access failure
+fn quote_not_found_or_failure(err: SQLiteError) -> DataError {
+    match err {
+        SQLiteError::QueryError(rusqlite::Error::QueryReturnedNoRows) => {
+            DataError::NotFound(err.to_string())
+        }
+        _ => invalid_data_or_failure(err),
+    }
+}
     
 
 /// Sqlite implementation of quote handler
@@ -85,15 +117,39 @@ impl QuoteHandler for SqliteDB {
                     asset: row.get(1)?,
                     source: row.get(2)?,
                     priority: row.get(3)?,
-                    currency: Currency::from_str(&currency)
-                        .map_err(|e| rusqlite::Error::InvalidParameterName(format!("{}", e)))?,
+                    currency: parse_currency(&currency, 4)?,
                     factor: row.get(5)?,
                     tz: row.get(6)?,
                     cal: row.get(7)?
                 })
             })?)
         }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
-        .map_err(|e| DataError::DataAccessFailure(e.to_string()))
+        .map_err(invalid_data_or_failure)
+    }
+
+    async fn get_ticker_by_name(&self, name: &str) -> Result<Ticker, DataError> {
+        let name = name.to_owned();
+        self.conn.interact(move |conn| -> Result<Ticker, SQLiteError> {
+            Ok(conn.query_row(
+                "SELECT id, name, asset_id, source, priority, currency, factor, tz, cal \
+                 FROM ticker WHERE name=?",
+                params![&name],
+                |row| {
+                    let currency: String = row.get(5)?;
+                    Ok(Ticker {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    asset: row.get(2)?,
+                    source: row.get(3)?,
+                    priority: row.get(4)?,
+                    currency: parse_currency(&currency, 5)?,
+                    factor: row.get(6)?,
+                    tz: row.get(7)?,
+                    cal: row.get(8)?
+                })
+            })?)
+        }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
+        .map_err(quote_not_found_or_failure)
     }
 
     async fn get_all_ticker(&self) -> Result<Vec<Ticker>, DataError> {
@@ -108,17 +164,16 @@ impl QuoteHandler for SqliteDB {
                     asset: row.get(2)?,
                     priority: row.get(3)?,
                     source: row.get(4)?,
-                    currency: Currency::from_str(&currency)
-                        .map_err(|e| rusqlite::Error::InvalidParameterName(format!("{}", e)))?,
+                    currency: parse_currency(&currency, 5)?,
                     factor: row.get(6)?,
                     tz: row.get(7)?,
                     cal: row.get(8)?
                 })
-            })?.filter_map(|e| e.ok() ).collect();
+            })?.collect::<Result<_, _>>()?;
             Ok(ticker)
         })
         .await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
-        .map_err(|e| DataError::DataAccessFailure(e.to_string()))
+        .map_err(invalid_data_or_failure)
     }
 
     async fn get_all_ticker_for_source(
@@ -137,17 +192,16 @@ impl QuoteHandler for SqliteDB {
                     asset: row.get(2)?,
                     priority: row.get(3)?,
                     source: source.clone(),
-                    currency: Currency::from_str(&currency)
-                        .map_err(|e| rusqlite::Error::InvalidParameterName(format!("{}", e)))?,
+                    currency: parse_currency(&currency, 4)?,
                     factor: row.get(5)?,
                     tz: row.get(6)?,
                     cal: row.get(7)?
                 })
-            })?.filter_map(|e| e.ok() ).collect();
+            })?.collect::<Result<_, _>>()?;
             Ok(ticker)
         })
         .await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
-        .map_err(|e| DataError::DataAccessFailure(e.to_string()))
+        .map_err(invalid_data_or_failure)
     }
 
     async fn get_all_ticker_for_asset(
@@ -165,17 +219,16 @@ impl QuoteHandler for SqliteDB {
                     asset: asset_id,
                     source: row.get(2)?,
                     priority: row.get(3)?,
-                    currency: Currency::from_str(&currency)
-                        .map_err(|e| rusqlite::Error::InvalidParameterName(format!("{}", e)))?,
+                    currency: parse_currency(&currency, 4)?,
                     factor: row.get(5)?,
                     tz: row.get(6)?,
                     cal: row.get(7)?
                 })
-            })?.filter_map(|e| e.ok() ).collect();
+            })?.collect::<Result<_, _>>()?;
             Ok(ticker)
         })
         .await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
-        .map_err(|e| DataError::DataAccessFailure(e.to_string()))
+        .map_err(invalid_data_or_failure)
     }
 
     async fn update_ticker(&self, ticker: &Ticker) -> Result<(), DataError> {
@@ -210,33 +263,100 @@ impl QuoteHandler for SqliteDB {
 
     async fn delete_ticker(&self, id: usize) -> Result<(), DataError> {
         self.conn.interact(move |conn| -> Result<(), SQLiteError> {
-            conn.execute("DELETE FROM ticker WHERE id=?", params![&id])?;
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM quotes WHERE ticker_id=?", params![&id])?;
+            tx.execute("DELETE FROM ticker WHERE id=?", params![&id])?;
+            tx.commit()?;
             Ok(())
         }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
         .map_err(|e| DataError::DataAccessFailure(e.to_string()))
     }
 
+    async fn count_quotes_for_ticker(&self, ticker_id: usize) -> Result<usize, DataError> {
+        self.conn.interact(move |conn| -> Result<usize, SQLiteError> {
+            Ok(conn.query_row(
+                "SELECT COUNT(*) FROM quotes WHERE ticker_id=?",
+                params![&ticker_id],
+                |row| row.get(0) )?)
+        }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
+        .map_err(|e| DataError::DataAccessFailure(e.to_string()))
+    }
+
     // insert, get, update and delete for market data sources
     async fn insert_quote(&self, quote: &Quote) -> Result<usize, DataError> {
         let quote = quote.to_owned();
-        let quote_ticker = quote.ticker.clone();
-        let quote_time = quote.time.clone();
-        let _ = self.conn.interact(move |conn| -> Result<(), SQLiteError> {
+        // fails if a quote of the same ticker and time is already stored
+        self.conn.interact(move |conn| -> Result<usize, SQLiteError> {
             conn.execute(
-                "INSERT INTO quotes (ticker_id, price, time, volume) \
-                VALUES (?, ?, ?, ?)",
-                params![&quote.ticker, quote.price, quote.time, quote.volume])?;
-            Ok(())
+                "INSERT INTO quotes (ticker_id, price, time, volume, bid, ask) \
+                VALUES (?, ?, ?, ?, ?, ?)",
+                params![&quote.ticker, quote.price, quote.time, quote.volume, quote.bid, quote.ask])?;
+            Ok(conn.last_insert_rowid() as usize)
         }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
-        .map_err(|e| DataError::DataAccessFailure(e.to_string()));
+        .map_err(|e| DataError::InsertFailed(e.to_string()))
+    }
 
+    async fn upsert_quote(&self, quote: &Quote) -> Result<usize, DataError> {
+        let quote = quote.to_owned();
         self.conn.interact(move |conn| -> Result<usize, SQLiteError> {
-            Ok(conn.query_row(
-                "SELECT id FROM quotes WHERE ticker_id=? and time=?",
-                params![&quote_ticker, &quote_time],
-                |row| row.get(0) )?)
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO quotes (ticker_id, price, time, volume, bid, ask) \
+                VALUES (?, ?, ?, ?, ?, ?) \
+                ON CONFLICT (ticker_id, time) DO UPDATE \
+                SET price=excluded.price, volume=excluded.volume, bid=excluded.bid, ask=excluded.ask",
+                params![&quote.ticker, quote.price, quote.time, quote.volume, quote.bid, quote.ask])?;
+            let id = tx.query_row(
+                "SELECT id FROM quotes WHERE ticker_id=? AND time=?",
+                params![&quote.ticker, &quote.time],
+                |row| row.get(0))?;
+            tx.commit()?;
+            Ok(id)
         }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
-        .map_err(|e| DataError::DataAccessFailure(e.to_string()))
+        .map_err(|e| DataError::InsertFailed(e.to_string()))
+    }
+
+    async fn insert_quotes(&self, quotes: &[Quote]) -> Result<Vec<usize>, DataError> {
+        let quotes = quotes.to_vec();
+        self.conn.interact(move |conn| -> Result<Vec<usize>, SQLiteError> {
+            // the transaction is rolled back if dropped without commit
+            let tx = conn.transaction()?;
+            let mut ids = Vec::with_capacity(quotes.len());
+            {
+                let mut stmt = tx.prepare("INSERT INTO quotes (ticker_id, price, time, volume, bid, ask) \
+                VALUES (?, ?, ?, ?, ?, ?)")?;
+                for quote in &quotes {
+                    stmt.execute(params![&quote.ticker, quote.price, quote.time, quote.volume, quote.bid, quote.ask])?;
+                    ids.push(tx.last_insert_rowid() as usize);
+                }
+            }
+            tx.commit()?;
+            Ok(ids)
+        }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
+        .map_err(|e| DataError::InsertFailed(e.to_string()))
+    }
+
+    async fn upsert_quotes(&self, quotes: &[Quote]) -> Result<Vec<usize>, DataError> {
+        let quotes = quotes.to_vec();
+        self.conn.interact(move |conn| -> Result<Vec<usize>, SQLiteError> {
+            // the transaction is rolled back if dropped without commit
+            let tx = conn.transaction()?;
+            let mut ids = Vec::with_capacity(quotes.len());
+            {
+                let mut upsert = tx.prepare("INSERT INTO quotes (ticker_id, price, time, volume, bid, ask) \
+                VALUES (?, ?, ?, ?, ?, ?) \
+                ON CONFLICT (ticker_id, time) DO UPDATE \
+                SET price=excluded.price, volume=excluded.volume, bid=excluded.bid, ask=excluded.ask")?;
+                let mut select = tx.prepare("SELECT id FROM quotes WHERE ticker_id=? AND time=?")?;
+                for quote in &quotes {
+                    upsert.execute(params![&quote.ticker, quote.price, quote.time, quote.volume, quote.bid, quote.ask])?;
+                    ids.push(select.query_row(params![&quote.ticker, &quote.time], |row| row.get(0))?);
+                }
+            }
+            tx.commit()?;
+            Ok(ids)
+        }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
+        .map_err(|e| DataError::InsertFailed(e.to_string()))
     }
 
     async fn get_last_quote_before(
@@ -247,21 +367,52 @@ impl QuoteHandler for SqliteDB {
         let asset = asset_name.to_owned();
         self.conn.interact(move |conn| -> Result<(Quote, Currency), SQLiteError> {
             Ok(conn.query_row(
-                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, t.currency, t.priority \
+                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, t.currency, t.priority, q.bid, q.ask \
                 FROM quotes q, ticker t, assets a \
                 WHERE a.name=? AND t.asset_id=a.id AND t.id=q.ticker_id AND q.time<=? \
                 ORDER BY q.time DESC, t.priority ASC LIMIT 1",
                 params![&asset, &time],
                 |row| { 
                     let currency: String = row.get(5)?;
-                    let currency = Currency::from_str(&currency)
-                        .map_err(|e| rusqlite::Error::InvalidParameterName(format!("{}", e)))?;
+                    let currency = parse_currency(&currency, 5)?;
+                    Ok((Quote {
+                    id: row.get(0)?,
+                    ticker: row.get(1)?,
+                    price: row.get(2)?,
+                    time: row.get(3)?,
+                    volume: row.get(4)?,
+                    bid: row.get(7)?,
+                    ask: row.get(8)?,
+                }, currency))
+            })?)
+        }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
+        .map_err(quote_not_found_or_failure)
+    }
+
+    async fn get_first_quote_after(
+        &self,
+        asset_name: &str,
+        time: DateTime<Local>,
+    ) -> Result<(Quote, Currency), DataError> {
+        let asset = asset_name.to_owned();
+        self.conn.interact(move |conn| -> Result<(Quote, Currency), SQLiteError> {
+            Ok(conn.query_row(
+                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, t.currency, t.priority, q.bid, q.ask \
+                FROM quotes q, ticker t, assets a \
+                WHERE a.name=? AND t.asset_id=a.id AND t.id=q.ticker_id AND q.time>=? \
+                ORDER BY q.time ASC, t.priority ASC LIMIT 1",
+                params![&asset, &time],
+                |row| { 
+                    let currency: String = row.get(5)?;
+                    let currency = parse_currency(&currency, 5)?;
                     Ok((Quote {
                     id: row.get(0)?,
                     ticker: row.get(1)?,
                     price: row.get(2)?,
                     time: row.get(3)?,
                     volume: row.get(4)?,
+                    bid: row.get(7)?,
+                    ask: row.get(8)?,
                 }, currency))
             })?)
         }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
@@ -276,30 +427,122 @@ impl QuoteHandler for SqliteDB {
     ) -> Result<(Quote, Currency), DataError> {
         self.conn.interact(move |conn| -> Result<(Quote, Currency), SQLiteError> {
             Ok(conn.query_row(
-                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, t.currency, t.priority \
+                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, t.currency, t.priority, q.bid, q.ask \
                 FROM quotes q, ticker t \
                 WHERE t.asset_id=?1 AND t.id=q.ticker_id AND q.time<= ?2 \
                 ORDER BY q.time DESC, t.priority ASC LIMIT 1",
                 params![&asset_id, &time],
                 |row| { 
                     let currency: String = row.get(5)?;
-                    let currency = Currency::from_str(&currency)
-                        .map_err(|e| rusqlite::Error::InvalidParameterName(format!("{}", e)))?;
+                    let currency = parse_currency(&currency, 5)?;
                     Ok((Quote {
                     id: row.get(0)?,
                     ticker: row.get(1)?,
                     price: row.get(2)?,
                     time: row.get(3)?,
                     volume: row.get(4)?,
+                    bid: row.get(7)?,
+                    ask: row.get(8)?,
                 }, currency))
             })?)
         }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
-        .map_err(|e| DataError::DataAccessFailure(e.to_string()))
+        .map_err(quote_not_found_or_failure)
+    }
+
+    async fn get_last_quote_before_for_ticker(
+        &self,
+        ticker_id: usize,
+        time: DateTime<Local>,
+    ) -> Result<Quote, DataError> {
+        self.conn.interact(move |conn| -> Result<Quote, SQLiteError> {
+            Ok(conn.query_row(
+                "SELECT id, price, time, volume, bid, ask FROM quotes \
+                WHERE ticker_id=?1 AND time<=?2 ORDER BY time DESC LIMIT 1",
+                params![&ticker_id, &time],
+                |row| Ok(Quote {
+                    id: row.get(0)?,
+                    ticker: ticker_id,
+                    price: row.get(1)?,
+                    time: row.get(2)?,
+                    volume: row.get(3)?,
+                    bid: row.get(4)?,
+                    ask: row.get(5)?,
+                }))?)
+        }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
+        .map_err(quote_not_found_or_failure)
+    }
+
+    async fn get_latest_quote(&self, asset_id: usize) -> Result<(Quote, Currency), DataError> {
+        self.conn.interact(move |conn| -> Result<(Quote, Currency), SQLiteError> {
+            Ok(conn.query_row(
+                "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, t.currency, t.priority, q.bid, q.ask \
+                FROM quotes q, ticker t \
+                WHERE t.asset_id=?1 AND t.id=q.ticker_id \
+                ORDER BY q.time DESC, t.priority ASC LIMIT 1",
+                params![&asset_id],
+                |row| {
+                    let currency: String = row.get(5)?;
+                    let currency = parse_currency(&currency, 5)?;
+                    Ok((Quote {
+                    id: row.get(0)?,
+                    ticker: row.get(1)?,
+                    price: row.get(2)?,
+                    time: row.get(3)?,
+                    volume: row.get(4)?,
+                    bid: row.get(7)?,
+                    ask: row.get(8)?,
+                }, currency))
+            })?)
+        }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
+        .map_err(quote_not_found_or_failure)
+    }
+
+    async fn get_nearest_quote(
+        &self,
+        ticker_id: usize,
+        time: DateTime<Local>,
+        max_gap: Duration,
+    ) -> Result<Option<(Quote, Duration)>, DataError> {
+        let quotes = self.conn.interact(move |conn| -> Result<Vec<Quote>, SQLiteError> {
+            let mut quotes = Vec::new();
+            for query in [
+                "SELECT id, price, time, volume, bid, ask FROM quotes \
+                WHERE ticker_id=?1 AND time<=?2 ORDER BY time DESC LIMIT 1",
+                "SELECT id, price, time, volume, bid, ask FROM quotes \
+                WHERE ticker_id=?1 AND time>=?2 ORDER BY time ASC LIMIT 1",
+            ] {
+                let mut stmt = conn.prepare(query)?;
+                let mut rows = stmt.query_map(params![&ticker_id, &time], |row| {
+                    Ok(Quote {
+                        id: row.get(0)?,
+                        ticker: ticker_id,
+                        price: row.get(1)?,
+                        time: row.get(2)?,
+                        volume: row.get(3)?,
+                        bid: row.get(4)?,
+                        ask: row.get(5)?,
+                    })
+                })?;
+                if let Some(quote) = rows.next() {
+                    quotes.push(quote?);
+                }
+            }
+            Ok(quotes)
+        }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
+        .map_err(|e| DataError::DataAccessFailure(e.to_string()))?;
+
+        let nearest = quotes.into_iter()
+            .map(|quote| {
+                let gap = if quote.time > time { quote.time - time } else { time - quote.time };
+                (quote, gap)
+            })
+            .min_by_key(|(_, gap)| *gap);
+        Ok(nearest.filter(|(_, gap)| *gap <= max_gap))
     }
 
     async fn get_all_quotes_for_ticker(&self, ticker_id: usize) -> Result<Vec<Quote>, DataError> {
         self.conn.interact(move |conn| -> Result<Vec<Quote>, SQLiteError> {
-            let mut stmt = conn.prepare("SELECT id, price, time, volume FROM quotes \
+            let mut stmt = conn.prepare("SELECT id, price, time, volume, bid, ask FROM quotes \
             WHERE ticker_id=?1 ORDER BY time ASC")?;
             let quotes: Vec<Quote> = stmt.query_map([&ticker_id], |row| {
                 Ok(Quote {
@@ -308,6 +551,8 @@ impl QuoteHandler for SqliteDB {
                     price: row.get(1)?,
                     time: row.get(2)?,
                     volume: row.get(3)?,
+                    bid: row.get(4)?,
+                    ask: row.get(5)?,
                 })
             })?.filter_map(|quote| quote.ok() ).collect();
             Ok(quotes)
@@ -316,14 +561,75 @@ impl QuoteHandler for SqliteDB {
         .map_err(|e| DataError::DataAccessFailure(e.to_string()))
     }
 
+    async fn get_quotes_for_ticker_in_range(
+        &self,
+        ticker_id: usize,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<Quote>, DataError> {
+        self.conn.interact(move |conn| -> Result<Vec<Quote>, SQLiteError> {
+            let mut stmt = conn.prepare("SELECT id, price, time, volume, bid, ask FROM quotes \
+            WHERE ticker_id=?1 AND time BETWEEN ?2 AND ?3 ORDER BY time ASC")?;
+            let quotes = stmt.query_map(params![&ticker_id, &start, &end], |row| {
+                Ok(Quote {
+                    id: row.get(0)?,
+                    ticker: ticker_id,
+                    price: row.get(1)?,
+                    time: row.get(2)?,
+                    volume: row.get(3)?,
+                    bid: row.get(4)?,
+                    ask: row.get(5)?,
+                })
+            })?.collect::<Result<Vec<Quote>, _>>()?;
+            Ok(quotes)
+        })
+        .await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
+        .map_err(|e| DataError::DataAccessFailure(e.to_string()))
+    }
+
+    fn stream_quotes_for_ticker(&self, ticker_id: usize) -> BoxStream<'_, Result<Quote, DataError>> {
+        // Quotes are fetched page by page, each page only once the previous one is consumed
+        stream::unfold(Some(0_usize), move |offset| async move {
+            let offset = offset?;
+            let page = self.conn.interact(move |conn| -> Result<Vec<Quote>, SQLiteError> {
+                let mut stmt = conn.prepare("SELECT id, price, time, volume, bid, ask FROM quotes \
+                WHERE ticker_id=?1 ORDER BY time ASC, id ASC LIMIT ?2 OFFSET ?3")?;
+                let quotes: Vec<Quote> = stmt.query_map(params![&ticker_id, &QUOTE_STREAM_PAGE_SIZE, &offset], |row| {
+                    Ok(Quote {
+                        id: row.get(0)?,
+                        ticker: ticker_id,
+                        price: row.get(1)?,
+                        time: row.get(2)?,
+                        volume: row.get(3)?,
+                        bid: row.get(4)?,
+                        ask: row.get(5)?,
+                    })
+                })?.filter_map(|quote| quote.ok() ).collect();
+                Ok(quotes)
+            })
+            .await.map_err(|e| DataError::DataAccessFailure(e.to_string()))
+            .and_then(|page| page.map_err(|e| DataError::DataAccessFailure(e.to_string())));
+            match page {
+                Ok(quotes) => {
+                    let next = if quotes.len() < QUOTE_STREAM_PAGE_SIZE { None } else { Some(offset + quotes.len()) };
+                    let quotes: Vec<Result<Quote, DataError>> = quotes.into_iter().map(Ok).collect();
+                    Some((stream::iter(quotes), next))
+                }
+                Err(err) => Some((stream::iter(vec![Err(err)]), None)),
+            }
+        })
+        .flatten()
+        .boxed()
+    }
+
     async fn update_quote(&self, quote: &Quote) -> Result<(), DataError> {
         if let Some(id) = quote.id {
             let quote = quote.to_owned();
             self.conn.interact(move |conn| -> Result<(), SQLiteError> {
                 conn.execute(
-                    "UPDATE quotes SET ticker_id=?2, price=?3, time=?4, volume=?5 \
+                    "UPDATE quotes SET ticker_id=?2, price=?3, time=?4, volume=?5, bid=?6, ask=?7 \
                     WHERE id=?1",
-                    params![&id, &quote.ticker, &quote.price, &quote.time, &quote.volume])?;
+                    params![&id, &quote.ticker, &quote.price, &quote.time, &quote.volume, &quote.bid, &quote.ask])?;
                 Ok(())
             }).await.map_err(|e| DataError::DataAccessFailure(e.to_string()))?
             .map_err(|e| DataError::DataAccessFailure(e.to_string()))
@@ -392,6 +698,31 @@ mod test {
     use super::super::SqliteDBPool;
     use finql_data::{Asset, date_time_helper::make_time};
 
+    /// Store the asset "asset A" with the EUR ticker "A", returning their ids
+    async fn setup_ticker(db: &SqliteDB) -> (usize, usize) {
+        let asset = Asset{
+            id: None,
+            name: "asset A".to_string(),
+            isin: None,
+            wkn: None,
+            note: None,
+        };
+        let asset = db.insert_asset(&asset).await.unwrap();
+        let ticker = Ticker{
+            id: None,
+            asset,
+            name: "A".to_string(),
+            currency: Currency::from_str("EUR").unwrap(),
+            source: "s1".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        };
+        let ticker = db.insert_ticker(&ticker).await.unwrap();
+        (asset, ticker)
+    }
+
     #[tokio::test]
     async fn sqlite_ticker_test() {
         let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
@@ -424,6 +755,11 @@ mod test {
         let s1_id = db.insert_ticker(&s1_ticker).await.unwrap();
         assert_eq!(s1_id, 1);
         assert_eq!(db.insert_if_new_ticker(&s1_ticker).await.unwrap(), 1);
+        let ticker = db.get_ticker_by_name("A").await.unwrap();
+        assert_eq!(ticker.id, Some(s1_id));
+        assert_eq!(ticker.priority, 2);
+        assert_eq!(ticker.factor, 3.0);
+        assert!(matches!(db.get_ticker_by_name("unknown").await, Err(DataError::NotFound(_))));
 
         let mut s2_ticker = Ticker{
             id: None,
@@ -500,6 +836,8 @@ mod test {
             price: 2.0,
             time: time_now,
             volume: None,
+            bid: None,
+            ask: None,
         };
         let qid1 = db.insert_quote(&quote1).await.unwrap();
         assert_eq!(qid1, 1);
@@ -511,6 +849,8 @@ mod test {
             price: 1.5,
             time: time2,
             volume: None,
+            bid: None,
+            ask: None,
         };
         let qid2 = db.insert_quote(&quote2).await.unwrap();
         assert_eq!(qid2, 2);
@@ -522,12 +862,29 @@ mod test {
 
         let quote_before = db.get_last_quote_before_by_id(asset, time3).await.unwrap();
         assert_eq!(quote_before.0.price, 1.5);
+
+        let latest = db.get_latest_quote(asset).await.unwrap();
+        assert_eq!(latest.0.price, 2.0);
+        assert_eq!(latest.1, eur);
+        assert!(matches!(db.get_latest_quote(asset + 1).await, Err(DataError::NotFound(_))));
+
+        let quote_after = db.get_first_quote_after("asset A", time3).await.unwrap();
+        assert_eq!(quote_after.0.price, 2.0);
+        assert_eq!(quote_after.1, eur);
+        let time1 = make_time(2021,12,6,18,59,0).unwrap();
+        let quote_after = db.get_first_quote_after("asset A", time1).await.unwrap();
+        assert_eq!(quote_after.0.price, 1.5);
+        let quote_after = db.get_first_quote_after("asset A", time2).await.unwrap();
+        assert_eq!(quote_after.0.price, 1.5);
         
         quote2.id = Some(qid2);
         quote2.price = 1.7;
         assert!(db.update_quote(&quote2).await.is_ok());
         assert!(db.delete_quote(qid2).await.is_ok());
 
+        // databases created before quotes became unique per ticker and time may contain duplicates
+        db.conn.interact(|conn| conn.execute("DROP INDEX quotes_ticker_id_time_idx", []))
+            .await.unwrap().unwrap();
         let _ = db.insert_quote(&quote1).await.unwrap();
         let quotes = db.get_all_quotes_for_ticker(ticker).await.unwrap();
         assert_eq!(quotes.len(), 2);
@@ -536,6 +893,278 @@ mod test {
         assert_eq!(quotes.len(), 1);
     }
 
+    #[tokio::test]
+    async fn sqlite_insert_duplicate_quote_test() {
+        let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
+        let db = sqlite_pool.get_conection().await.unwrap();
+        assert!(db.clean().await.is_ok());
+
+        let (_, ticker) = setup_ticker(&db).await;
+
+        let mut quote = Quote{
+            id: None,
+            ticker,
+            price: 1.0,
+            time: make_time(2021,12,6,18,0,0).unwrap(),
+            volume: None,
+            bid: None,
+            ask: None,
+        };
+        let id = db.insert_quote(&quote).await.unwrap();
+        quote.price = 1.2;
+        assert!(matches!(db.insert_quote(&quote).await, Err(DataError::InsertFailed(_))));
+        let quotes = db.get_all_quotes_for_ticker(ticker).await.unwrap();
+        assert_eq!(quotes.len(), 1);
+        assert_eq!(quotes[0].id, Some(id));
+        assert_eq!(quotes[0].price, 1.0);
+    }
+
+    #[tokio::test]
+    async fn sqlite_invalid_currency_test() {
+        let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
+        let db = sqlite_pool.get_conection().await.unwrap();
+        assert!(db.clean().await.is_ok());
+
+        let (_, ticker) = setup_ticker(&db).await;
+        let quote = Quote{ id: None, ticker, price: 1.0, time: make_time(2021,12,6,12,0,0).unwrap(),
+            volume: None, bid: None, ask: None };
+        db.insert_quote(&quote).await.unwrap();
+
+        // corrupt the currency code
+        db.conn.interact(move |conn| {
+            conn.execute("UPDATE ticker SET currency='E1' WHERE id=?", params![&ticker])
+        }).await.unwrap().unwrap();
+
+        assert!(matches!(db.get_ticker_by_id(ticker).await, Err(DataError::InvalidData(_))));
+        assert!(matches!(db.get_all_ticker().await, Err(DataError::InvalidData(_))));
+        assert!(matches!(db.get_last_quote_before("asset A", make_time(2021,12,7,0,0,0).unwrap()).await,
+            Err(DataError::InvalidData(_))));
+        // missing rows are still reported as not found
+        assert!(matches!(db.get_last_quote_before("asset A", make_time(2021,12,1,0,0,0).unwrap()).await,
+            Err(DataError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn sqlite_quotes_in_range_test() {
+        let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
+        let db = sqlite_pool.get_conection().await.unwrap();
+        assert!(db.clean().await.is_ok());
+
+        let (_, ticker) = setup_ticker(&db).await;
+
+        for day in [9, 7, 6, 8, 10] {
+            let time = make_time(2021,12,day,12,0,0).unwrap();
+            let quote = Quote{ id: None, ticker, price: day as f64, time, volume: None, bid: None, ask: None };
+            db.insert_quote(&quote).await.unwrap();
+        }
+
+        let quotes = db.get_quotes_for_ticker_in_range(
+            ticker,
+            make_time(2021,12,7,12,0,0).unwrap(),
+            make_time(2021,12,9,18,0,0).unwrap(),
+        ).await.unwrap();
+        let prices: Vec<f64> = quotes.iter().map(|q| q.price).collect();
+        assert_eq!(prices, vec![7.0, 8.0, 9.0]);
+
+        let quotes = db.get_quotes_for_ticker_in_range(
+            ticker,
+            make_time(2021,12,11,0,0,0).unwrap(),
+            make_time(2021,12,12,0,0,0).unwrap(),
+        ).await.unwrap();
+        assert!(quotes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sqlite_insert_quotes_test() {
+        let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
+        let db = sqlite_pool.get_conection().await.unwrap();
+        assert!(db.clean().await.is_ok());
+
+        let (_, ticker) = setup_ticker(&db).await;
+
+        assert!(db.insert_quotes(&[]).await.unwrap().is_empty());
+
+        let quotes: Vec<Quote> = [6, 7, 8].iter().map(|day| Quote{
+            id: None,
+            ticker,
+            price: *day as f64,
+            time: make_time(2021,12,*day,12,0,0).unwrap(),
+            volume: None,
+            bid: None,
+            ask: None,
+        }).collect();
+        let ids = db.insert_quotes(&quotes).await.unwrap();
+        let stored = db.get_all_quotes_for_ticker(ticker).await.unwrap();
+        assert_eq!(ids, stored.iter().map(|q| q.id.unwrap()).collect::<Vec<usize>>());
+        assert_eq!(stored.iter().map(|q| q.price).collect::<Vec<f64>>(), vec![6.0, 7.0, 8.0]);
+
+        // NaN is stored as NULL, which violates the price constraint and fails the whole batch
+        let mut invalid = quotes.clone();
+        invalid[1].price = f64::NAN;
+        assert!(db.insert_quotes(&invalid).await.is_err());
+        assert_eq!(db.get_all_quotes_for_ticker(ticker).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn sqlite_upsert_quote_test() {
+        let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
+        let db = sqlite_pool.get_conection().await.unwrap();
+        assert!(db.clean().await.is_ok());
+
+        let (_, ticker) = setup_ticker(&db).await;
+
+        let mut quote = Quote{
+            id: None,
+            ticker,
+            price: 1.0,
+            time: make_time(2021,12,6,18,0,0).unwrap(),
+            volume: None,
+            bid: None,
+            ask: None,
+        };
+        let id = db.upsert_quote(&quote).await.unwrap();
+        quote.price = 1.2;
+        quote.volume = Some(100.0);
+        assert_eq!(db.upsert_quote(&quote).await.unwrap(), id);
+        let quotes = db.get_all_quotes_for_ticker(ticker).await.unwrap();
+        assert_eq!(quotes.len(), 1);
+        assert_eq!(quotes[0].price, 1.2);
+        assert_eq!(quotes[0].volume, Some(100.0));
+
+        quote.time = make_time(2021,12,7,18,0,0).unwrap();
+        let new_id = db.upsert_quote(&quote).await.unwrap();
+        assert_ne!(new_id, id);
+        assert_eq!(db.get_all_quotes_for_ticker(ticker).await.unwrap().len(), 2);
+
+        // batch overlapping the stored quotes
+        let mut next = quote.clone();
+        next.time = make_time(2021,12,8,18,0,0).unwrap();
+        quote.price = 1.3;
+        let ids = db.upsert_quotes(&[quote, next]).await.unwrap();
+        assert_eq!(ids[0], new_id);
+        let quotes = db.get_all_quotes_for_ticker(ticker).await.unwrap();
+        assert_eq!(quotes.len(), 3);
+        assert_eq!(quotes[1].price, 1.3);
+        assert_eq!(quotes[2].id, Some(ids[1]));
+    }
+
+    #[tokio::test]
+    async fn sqlite_delete_ticker_with_quotes_test() {
+        let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
+        let db = sqlite_pool.get_conection().await.unwrap();
+        assert!(db.clean().await.is_ok());
+
+        let (_, ticker_a) = setup_ticker(&db).await;
+        let mut ticker = db.get_ticker_by_id(ticker_a).await.unwrap();
+        ticker.id = None;
+        ticker.name = "B".to_string();
+        let ticker_b = db.insert_ticker(&ticker).await.unwrap();
+
+        for (ticker, day) in [(ticker_a, 6), (ticker_a, 7), (ticker_b, 6)] {
+            let time = make_time(2021,12,day,12,0,0).unwrap();
+            let quote = Quote{ id: None, ticker, price: 1.0, time, volume: None, bid: None, ask: None };
+            db.insert_quote(&quote).await.unwrap();
+        }
+        assert_eq!(db.count_quotes_for_ticker(ticker_a).await.unwrap(), 2);
+
+        assert!(db.delete_ticker(ticker_a).await.is_ok());
+        assert_eq!(db.count_quotes_for_ticker(ticker_a).await.unwrap(), 0);
+        assert!(db.get_ticker_by_id(ticker_a).await.is_err());
+        assert_eq!(db.count_quotes_for_ticker(ticker_b).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn sqlite_nearest_quote_test() {
+        let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
+        let db = sqlite_pool.get_conection().await.unwrap();
+        assert!(db.clean().await.is_ok());
+
+        let (_, ticker) = setup_ticker(&db).await;
+
+        for (price, time) in [
+            (1.0, make_time(2021,12,6,12,0,0).unwrap()),
+            (2.0, make_time(2021,12,8,12,0,0).unwrap()),
+        ] {
+            let quote = Quote{ id: None, ticker, price, time, volume: None, bid: None, ask: None };
+            db.insert_quote(&quote).await.unwrap();
+        }
+
+        // closer to the later quote
+        let target = make_time(2021,12,7,18,0,0).unwrap();
+        let (quote, gap) = db.get_nearest_quote(ticker, target, Duration::days(2)).await
+            .unwrap().unwrap();
+        assert_eq!(quote.price, 2.0);
+        assert_eq!(gap, Duration::hours(18));
+
+        // closer to the earlier quote
+        let target = make_time(2021,12,6,18,0,0).unwrap();
+        let (quote, gap) = db.get_nearest_quote(ticker, target, Duration::days(2)).await
+            .unwrap().unwrap();
+        assert_eq!(quote.price, 1.0);
+        assert_eq!(gap, Duration::hours(6));
+
+        // no quote within tolerance
+        let target = make_time(2021,12,20,12,0,0).unwrap();
+        let nearest = db.get_nearest_quote(ticker, target, Duration::days(2)).await.unwrap();
+        assert!(nearest.is_none());
+    }
+
+    #[tokio::test]
+    async fn sqlite_bid_ask_quote_test() {
+        let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
+        let db = sqlite_pool.get_conection().await.unwrap();
+        assert!(db.clean().await.is_ok());
+
+        let (_, ticker) = setup_ticker(&db).await;
+        let time = make_time(2021,12,6,12,0,0).unwrap();
+        let quote = Quote{ id: None, ticker, price: 99.5, time, volume: None, bid: Some(99.0), ask: Some(101.0) };
+        assert_eq!(quote.mid(), Some(100.0));
+        let id = db.insert_quote(&quote).await.unwrap();
+
+        let (stored, _) = db.get_last_quote_before("asset A", time).await.unwrap();
+        assert_eq!(stored.bid, Some(99.0));
+        assert_eq!(stored.ask, Some(101.0));
+        assert_eq!(stored.mid(), Some(100.0));
+
+        let updated = Quote{ id: Some(id), ticker, price: 99.5, time, volume: None, bid: Some(99.0), ask: None };
+        assert!(db.update_quote(&updated).await.is_ok());
+        let quotes = db.get_all_quotes_for_ticker(ticker).await.unwrap();
+        assert_eq!(quotes[0].ask, None);
+        // falls back to the quoted price
+        assert_eq!(quotes[0].mid(), Some(99.5));
+    }
+
+    #[tokio::test]
+    async fn sqlite_stream_quotes_test() {
+        let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
+        let db = sqlite_pool.get_conection().await.unwrap();
+        assert!(db.clean().await.is_ok());
+
+        let (_, ticker) = setup_ticker(&db).await;
+
+        // insert quotes out of time order
+        for day in [3, 1, 5, 2, 4] {
+            let quote = Quote{
+                id: None,
+                ticker,
+                price: day as f64,
+                time: make_time(2021,12,day,12,0,0).unwrap(),
+                volume: None,
+                bid: None,
+                ask: None,
+            };
+            db.insert_quote(&quote).await.unwrap();
+        }
+
+        let mut quotes = db.stream_quotes_for_ticker(ticker);
+        let mut count = 0;
+        while let Some(quote) = quotes.next().await {
+            count += 1;
+            assert_eq!(quote.unwrap().price, count as f64);
+        }
+        assert_eq!(count, 5);
+    }
+
     #[tokio::test]
     async fn sqlite_rounding_digits_test() {
         let sqlite_pool = Arc::new(SqliteDBPool::in_memory().await.unwrap());
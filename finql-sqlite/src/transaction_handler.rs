@@ -36,7 +36,7 @@ const FEE: &str = "f";
 impl RawTransaction {
     pub fn to_transaction(&self) -> Result<Transaction, DataError> {
         let currency = Currency::from_str(&self.cash_currency)
-            .map_err(|e| DataError::InsertFailed(e.to_string()))?;
+            .map_err(|e| DataError::InvalidData(e.to_string()))?;
         let id = self.id.map(|x| x as usize);
         let cash_flow = CashFlow {
             amount: CashAmount {
@@ -127,6 +127,8 @@ async fn quote_tests(market: &mut Market) {
         price: 67.35,
         time,
         volume: None,
+        bid: None,
+        ask: None,
     };
     market.db().insert_quote(&quote).await.unwrap();
     let time = make_time(2020, 1, 2, 20, 0, 0).unwrap();
@@ -136,6 +138,8 @@ async fn quote_tests(market: &mut Market) {
         price: 68.29,
         time,
         volume: None,
+        bid: None,
+        ask: None,
     };
     market.db().insert_quote(&quote).await.unwrap();
     let time = make_time(2020, 1, 3, 20, 0, 0).unwrap();
@@ -145,6 +149,8 @@ async fn quote_tests(market: &mut Market) {
         price: 67.27,
         time,
         volume: None,
+        bid: None,
+        ask: None,
     };
     market.db().insert_quote(&quote).await.unwrap();
     let time = make_time(2020, 1, 6, 20, 0, 0).unwrap();
@@ -154,6 +160,8 @@ async fn quote_tests(market: &mut Market) {
         price: 66.27,
         time,
         volume: None,
+        bid: None,
+        ask: None,
     };
     market.db().insert_quote(&quote).await.unwrap();
     let time = make_time(2020, 1, 7, 20, 0, 0).unwrap();
@@ -163,6 +171,8 @@ async fn quote_tests(market: &mut Market) {
         price: 66.30,
         time,
         volume: None,
+        bid: None,
+        ask: None,
     };
     market.db().insert_quote(&quote).await.unwrap();
     let time = make_time(2020, 1, 8, 20, 0, 0).unwrap();
@@ -172,6 +182,8 @@ async fn quote_tests(market: &mut Market) {
         price: 65.73,
         time,
         volume: None,
+        bid: None,
+        ask: None,
     };
     let wrong_quote_id = market.db().insert_quote(&wrong_quote).await.unwrap();
     println!("ok");
@@ -2,13 +2,15 @@
 //! and functionality to rollout cashflows and calculate basic
 //! valuation figures
 
-use chrono::{Datelike, NaiveDate};
+use chrono::{Datelike, Duration, NaiveDate};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use std::io::Write;
 
-use finql_data::currency::Currency;
-use finql_data::cash_flow::CashFlow;
+use finql_data::currency::{Currency, CurrencyError};
+use finql_data::cash_flow::{round2digits, CashFlow};
+use finql_data::DataError;
 
 use crate::day_adjust::DayAdjust;
 use crate::day_count_conv::{DayCountConv, DayCountConvError};
@@ -23,6 +25,12 @@ pub enum BondError {
     DiscountingFailure(DiscountError),
     MissingCalendar,
     DayCountError(DayCountConvError),
+    MissingIndexValue(NaiveDate),
+    CurrencyConversion(CurrencyError),
+    DataFailure(DataError),
+    ExportFailure(csv::Error),
+    #[cfg(feature = "parquet")]
+    ParquetExportFailure(parquet::errors::ParquetError),
 }
 
 impl fmt::Display for BondError {
@@ -33,6 +41,12 @@ impl fmt::Display for BondError {
                 write!(f, "invalid day count convention in this context")
             }
             BondError::DiscountingFailure(_) => write!(f, "discounting cash flows failed"),
+            BondError::MissingIndexValue(date) => write!(f, "no index value available for {}", date),
+            BondError::CurrencyConversion(_) => write!(f, "currency conversion failed"),
+            BondError::DataFailure(_) => write!(f, "retrieving data failed"),
+            BondError::ExportFailure(_) => write!(f, "export of cash flows failed"),
+            #[cfg(feature = "parquet")]
+            BondError::ParquetExportFailure(_) => write!(f, "export of cash flows failed"),
         }
     }
 }
@@ -42,6 +56,11 @@ impl Error for BondError {
         match self {
             BondError::DayCountError(err) => Some(err),
             BondError::DiscountingFailure(err) => Some(err),
+            BondError::CurrencyConversion(err) => Some(err),
+            BondError::DataFailure(err) => Some(err),
+            BondError::ExportFailure(err) => Some(err),
+            #[cfg(feature = "parquet")]
+            BondError::ParquetExportFailure(err) => Some(err),
             _ => None,
         }
     }
@@ -53,6 +72,31 @@ impl From<DayCountConvError> for BondError {
     }
 }
 
+impl From<CurrencyError> for BondError {
+    fn from(error: CurrencyError) -> Self {
+        BondError::CurrencyConversion(error)
+    }
+}
+
+impl From<DataError> for BondError {
+    fn from(error: DataError) -> Self {
+        BondError::DataFailure(error)
+    }
+}
+
+impl From<csv::Error> for BondError {
+    fn from(error: csv::Error) -> Self {
+        BondError::ExportFailure(error)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for BondError {
+    fn from(error: parquet::errors::ParquetError) -> Self {
+        BondError::ParquetExportFailure(error)
+    }
+}
+
 impl From<CalendarNotFound> for BondError {
     fn from(_: CalendarNotFound) -> Self {
         BondError::MissingCalendar
@@ -87,6 +131,41 @@ pub struct Bond {
     /// Smallest purchasable unit
     pub denomination: u32,
     volume: Option<f64>,
+    /// Optional amortization schedule, i.e. fractions of the denomination repaid at the
+    /// end of the coupon period containing the given date. The remaining notional is repaid
+    /// at maturity.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    amortization: Vec<(NaiveDate, f64)>,
+}
+
+/// Row of a cash flow ladder report, summarizing all payments of a bond on a given date
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct LadderRow {
+    pub date: NaiveDate,
+    pub coupon: f64,
+    pub principal: f64,
+    pub total: f64,
+    /// Notional outstanding after all payments of this date
+    pub remaining_notional: f64,
+}
+
+/// Parquet schema of the cash flow ladder, see `Bond::ladder_to_parquet`
+#[cfg(feature = "parquet")]
+const LADDER_PARQUET_SCHEMA: &str = "
+    message cash_flow_ladder {
+        REQUIRED INT32 date (DATE);
+        REQUIRED DOUBLE coupon;
+        REQUIRED DOUBLE principal;
+        REQUIRED DOUBLE total;
+        REQUIRED DOUBLE remaining_notional;
+    }
+";
+
+/// Kind of bond payment
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PaymentKind {
+    Coupon,
+    Principal,
 }
 
 /// Information regarding the issuer of an asset
@@ -116,6 +195,10 @@ struct Coupon {
     coupon_date: CouponDate,
     period: TimePeriod,
     day_count_convention: DayCountConv,
+    /// Optional step-up/step-down schedule of coupon rates, each effective
+    /// for periods starting on or after the given date
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    coupon_schedule: Vec<(NaiveDate, f64)>,
 }
 
 impl Coupon {
@@ -134,6 +217,14 @@ impl Coupon {
         self.day_count_convention
             .year_fraction(start, end, Some(roll_date), Some(self.period))
     }
+    /// Coupon rate of the period starting at the given date
+    fn rate_at(&self, start: NaiveDate) -> f64 {
+        self.coupon_schedule
+            .iter()
+            .filter(|(date, _)| *date <= start)
+            .max_by_key(|(date, _)| *date)
+            .map_or(self.rate, |(_, rate)| *rate)
+    }
 }
 
 impl Bond {
@@ -153,52 +244,198 @@ impl Bond {
             )
         }
     }
-}
 
-impl FixedIncome for Bond {
-    type Error = BondError;
+    /// Fraction of the denomination still outstanding at the given date
+    fn outstanding_fraction(&self, date: NaiveDate) -> f64 {
+        let repaid: f64 = self
+            .amortization
+            .iter()
+            .filter(|(repay_date, _)| *repay_date <= date)
+            .map(|(_, fraction)| fraction)
+            .sum();
+        (1. - repaid).max(0.)
+    }
 
-    /// Convert bond in stream of cash flows
-    fn rollout_cash_flows(
+    /// Roll out all coupon and principal payments with (adjusted) payment dates
+    fn payment_schedule(
         &self,
         position: f64,
         calendar_provider: &dyn CalendarProvider,
-    ) -> Result<Vec<CashFlow>, BondError> {
-        let mut cfs = Vec::new();
-        let start_date = self.issue_date;
-        let mut end_date = self.first_coupon_end(start_date);
-        let year_fraction = self.coupon.year_fraction(start_date, end_date, end_date)?;
-        let amount =
-            position * (self.denomination as f64) * self.coupon.rate / 100. * year_fraction;
+    ) -> Result<Vec<(NaiveDate, PaymentKind, f64)>, BondError> {
+        let mut payments = Vec::new();
         let cal = calendar_provider.get_calendar(&self.calendar)?;
-        let pay_date = self.business_day_rule.adjust_date(end_date, cal);
-        let cf = CashFlow::new(amount, self.currency, pay_date);
-        cfs.push(cf);
+        let notional = position * (self.denomination as f64);
         let maturity = self.maturity;
-        while end_date < maturity {
-            let start_date = end_date;
-            end_date = self.coupon.period.add_to(start_date, None);
-            let year_fraction = self
-                .coupon
-                .year_fraction(start_date, end_date, start_date)?;
-            let amount =
-                position * (self.denomination as f64) * self.coupon.rate / 100. * year_fraction;
+        let mut outstanding = 1.;
+        let mut start_date = self.issue_date;
+        let mut end_date = self.first_coupon_end(start_date);
+        let mut roll_date = end_date;
+        loop {
+            let year_fraction = self.coupon.year_fraction(start_date, end_date, roll_date)?;
+            let amount = self.coupon_amount(position * outstanding, start_date, year_fraction);
             let pay_date = self.business_day_rule.adjust_date(end_date, cal);
-            let cf = CashFlow::new(amount, self.currency, pay_date);
-            cfs.push(cf);
+            payments.push((pay_date, PaymentKind::Coupon, amount));
+            if end_date >= maturity {
+                break;
+            }
+            let repayment = outstanding - self.outstanding_fraction(end_date).min(outstanding);
+            if repayment > 0. {
+                payments.push((pay_date, PaymentKind::Principal, notional * repayment));
+                outstanding -= repayment;
+            }
+            start_date = end_date;
+            end_date = self.coupon.period.add_to(start_date, None);
+            roll_date = start_date;
         }
         // final nominal payment
-        let cf = CashFlow::new(
-            position * (self.denomination as f64),
-            self.currency,
+        payments.push((
             self.business_day_rule.adjust_date(maturity, cal),
-        );
-        cfs.push(cf);
+            PaymentKind::Principal,
+            notional * outstanding,
+        ));
+        Ok(payments)
+    }
+
+    /// Projected payments per date with coupon, principal and outstanding notional,
+    /// e.g. for reporting purposes
+    pub fn cash_flow_ladder(
+        &self,
+        position: f64,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<Vec<LadderRow>, BondError> {
+        let mut remaining_notional = position * (self.denomination as f64);
+        let mut ladder: Vec<LadderRow> = Vec::new();
+        for (date, kind, amount) in self.payment_schedule(position, calendar_provider)? {
+            let (coupon, principal) = match kind {
+                PaymentKind::Coupon => (amount, 0.),
+                PaymentKind::Principal => (0., amount),
+            };
+            remaining_notional -= principal;
+            match ladder.last_mut() {
+                Some(row) if row.date == date => {
+                    row.coupon += coupon;
+                    row.principal += principal;
+                    row.total += amount;
+                    row.remaining_notional = remaining_notional;
+                }
+                _ => ladder.push(LadderRow {
+                    date,
+                    coupon,
+                    principal,
+                    total: amount,
+                    remaining_notional,
+                }),
+            }
+        }
+        Ok(ladder)
+    }
+
+    /// Write the cash flow ladder as CSV with one row per payment date, with dates given
+    /// in ISO format (YYYY-MM-DD) and all amounts as plain numbers
+    pub fn ladder_to_csv<W: Write>(
+        &self,
+        position: f64,
+        calendar_provider: &dyn CalendarProvider,
+        writer: W,
+    ) -> Result<(), BondError> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for row in self.cash_flow_ladder(position, calendar_provider)? {
+            writer.serialize(row)?;
+        }
+        writer.flush().map_err(csv::Error::from)?;
+        Ok(())
+    }
+
+    /// Write the cash flow ladder as Parquet file with one row per payment date. Dates are
+    /// stored as `DATE` (days since 1970-01-01) and all amounts as `DOUBLE` columns.
+    #[cfg(feature = "parquet")]
+    pub fn ladder_to_parquet<W: Write + Send>(
+        &self,
+        position: f64,
+        calendar_provider: &dyn CalendarProvider,
+        writer: W,
+    ) -> Result<(), BondError> {
+        use std::sync::Arc;
+        use parquet::data_type::{DoubleType, Int32Type};
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+
+        let ladder = self.cash_flow_ladder(position, calendar_provider)?;
+        let schema = Arc::new(parse_message_type(LADDER_PARQUET_SCHEMA)?);
+        let properties = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(writer, schema, properties)?;
+        let mut row_group = writer.next_row_group()?;
+        let epoch = NaiveDate::from_ymd(1970, 1, 1);
+        let dates: Vec<i32> = ladder.iter().map(|row| (row.date - epoch).num_days() as i32).collect();
+        if let Some(mut column) = row_group.next_column()? {
+            column.typed::<Int32Type>().write_batch(&dates, None, None)?;
+            column.close()?;
+        }
+        let amounts: [fn(&LadderRow) -> f64; 4] = [
+            |row| row.coupon,
+            |row| row.principal,
+            |row| row.total,
+            |row| row.remaining_notional,
+        ];
+        for amount in amounts {
+            let values: Vec<f64> = ladder.iter().map(amount).collect();
+            if let Some(mut column) = row_group.next_column()? {
+                column.typed::<DoubleType>().write_batch(&values, None, None)?;
+                column.close()?;
+            }
+        }
+        row_group.close()?;
+        writer.close()?;
+        Ok(())
+    }
+
+    /// Coupon payment for the given position and period start, rounded to the currency's minor unit
+    fn coupon_amount(&self, position: f64, start_date: NaiveDate, year_fraction: f64) -> f64 {
+        let rate = self.coupon.rate_at(start_date);
+        let amount = position * (self.denomination as f64) * rate / 100. * year_fraction;
+        round2digits(amount, self.currency.rounding_digits())
+    }
+}
+
+impl FixedIncome for Bond {
+    type Error = BondError;
+
+    /// Convert bond in stream of cash flows
+    fn rollout_cash_flows(
+        &self,
+        position: f64,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<Vec<CashFlow>, BondError> {
+        Ok(self
+            .payment_schedule(position, calendar_provider)?
+            .into_iter()
+            .map(|(date, _, amount)| CashFlow::new(amount, self.currency, date))
+            .collect())
+    }
 
-        Ok(cfs)
+    fn redemption(&self, calendar_provider: &dyn CalendarProvider) -> Result<CashFlow, BondError> {
+        // the final principal payment is always the last payment of the schedule
+        let (date, _, amount) = *self.payment_schedule(1., calendar_provider)?.last().unwrap();
+        Ok(CashFlow::new(amount, self.currency, date))
     }
 
     fn accrued_interest(&self, today: NaiveDate) -> Result<f64, BondError> {
+        self.accrued_interest_ex_div(today, None)
+    }
+
+    /// Year fraction according to the coupon's day count convention, rolled from the
+    /// first coupon date like the payment schedule
+    fn year_fraction(&self, start: NaiveDate, end: NaiveDate) -> Result<f64, BondError> {
+        let roll_date = self.first_coupon_end(self.issue_date);
+        Ok(self.coupon.year_fraction(start, end, roll_date)?)
+    }
+
+    fn accrued_interest_ex_div(
+        &self,
+        today: NaiveDate,
+        ex_div_days: Option<i64>,
+    ) -> Result<f64, BondError> {
         let mut start_date = self.issue_date;
         if today < start_date {
             return Ok(0.);
@@ -214,19 +451,98 @@ impl FixedIncome for Bond {
         let year_fraction = self
             .coupon
             .year_fraction(start_date, end_date, start_date)?;
-        let amount = (self.denomination as f64) * self.coupon.rate / 100. * year_fraction;
+        let amount = (self.denomination as f64)
+            * self.outstanding_fraction(start_date)
+            * self.coupon.rate_at(start_date)
+            / 100.
+            * year_fraction;
         let fraction = today.signed_duration_since(start_date).num_days() as f64
             / end_date.signed_duration_since(start_date).num_days() as f64;
 
-        Ok(amount * fraction)
+        match ex_div_days {
+            // within the ex-dividend period, the seller keeps the next coupon
+            Some(days) if today >= end_date - Duration::days(days) => Ok(amount * (fraction - 1.)),
+            _ => Ok(amount * fraction),
+        }
+    }
+}
+
+/// Fixtures shared by the tests of fixed income products
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use serde_json::{json, Value};
+
+    use super::Bond;
+
+    /// Parameters of a fixed coupon bond with a denomination of 1000 and the TARGET calendar
+    #[derive(Clone, Copy)]
+    pub(crate) struct BondSpec<'a> {
+        pub currency: &'a str,
+        pub rate: f64,
+        pub coupon_date: &'a str,
+        pub period: &'a str,
+        pub day_count: &'a str,
+        pub business_day_rule: &'a str,
+        pub issue_date: &'a str,
+        pub maturity: &'a str,
+    }
+
+    impl Default for BondSpec<'_> {
+        /// 5% EUR bond with semi-annual act/365 coupons, issued 2019-10-01 and maturing 2021-10-01
+        fn default() -> Self {
+            BondSpec {
+                currency: "EUR",
+                rate: 5.,
+                coupon_date: "01.04",
+                period: "6M",
+                day_count: "act/365",
+                business_day_rule: "none",
+                issue_date: "2019-10-01",
+                maturity: "2021-10-01",
+            }
+        }
+    }
+
+    impl BondSpec<'_> {
+        /// JSON representation of the bond, e.g. to add optional fields like an amortization schedule
+        pub(crate) fn json(&self) -> Value {
+            json!({
+                "bond_type": "bond",
+                "currency": self.currency,
+                "coupon": {
+                    "coupon_type": "fixed",
+                    "rate": self.rate,
+                    "coupon_date": self.coupon_date,
+                    "period": self.period,
+                    "day_count_convention": self.day_count
+                },
+                "business_day_rule": self.business_day_rule,
+                "calendar": "TARGET",
+                "issue_date": self.issue_date,
+                "maturity": self.maturity,
+                "denomination": 1000
+            })
+        }
+
+        pub(crate) fn bond(&self) -> Bond {
+            serde_json::from_value(self.json()).unwrap()
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
+    use chrono::{Local, TimeZone};
+    use finql_data::{Asset, AssetHandler, Quote, QuoteHandler, Ticker};
+    use finql_sqlite::SqliteDBPool;
     use crate::calendar::SimpleCalendar;
-    use crate::market::generate_calendars;
+    use crate::fixed_income::get_cash_flows_after;
+    use crate::market::{generate_calendars, Market};
+    use std::sync::Arc;
+    use crate::rates::Discounter;
+    use serde_json::json;
+    use super::fixtures::BondSpec;
     use super::*;
 
     #[test]
@@ -247,29 +563,30 @@ mod tests {
             "maturity": "2021-10-01",
             "denomination": 1000
         }"#;
-        let bond: Bond = serde_json::from_str(&data).unwrap();
+        let bond: Bond = serde_json::from_str(data).unwrap();
         let calendar = SimpleCalendar::default();
         let cash_flows = bond.rollout_cash_flows(1., &calendar).unwrap();
         assert_eq!(cash_flows.len(), 5);
         let curr = Currency::from_str("EUR").unwrap();
+        // coupons are rounded to cents, e.g. 0.05 * 1000 * 183 / 365 = 25.0685 to 25.07
         let reference_cash_flows = vec![
             CashFlow::new(
-                0.05 * 1000. * 183. / 365.,
+                25.07,
                 curr,
                 NaiveDate::from_ymd(2020, 4, 1),
             ),
             CashFlow::new(
-                0.05 * 1000. * 183. / 365.,
+                25.07,
                 curr,
                 NaiveDate::from_ymd(2020, 10, 1),
             ),
             CashFlow::new(
-                0.05 * 1000. * 182. / 365.,
+                24.93,
                 curr,
                 NaiveDate::from_ymd(2021, 4, 1),
             ),
             CashFlow::new(
-                0.05 * 1000. * 183. / 365.,
+                25.07,
                 curr,
                 NaiveDate::from_ymd(2021, 10, 1),
             ),
@@ -301,7 +618,7 @@ mod tests {
             "maturity": "2022-10-01",
             "denomination": 1000
         }"#;
-        let bond: Bond = serde_json::from_str(&data).unwrap();
+        let bond: Bond = serde_json::from_str(data).unwrap();
         let sample_calendars = generate_calendars();
         let calendar = SimpleCalendar::new(&sample_calendars["TARGET"]);
         let cash_flows = bond.rollout_cash_flows(1., &calendar).unwrap();
@@ -321,4 +638,381 @@ mod tests {
         assert!(reference_cash_flows[3].fuzzy_cash_flows_cmp_eq(&cash_flows[3], tol));
         assert!(reference_cash_flows[4].fuzzy_cash_flows_cmp_eq(&cash_flows[4], tol));
     }
+
+    #[test]
+    fn cash_flow_rollout_rounded_coupons() {
+        let bond = BondSpec {
+            rate: 4.99998,
+            day_count: "icma",
+            issue_date: "2020-10-01",
+            ..Default::default()
+        }.bond();
+        let calendar = SimpleCalendar::default();
+        let cash_flows = bond.rollout_cash_flows(1., &calendar).unwrap();
+        assert_eq!(cash_flows.len(), 3);
+        // raw coupon is 24.9999, which is rounded to 25.00 for EUR
+        assert_eq!(cash_flows[0].amount.amount, 25.0);
+        assert_eq!(cash_flows[1].amount.amount, 25.0);
+        assert_eq!(cash_flows[2].amount.amount, 1000.0);
+    }
+
+    #[test]
+    fn cash_flow_rollout_short_position() {
+        let mut data = BondSpec {
+            rate: 3.37,
+            coupon_date: "15.03",
+            business_day_rule: "modified",
+            issue_date: "2020-10-01",
+            maturity: "2023-03-15",
+            ..Default::default()
+        }.json();
+        data["amortization"] = json!([["2022-03-15", 0.3]]);
+        let bond: Bond = serde_json::from_value(data).unwrap();
+        let calendar = SimpleCalendar::default();
+        let long = bond.rollout_cash_flows(1., &calendar).unwrap();
+        let short = bond.rollout_cash_flows(-1., &calendar).unwrap();
+        assert_eq!(long.len(), short.len());
+        for (long_cf, short_cf) in long.iter().zip(short.iter()) {
+            assert_eq!(short_cf.date, long_cf.date);
+            assert_eq!(short_cf.amount.currency, long_cf.amount.currency);
+            assert_eq!(short_cf.amount.amount, -long_cf.amount.amount);
+            assert!(short_cf.amount.amount < 0.);
+        }
+    }
+
+    #[test]
+    fn cash_flow_rollout_step_up_coupons() {
+        let spec = BondSpec {
+            rate: 3.,
+            day_count: "icma",
+            issue_date: "2020-10-01",
+            maturity: "2022-10-01",
+            ..Default::default()
+        };
+        let mut data = spec.json();
+        data["coupon"]["coupon_schedule"] = json!([["2020-10-01", 3], ["2021-10-01", 5]]);
+        let bond: Bond = serde_json::from_value(data.clone()).unwrap();
+        let calendar = SimpleCalendar::default();
+        let cash_flows = bond.rollout_cash_flows(1., &calendar).unwrap();
+        assert_eq!(cash_flows.len(), 5);
+        let tol = 1e-11;
+        assert_fuzzy_eq!(cash_flows[0].amount.amount, 15.0, tol);
+        assert_fuzzy_eq!(cash_flows[1].amount.amount, 15.0, tol);
+        assert_fuzzy_eq!(cash_flows[2].amount.amount, 25.0, tol);
+        assert_fuzzy_eq!(cash_flows[3].amount.amount, 25.0, tol);
+        assert_fuzzy_eq!(cash_flows[4].amount.amount, 1000.0, tol);
+        assert_fuzzy_eq!(
+            bond.accrued_interest(NaiveDate::from_ymd(2022, 1, 1)).unwrap(),
+            25.0 * 92. / 182.,
+            tol
+        );
+
+        // the seller keeps the coupon within the ex-dividend period
+        assert_fuzzy_eq!(
+            bond.accrued_interest_ex_div(NaiveDate::from_ymd(2022, 1, 1), Some(7)).unwrap(),
+            25.0 * 92. / 182.,
+            tol
+        );
+        assert_fuzzy_eq!(
+            bond.accrued_interest_ex_div(NaiveDate::from_ymd(2022, 3, 28), Some(7)).unwrap(),
+            -25.0 * 4. / 182.,
+            tol
+        );
+        assert_fuzzy_eq!(
+            bond.accrued_interest_ex_div(NaiveDate::from_ymd(2022, 3, 28), None).unwrap(),
+            25.0 * 178. / 182.,
+            tol
+        );
+
+        // a flat single entry schedule is the same as a fixed coupon
+        data["coupon"]["coupon_schedule"] = json!([["2020-10-01", 5]]);
+        let flat: Bond = serde_json::from_value(data).unwrap();
+        let fixed = BondSpec { rate: 5., ..spec }.bond();
+        let flat_cash_flows = flat.rollout_cash_flows(1., &calendar).unwrap();
+        let fixed_cash_flows = fixed.rollout_cash_flows(1., &calendar).unwrap();
+        for (flat_cf, fixed_cf) in flat_cash_flows.iter().zip(fixed_cash_flows.iter()) {
+            assert!(flat_cf.fuzzy_cash_flows_cmp_eq(fixed_cf, tol));
+        }
+    }
+
+    #[test]
+    fn cash_flow_rollout_adjusted_payment_unadjusted_accrual() {
+        let bond = BondSpec {
+            coupon_date: "02.10",
+            period: "1Y",
+            business_day_rule: "following",
+            issue_date: "2020-10-02",
+            maturity: "2022-10-02",
+            ..Default::default()
+        }.bond();
+        let sample_calendars = generate_calendars();
+        let calendar = SimpleCalendar::new(&sample_calendars["TARGET"]);
+        let cash_flows = bond.rollout_cash_flows(1., &calendar).unwrap();
+        assert_eq!(cash_flows.len(), 3);
+        let curr = Currency::from_str("EUR").unwrap();
+        // 2021-10-02 is a Saturday and 2022-10-02 a Sunday, payments roll to the
+        // following Monday, but accrual still runs over 365 unadjusted days
+        let reference_cash_flows = vec![
+            CashFlow::new(0.05 * 1000., curr, NaiveDate::from_ymd(2021, 10, 4)),
+            CashFlow::new(0.05 * 1000., curr, NaiveDate::from_ymd(2022, 10, 3)),
+            CashFlow::new(1000., curr, NaiveDate::from_ymd(2022, 10, 3)),
+        ];
+        let tol = 1e-11;
+        assert!(reference_cash_flows[0].fuzzy_cash_flows_cmp_eq(&cash_flows[0], tol));
+        assert!(reference_cash_flows[1].fuzzy_cash_flows_cmp_eq(&cash_flows[1], tol));
+        assert!(reference_cash_flows[2].fuzzy_cash_flows_cmp_eq(&cash_flows[2], tol));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn cash_flow_ladder_of_amortizing_bond() {
+        let mut data = BondSpec {
+            coupon_date: "01.10",
+            period: "1Y",
+            day_count: "icma",
+            issue_date: "2020-10-01",
+            maturity: "2024-10-01",
+            ..Default::default()
+        }.json();
+        data["amortization"] = json!([["2021-10-01", 0.25], ["2022-10-01", 0.25], ["2023-10-01", 0.25]]);
+        let bond: Bond = serde_json::from_value(data).unwrap();
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let market = Market::new(Arc::new(db));
+        let ladder = bond.cash_flow_ladder(1., &market).unwrap();
+        assert_eq!(ladder.len(), 4);
+        let tol = 1e-11;
+        let coupons = [50., 37.5, 25., 12.5];
+        let remaining = [750., 500., 250., 0.];
+        for (i, row) in ladder.iter().enumerate() {
+            assert_fuzzy_eq!(row.coupon, coupons[i], tol);
+            assert_fuzzy_eq!(row.principal, 250., tol);
+            assert_fuzzy_eq!(row.total, coupons[i] + 250., tol);
+            assert_fuzzy_eq!(row.remaining_notional, remaining[i], tol);
+        }
+        assert_eq!(ladder[3].date, NaiveDate::from_ymd(2024, 10, 1));
+        let json = serde_json::to_string(&ladder[0]).unwrap();
+        assert!(json.contains(r#""remaining_notional":750.0"#));
+
+        let mut csv_data = Vec::new();
+        bond.ladder_to_csv(1., &market, &mut csv_data).unwrap();
+        let csv_data = String::from_utf8(csv_data).unwrap();
+        assert!(csv_data.starts_with("date,coupon,principal,total,remaining_notional\n2021-10-01,50.0,250.0,300.0,750.0\n"));
+        let rows: Vec<LadderRow> = csv::Reader::from_reader(csv_data.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows, ladder);
+
+        #[cfg(feature = "parquet")]
+        {
+            use parquet::file::reader::{FileReader, SerializedFileReader};
+            use parquet::record::{Field, RowAccessor};
+
+            let path = std::env::temp_dir().join(format!("finql_ladder_{}.parquet", std::process::id()));
+            bond.ladder_to_parquet(1., &market, std::fs::File::create(&path).unwrap()).unwrap();
+            let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+            let epoch = NaiveDate::from_ymd(1970, 1, 1);
+            let rows: Vec<LadderRow> = reader
+                .get_row_iter(None)
+                .unwrap()
+                .map(|row| {
+                    let row = row.unwrap();
+                    let days = match row.get_column_iter().next().unwrap() {
+                        (_, Field::Date(days)) => *days,
+                        (name, field) => panic!("unexpected field {} {:?}", name, field),
+                    };
+                    LadderRow {
+                        date: epoch + Duration::days(days as i64),
+                        coupon: row.get_double(1).unwrap(),
+                        principal: row.get_double(2).unwrap(),
+                        total: row.get_double(3).unwrap(),
+                        remaining_notional: row.get_double(4).unwrap(),
+                    }
+                })
+                .collect();
+            assert_eq!(rows, ladder);
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        // rolled out cash flows are consistent with ladder
+        let cash_flows = bond.rollout_cash_flows(1., &market).unwrap();
+        assert_eq!(cash_flows.len(), 8);
+        let total: f64 = cash_flows.iter().map(|cf| cf.amount.amount).sum();
+        assert_fuzzy_eq!(total, 1125., tol);
+        assert_fuzzy_eq!(
+            bond.accrued_interest(NaiveDate::from_ymd(2022, 4, 1)).unwrap(),
+            37.5 * 182. / 365.,
+            tol
+        );
+    }
+
+    #[test]
+    fn dirty_price_yield_round_trip() {
+        let bond = BondSpec {
+            rate: 4.,
+            day_count: "icma",
+            maturity: "2024-10-01",
+            ..Default::default()
+        }.bond();
+        let calendar = SimpleCalendar::default();
+        let curr = Currency::from_str("EUR").unwrap();
+        let settlement = NaiveDate::from_ymd(2020, 1, 15);
+        let purchase = CashFlow::new(-1020., curr, settlement);
+        let ytm = bond.calculate_ytm(&purchase, &calendar).unwrap();
+        let dirty_price = bond.dirty_price_from_yield(ytm, settlement, &calendar).unwrap();
+        assert_eq!(dirty_price.currency, curr);
+        assert_fuzzy_eq!(dirty_price.amount, 1020., 1e-6);
+        // higher yield gives lower price
+        let lower_price = bond.dirty_price_from_yield(ytm + 0.01, settlement, &calendar).unwrap();
+        assert!(lower_price.amount < dirty_price.amount);
+    }
+
+    /// Upward sloping zero curve with continuously compounded rates rising
+    /// by one percentage point per year of maturity, starting at 1%
+    struct UpwardSlopingCurve {
+        currency: Currency,
+    }
+
+    impl Discounter for UpwardSlopingCurve {
+        fn discount_factor(&self, today: NaiveDate, pay_date: NaiveDate) -> f64 {
+            let t = pay_date.signed_duration_since(today).num_days() as f64 / 365.;
+            (-(0.01 + 0.01 * t) * t).exp()
+        }
+
+        fn currency(&self) -> Currency {
+            self.currency
+        }
+    }
+
+    #[test]
+    fn carry_and_rolldown_on_upward_sloping_curve() {
+        let bond = BondSpec {
+            coupon_date: "01.01",
+            period: "1Y",
+            day_count: "icma",
+            issue_date: "2020-01-01",
+            maturity: "2025-01-01",
+            ..Default::default()
+        }.bond();
+        let calendar = SimpleCalendar::default();
+        let curr = Currency::from_str("EUR").unwrap();
+        let curve = UpwardSlopingCurve { currency: curr };
+        let today = NaiveDate::from_ymd(2021, 1, 1);
+        let horizon = NaiveDate::from_ymd(2022, 1, 1);
+
+        let cash_flows = bond.rollout_cash_flows(1., &calendar).unwrap();
+        let fair_value = curve
+            .discount_cash_flow_stream(&get_cash_flows_after(&cash_flows, today), today)
+            .unwrap()
+            .amount;
+        let price = CashFlow::new(-fair_value, curr, today);
+        let (carry, rolldown) = bond
+            .carry_and_rolldown(&price, &curve, horizon, &calendar)
+            .unwrap();
+        assert!(rolldown > 0.);
+        assert!(carry > 0.);
+
+        // carry and roll-down add up to the total P&L on the unchanged curve
+        let horizon_value = curve
+            .discount_cash_flow_stream(&get_cash_flows_after(&cash_flows, horizon), horizon)
+            .unwrap()
+            .amount;
+        let total_pnl = 50. + horizon_value - fair_value;
+        assert_fuzzy_eq!(carry + rolldown, total_pnl, 1e-6);
+    }
+
+    #[test]
+    fn carry_and_rolldown_of_bond_priced_off_the_curve() {
+        let bond = BondSpec {
+            coupon_date: "01.01",
+            period: "1Y",
+            day_count: "icma",
+            issue_date: "2020-01-01",
+            maturity: "2025-01-01",
+            ..Default::default()
+        }.bond();
+        let calendar = SimpleCalendar::default();
+        let curr = Currency::from_str("EUR").unwrap();
+        let curve = UpwardSlopingCurve { currency: curr };
+        let today = NaiveDate::from_ymd(2021, 1, 1);
+        let horizon = NaiveDate::from_ymd(2022, 1, 1);
+
+        let cash_flows = bond.rollout_cash_flows(1., &calendar).unwrap();
+        let fair_value = curve
+            .discount_cash_flow_stream(&get_cash_flows_after(&cash_flows, today), today)
+            .unwrap()
+            .amount;
+        // trading cheap to the curve
+        let price = CashFlow::new(20. - fair_value, curr, today);
+        let spread = bond.z_spread(&price, &curve, &calendar).unwrap();
+        assert!(spread > 0.);
+        let (carry, rolldown) = bond
+            .carry_and_rolldown(&price, &curve, horizon, &calendar)
+            .unwrap();
+        assert!(rolldown > 0.);
+
+        // at the horizon, the bond is valued with the same spread over the unchanged curve
+        let remaining_cash_flows = get_cash_flows_after(&cash_flows, horizon);
+        let horizon_value: f64 = remaining_cash_flows
+            .iter()
+            .map(|cf| {
+                let t = (cf.date - horizon).num_days() as f64 / 365.;
+                cf.amount.amount * curve.discount_factor(horizon, cf.date) * (1. + spread).powf(-t)
+            })
+            .sum();
+        let total_pnl = 50. + horizon_value + price.amount.amount;
+        assert_fuzzy_eq!(carry + rolldown, total_pnl, 1e-6);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn clean_quote_from_stored_dirty_quote() {
+        let bond = BondSpec::default().bond();
+
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let asset_id = db
+            .insert_asset(&Asset {
+                id: None,
+                name: "5% Bond 2021".to_string(),
+                wkn: None,
+                isin: None,
+                note: None,
+            })
+            .await.unwrap();
+        let ticker_id = db
+            .insert_ticker(&Ticker {
+                id: None,
+                asset: asset_id,
+                name: "BOND21".to_string(),
+                currency: Currency::from_str("EUR").unwrap(),
+                source: "manual".to_string(),
+                priority: 1,
+                factor: 1.0,
+                tz: None,
+                cal: None,
+            })
+            .await.unwrap();
+        let time = Local.ymd(2020, 1, 15).and_hms_milli(18, 0, 0, 0);
+        db.insert_quote(&Quote {
+                id: None,
+                ticker: ticker_id,
+                price: 1020.,
+                time,
+                volume: None,
+                bid: None,
+                ask: None,
+            })
+            .await.unwrap();
+
+        let quotes = db.get_all_quotes_for_ticker(ticker_id).await.unwrap();
+        assert_eq!(quotes.len(), 1);
+        let clean = bond.clean_quote(&quotes[0]).unwrap();
+        let tol = 1e-11;
+        // 106 days accrued since the last coupon date 2019-10-01
+        assert_fuzzy_eq!(clean.price, 1020. - 0.05 * 1000. * 106. / 365., tol);
+        assert_eq!(clean.ticker, ticker_id);
+        assert_eq!(clean.time, time);
+    }
 }
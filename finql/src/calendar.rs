@@ -12,6 +12,8 @@ use chrono::{Datelike, Duration, NaiveDate, Weekday};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 
+use crate::day_adjust::DayAdjust;
+
 /// Specifies the nth week of a month
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub enum NthWeek {
@@ -48,6 +50,9 @@ pub enum Holiday {
     },
     /// A single holiday which is valid only once in time.
     SingularDay(NaiveDate),
+    /// A single holiday which, if it falls on a Saturday, is observed on the preceding Friday,
+    /// and if it falls on a Sunday, is observed on the following Monday.
+    SingularDayObserved(NaiveDate),
     /// A holiday that is defined in relative days (e.g. -2 for Good Friday) to Easter (Sunday).
     EasterOffset {
         offset: i32,
@@ -88,6 +93,17 @@ impl Calendar {
                         holidays.insert(*date);
                     }
                 }
+                Holiday::SingularDayObserved(date) => {
+                    let year = date.year();
+                    if year >= start && year <= end {
+                        let observed = match date.weekday() {
+                            Weekday::Sat => *date - Duration::days(1),
+                            Weekday::Sun => *date + Duration::days(1),
+                            _ => *date,
+                        };
+                        holidays.insert(observed);
+                    }
+                }
                 Holiday::WeekDay(weekday) => {
                     weekdays.push(*weekday);
                 }
@@ -190,6 +206,73 @@ impl Calendar {
         date
     }
 
+    /// Iterate over all business days in the range from `start` to `end`, both inclusive
+    pub fn business_days(&self, start: NaiveDate, end: NaiveDate) -> impl Iterator<Item = NaiveDate> + '_ {
+        let first = if self.is_business_day(start) {
+            start
+        } else {
+            self.next_bday(start)
+        };
+        std::iter::successors(Some(first), move |date| Some(self.next_bday(*date)))
+            .take_while(move |date| *date <= end)
+    }
+
+    /// Number of business days in the range from `start` (exclusive) to `end` (inclusive),
+    /// or the negative number of business days from `end` (exclusive) to `start` (inclusive)
+    /// if `end` precedes `start`
+    pub fn bdays_between(&self, start: NaiveDate, end: NaiveDate) -> i64 {
+        if end < start {
+            return -self.bdays_between(end, start);
+        }
+        self.business_days(start + Duration::days(1), end).count() as i64
+    }
+
+    /// Move `n` business days forward, or backward if `n` is negative, starting from `date`
+    pub fn add_bdays(&self, date: NaiveDate, n: i64) -> NaiveDate {
+        let mut date = date;
+        for _ in 0..n.abs() {
+            date = if n > 0 {
+                self.next_bday(date)
+            } else {
+                self.prev_bday(date)
+            };
+        }
+        date
+    }
+
+    /// Combine two calendars such that a day is a holiday if it is a holiday in either calendar
+    pub fn union(&self, other: &Calendar) -> Calendar {
+        let mut weekdays = self.weekdays.clone();
+        for weekday in &other.weekdays {
+            if !weekdays.contains(weekday) {
+                weekdays.push(*weekday);
+            }
+        }
+        Calendar {
+            holidays: self.holidays.union(&other.holidays).cloned().collect(),
+            weekdays,
+        }
+    }
+
+    /// Combine two calendars such that a day is a holiday only if it is a holiday in both calendars
+    pub fn intersection(&self, other: &Calendar) -> Calendar {
+        let weekdays: Vec<Weekday> = self
+            .weekdays
+            .iter()
+            .filter(|weekday| other.weekdays.contains(weekday))
+            .cloned()
+            .collect();
+        // holidays of one calendar may fall on a weekend day of the other calendar
+        let holidays = self
+            .holidays
+            .iter()
+            .filter(|date| !other.is_business_day(**date))
+            .chain(other.holidays.iter().filter(|date| !self.is_business_day(**date)))
+            .cloned()
+            .collect();
+        Calendar { holidays, weekdays }
+    }
+
     fn calc_first_and_last(
         start: i32,
         end: i32,
@@ -218,15 +301,26 @@ impl Calendar {
         false
     }
 
-    /// Returns true if the specified day is a bank holiday
+    /// Returns true if the specified day is a bank holiday. Weekend days are not
+    /// considered to be holidays, unless they are holidays by another rule.
     pub fn is_holiday(&self, date: NaiveDate) -> bool {
-        self.holidays.get(&date).is_some()
+        self.holidays.contains(&date)
     }
 
     /// Returns true if the specified day is a business day
     pub fn is_business_day(&self, date: NaiveDate) -> bool {
         !self.is_weekend(date) && !self.is_holiday(date)
     }
+
+    /// Adjust `date` to a business day according to the given rule
+    pub fn adjust(&self, date: NaiveDate, conv: DayAdjust) -> NaiveDate {
+        conv.adjust_date(date, self)
+    }
+
+    /// Short form of `is_business_day`, in line with `next_bday` and `prev_bday`
+    pub fn is_bday(&self, date: NaiveDate) -> bool {
+        self.is_business_day(date)
+    }
 }
 
 pub struct CalendarNotFound {}
@@ -424,6 +518,16 @@ mod tests {
         let cal = Calendar::calc_calendar(&holidays, 2019, 2020);
         assert_eq!(false, cal.is_business_day(NaiveDate::from_ymd(2019, 4, 19)));
         assert_eq!(false, cal.is_business_day(NaiveDate::from_ymd(2020, 4, 10)));
+
+        // Good Friday and Easter Monday
+        let holidays = vec![
+            Holiday::EasterOffset { offset: -2, first: None, last: None },
+            Holiday::EasterOffset { offset: 1, first: None, last: None },
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2021, 2021);
+        assert!(cal.is_holiday(NaiveDate::from_ymd(2021, 4, 2)));
+        assert!(cal.is_holiday(NaiveDate::from_ymd(2021, 4, 5)));
+        assert!(cal.is_business_day(NaiveDate::from_ymd(2021, 4, 1)));
     }
 
     #[test]
@@ -575,4 +679,165 @@ mod tests {
             assert_eq!(holidays[i], holidays2[i]);
         }
     }
+
+    #[test]
+    fn iterate_business_days() {
+        let holidays = vec![
+            Holiday::SingularDay(NaiveDate::from_ymd(2019, 11, 20)),
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2019, 2019);
+        let days: Vec<NaiveDate> = cal
+            .business_days(NaiveDate::from_ymd(2019, 11, 16), NaiveDate::from_ymd(2019, 11, 24))
+            .collect();
+        assert_eq!(
+            days,
+            vec![
+                NaiveDate::from_ymd(2019, 11, 18),
+                NaiveDate::from_ymd(2019, 11, 19),
+                NaiveDate::from_ymd(2019, 11, 21),
+                NaiveDate::from_ymd(2019, 11, 22),
+            ]
+        );
+        let days: Vec<NaiveDate> = cal
+            .business_days(NaiveDate::from_ymd(2019, 11, 22), NaiveDate::from_ymd(2019, 11, 22))
+            .collect();
+        assert_eq!(days, vec![NaiveDate::from_ymd(2019, 11, 22)]);
+        assert_eq!(
+            cal.business_days(NaiveDate::from_ymd(2019, 11, 23), NaiveDate::from_ymd(2019, 11, 24))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn combine_calendars() {
+        let cal1 = Calendar::calc_calendar(
+            &[
+                Holiday::SingularDay(NaiveDate::from_ymd(2021, 11, 10)),
+                Holiday::SingularDay(NaiveDate::from_ymd(2021, 11, 11)),
+                Holiday::WeekDay(Weekday::Sat),
+                Holiday::WeekDay(Weekday::Sun),
+            ],
+            2021,
+            2021,
+        );
+        let cal2 = Calendar::calc_calendar(
+            &[
+                Holiday::SingularDay(NaiveDate::from_ymd(2021, 11, 11)),
+                Holiday::SingularDay(NaiveDate::from_ymd(2021, 11, 12)),
+                Holiday::SingularDay(NaiveDate::from_ymd(2021, 11, 13)),
+                Holiday::WeekDay(Weekday::Fri),
+                Holiday::WeekDay(Weekday::Sun),
+            ],
+            2021,
+            2021,
+        );
+        let union = cal1.union(&cal2);
+        for day in [10, 11, 12, 13, 14, 19, 20] {
+            assert!(!union.is_business_day(NaiveDate::from_ymd(2021, 11, day)));
+        }
+        assert_eq!(union.next_bday(NaiveDate::from_ymd(2021, 11, 9)), NaiveDate::from_ymd(2021, 11, 15));
+        assert_eq!(union.prev_bday(NaiveDate::from_ymd(2021, 11, 15)), NaiveDate::from_ymd(2021, 11, 9));
+
+        let intersection = cal1.intersection(&cal2);
+        // holiday in both calendars
+        assert!(!intersection.is_business_day(NaiveDate::from_ymd(2021, 11, 11)));
+        // holiday in the first calendar only
+        assert!(intersection.is_business_day(NaiveDate::from_ymd(2021, 11, 10)));
+        // Friday holiday and weekend day in the second, but business day in the first calendar
+        assert!(intersection.is_business_day(NaiveDate::from_ymd(2021, 11, 12)));
+        assert!(intersection.is_business_day(NaiveDate::from_ymd(2021, 11, 19)));
+        // Saturday weekend day in the first calendar and holiday in the second
+        assert!(!intersection.is_business_day(NaiveDate::from_ymd(2021, 11, 13)));
+        assert!(intersection.is_business_day(NaiveDate::from_ymd(2021, 11, 20)));
+        assert!(!intersection.is_business_day(NaiveDate::from_ymd(2021, 11, 14)));
+        assert_eq!(intersection.next_bday(NaiveDate::from_ymd(2021, 11, 12)), NaiveDate::from_ymd(2021, 11, 15));
+    }
+
+    #[test]
+    fn count_and_add_business_days() {
+        let holidays = vec![
+            Holiday::SingularDay(NaiveDate::from_ymd(2019, 11, 20)),
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2019, 2019);
+        let fri = NaiveDate::from_ymd(2019, 11, 15);
+        let sat = NaiveDate::from_ymd(2019, 11, 16);
+        let fri2 = NaiveDate::from_ymd(2019, 11, 22);
+        assert_eq!(cal.bdays_between(fri, fri2), 4);
+        assert_eq!(cal.bdays_between(fri2, fri), -4);
+        assert_eq!(cal.bdays_between(sat, fri2), 4);
+        assert_eq!(cal.bdays_between(fri, fri), 0);
+        assert_eq!(cal.bdays_between(fri, sat), 0);
+
+        assert_eq!(cal.add_bdays(fri, 3), NaiveDate::from_ymd(2019, 11, 21));
+        assert_eq!(cal.add_bdays(fri2, -3), NaiveDate::from_ymd(2019, 11, 18));
+        assert_eq!(cal.add_bdays(sat, 1), NaiveDate::from_ymd(2019, 11, 18));
+        assert_eq!(cal.add_bdays(sat, 0), sat);
+        for n in -5..=5 {
+            assert_eq!(cal.bdays_between(fri, cal.add_bdays(fri, n)), n);
+        }
+    }
+
+    #[test]
+    fn query_business_days_and_holidays() {
+        let holidays = vec![
+            Holiday::SingularDay(NaiveDate::from_ymd(2019, 11, 20)),
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2019, 2019);
+        let saturday = NaiveDate::from_ymd(2019, 11, 16);
+        assert!(!cal.is_bday(saturday));
+        assert!(!cal.is_holiday(saturday));
+        assert!(cal.is_weekend(saturday));
+        let singular_day = NaiveDate::from_ymd(2019, 11, 20);
+        assert!(!cal.is_bday(singular_day));
+        assert!(cal.is_holiday(singular_day));
+        let tuesday = NaiveDate::from_ymd(2019, 11, 19);
+        assert!(cal.is_bday(tuesday));
+        assert!(!cal.is_holiday(tuesday));
+        assert_eq!(cal.next_bday(tuesday), NaiveDate::from_ymd(2019, 11, 21));
+    }
+
+    #[test]
+    fn observe_singular_days_on_weekends() {
+        let holidays = vec![
+            Holiday::SingularDayObserved(NaiveDate::from_ymd(2020, 7, 4)),
+            Holiday::SingularDayObserved(NaiveDate::from_ymd(2021, 7, 4)),
+            Holiday::SingularDayObserved(NaiveDate::from_ymd(2019, 7, 4)),
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2019, 2021);
+        // Saturday, observed on Friday
+        assert!(cal.is_holiday(NaiveDate::from_ymd(2020, 7, 3)));
+        assert!(!cal.is_holiday(NaiveDate::from_ymd(2020, 7, 4)));
+        // Sunday, observed on Monday
+        assert!(cal.is_holiday(NaiveDate::from_ymd(2021, 7, 5)));
+        // Thursday
+        assert!(cal.is_holiday(NaiveDate::from_ymd(2019, 7, 4)));
+        assert!(cal.is_business_day(NaiveDate::from_ymd(2019, 7, 5)));
+    }
+
+    #[test]
+    fn adjust_to_business_days() {
+        let holidays = vec![
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2021, 2021);
+        // Saturday, the following Monday is in the next month
+        let date = NaiveDate::from_ymd(2021, 7, 31);
+        assert_eq!(cal.adjust(date, DayAdjust::None), date);
+        assert_eq!(cal.adjust(date, DayAdjust::Following), NaiveDate::from_ymd(2021, 8, 2));
+        assert_eq!(cal.adjust(date, DayAdjust::Modified), NaiveDate::from_ymd(2021, 7, 30));
+        assert_eq!(cal.adjust(date, DayAdjust::Preceding), NaiveDate::from_ymd(2021, 7, 30));
+        // Sunday in mid month
+        let date = NaiveDate::from_ymd(2021, 7, 18);
+        assert_eq!(cal.adjust(date, DayAdjust::Modified), NaiveDate::from_ymd(2021, 7, 19));
+    }
 }
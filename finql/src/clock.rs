@@ -0,0 +1,44 @@
+//! Source of the current time
+//! Functions that depend on the current time take a `Clock` instead of
+//! calling `Local::now()`, so that they can be tested deterministically.
+
+use chrono::{DateTime, Local, NaiveDate};
+
+/// Provider of the current time
+pub trait Clock {
+    /// Current time
+    fn now(&self) -> DateTime<Local>;
+
+    /// Current (local) date
+    fn today(&self) -> NaiveDate {
+        self.now().naive_local().date()
+    }
+}
+
+/// Clock returning the system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// Clock that always returns the same, fixed time
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock {
+    time: DateTime<Local>,
+}
+
+impl FixedClock {
+    pub fn new(time: DateTime<Local>) -> FixedClock {
+        FixedClock { time }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Local> {
+        self.time
+    }
+}
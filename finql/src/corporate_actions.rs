@@ -0,0 +1,272 @@
+//! Corporate actions like splits or dividends and adjustment of quotes
+//! The stored quotes are never modified, instead prices are back-adjusted
+//! on read, such that quotes before a corporate action are comparable to
+//! quotes after the action.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use finql_data::{CashAmount, Currency, DataError, Quote, QuoteHandler, Ticker};
+use finql_data::date_time_helper::naive_date_to_date_time;
+
+/// Types of corporate actions affecting the price of an asset
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CorporateActionType {
+    /// Stock split, where each share is replaced by `ratio` new shares
+    Split(f64),
+    /// Cash dividend paid per share
+    Dividend(CashAmount),
+}
+
+/// Corporate action for an asset, effective on the ex-date
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CorporateAction {
+    pub asset_id: usize,
+    pub ex_date: NaiveDate,
+    pub action: CorporateActionType,
+}
+
+/// Whether quotes are returned as stored or adjusted for corporate actions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdjustmentPolicy {
+    /// Return raw stored quotes
+    None,
+    /// Back-adjust quotes for all corporate actions after the quote's time
+    Adjusted,
+}
+
+/// Quote reader that applies corporate actions to quotes read from a quote handler
+pub struct AdjustedQuotes {
+    db: Arc<dyn QuoteHandler + Send + Sync>,
+    actions: Vec<CorporateAction>,
+}
+
+impl AdjustedQuotes {
+    pub fn new(db: Arc<dyn QuoteHandler + Send + Sync>, actions: Vec<CorporateAction>) -> AdjustedQuotes {
+        AdjustedQuotes { db, actions }
+    }
+
+    /// Ex-dates and factors of all corporate actions of the ticker's asset. Dividends are related
+    /// to the last price of the ticker before the ex-date, taken from the ticker's `quotes` sorted
+    /// by time, and must be paid in the ticker's currency.
+    fn action_factors(&self, ticker: &Ticker, quotes: &[Quote]) -> Result<Vec<(NaiveDate, f64)>, DataError> {
+        let mut factors = Vec::new();
+        for action in self.actions.iter().filter(|a| a.asset_id == ticker.asset) {
+            let factor = match action.action {
+                CorporateActionType::Split(ratio) => 1.0 / ratio,
+                CorporateActionType::Dividend(dividend) => {
+                    if dividend.currency != ticker.currency {
+                        return Err(DataError::InvalidData(format!(
+                            "dividend currency {} does not match currency {} of ticker {}",
+                            dividend.currency, ticker.currency, ticker.name
+                        )));
+                    }
+                    let ex_time = naive_date_to_date_time(&action.ex_date, 0, None)
+                        .map_err(|e| DataError::NotFound(e.to_string()))?;
+                    // Last price before the ex-date, including the dividend
+                    let idx = quotes.partition_point(|q| q.time < ex_time);
+                    let price = match idx {
+                        0 => return Err(DataError::NotFound(format!(
+                            "no quote of ticker {} before ex-date {}", ticker.name, action.ex_date
+                        ))),
+                        _ => quotes[idx - 1].price,
+                    };
+                    if price <= 0.0 {
+                        return Err(DataError::InvalidData(format!(
+                            "non-positive price of ticker {} before ex-date {}", ticker.name, action.ex_date
+                        )));
+                    }
+                    1.0 - dividend.amount / price
+                }
+            };
+            factors.push((action.ex_date, factor));
+        }
+        Ok(factors)
+    }
+
+    /// Factor to multiply prices of the given ticker quoted at `time` with,
+    /// to make them comparable to today's prices
+    pub async fn adjustment_factor(
+        &self,
+        ticker_id: usize,
+        time: DateTime<Local>,
+    ) -> Result<f64, DataError> {
+        let ticker = self.db.get_ticker_by_id(ticker_id).await?;
+        let quotes = self.db.get_all_quotes_for_ticker(ticker_id).await?;
+        let factors = self.action_factors(&ticker, &quotes)?;
+        Ok(factor_before(&factors, time))
+    }
+
+    /// Get the last quote for an asset on or before the given time
+    pub async fn get_last_quote_before_by_id(
+        &self,
+        asset_id: usize,
+        time: DateTime<Local>,
+        adjust: AdjustmentPolicy,
+    ) -> Result<(Quote, Currency), DataError> {
+        let (mut quote, currency) = self.db.get_last_quote_before_by_id(asset_id, time).await?;
+        if adjust == AdjustmentPolicy::Adjusted {
+            quote.price *= self.adjustment_factor(quote.ticker, quote.time).await?;
+        }
+        Ok((quote, currency))
+    }
+
+    /// Get all quotes of a ticker
+    pub async fn get_all_quotes_for_ticker(
+        &self,
+        ticker_id: usize,
+        adjust: AdjustmentPolicy,
+    ) -> Result<Vec<Quote>, DataError> {
+        let mut quotes = self.db.get_all_quotes_for_ticker(ticker_id).await?;
+        if adjust == AdjustmentPolicy::None {
+            return Ok(quotes);
+        }
+        let ticker = self.db.get_ticker_by_id(ticker_id).await?;
+        let factors = self.action_factors(&ticker, &quotes)?;
+        for quote in quotes.iter_mut() {
+            quote.price *= factor_before(&factors, quote.time);
+        }
+        Ok(quotes)
+    }
+}
+
+/// Product of all factors with ex-date after the date of `time`
+fn factor_before(factors: &[(NaiveDate, f64)], time: DateTime<Local>) -> f64 {
+    let date = time.naive_local().date();
+    factors
+        .iter()
+        .filter(|(ex_date, _)| *ex_date > date)
+        .map(|(_, factor)| factor)
+        .product()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    use finql_data::Asset;
+    use finql_data::date_time_helper::make_time;
+    use finql_sqlite::SqliteDBPool;
+
+    async fn prepare_db(db: Arc<dyn QuoteHandler + Send + Sync>) -> (usize, usize) {
+        let asset_id = db
+            .insert_asset(&Asset {
+                id: None,
+                name: "Split AG".to_string(),
+                wkn: None,
+                isin: None,
+                note: None,
+            })
+            .await.unwrap();
+        let ticker_id = db
+            .insert_ticker(&Ticker {
+                id: None,
+                asset: asset_id,
+                name: "SPLIT".to_string(),
+                currency: Currency::from_str("EUR").unwrap(),
+                source: "manual".to_string(),
+                priority: 1,
+                factor: 1.0,
+                tz: None,
+                cal: None,
+            })
+            .await.unwrap();
+        for (price, time) in [
+            (100.0, make_time(2021, 1, 4, 18, 0, 0).unwrap()),
+            (52.0, make_time(2021, 2, 1, 18, 0, 0).unwrap()),
+            (50.0, make_time(2021, 3, 1, 18, 0, 0).unwrap()),
+        ] {
+            db.insert_quote(&Quote { id: None, ticker: ticker_id, price, time, volume: None, bid: None, ask: None })
+                .await.unwrap();
+        }
+        (asset_id, ticker_id)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn split_adjusted_quotes() {
+        let tol = 1e-11;
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let db: Arc<dyn QuoteHandler + Send + Sync> = Arc::new(db);
+        let (asset_id, ticker_id) = prepare_db(db.clone()).await;
+
+        let split = CorporateAction {
+            asset_id,
+            ex_date: NaiveDate::from_ymd(2021, 2, 1),
+            action: CorporateActionType::Split(2.0),
+        };
+        let quotes = AdjustedQuotes::new(db.clone(), vec![split]);
+        let time = make_time(2021, 1, 5, 0, 0, 0).unwrap();
+
+        let (quote, _) = quotes.get_last_quote_before_by_id(asset_id, time, AdjustmentPolicy::Adjusted).await.unwrap();
+        assert_fuzzy_eq!(quote.price, 50.0, tol);
+        let (quote, _) = quotes.get_last_quote_before_by_id(asset_id, time, AdjustmentPolicy::None).await.unwrap();
+        assert_fuzzy_eq!(quote.price, 100.0, tol);
+
+        let adjusted = quotes.get_all_quotes_for_ticker(ticker_id, AdjustmentPolicy::Adjusted).await.unwrap();
+        assert_fuzzy_eq!(adjusted[0].price, 50.0, tol);
+        assert_fuzzy_eq!(adjusted[1].price, 52.0, tol);
+        assert_fuzzy_eq!(adjusted[2].price, 50.0, tol);
+
+        // stored quotes are left unchanged
+        let stored = db.get_all_quotes_for_ticker(ticker_id).await.unwrap();
+        assert_fuzzy_eq!(stored[0].price, 100.0, tol);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn dividend_adjusted_quotes() {
+        let tol = 1e-11;
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let db: Arc<dyn QuoteHandler + Send + Sync> = Arc::new(db);
+        let (asset_id, ticker_id) = prepare_db(db.clone()).await;
+
+        let eur_dividend = CorporateAction {
+            asset_id,
+            ex_date: NaiveDate::from_ymd(2021, 3, 1),
+            action: CorporateActionType::Dividend(CashAmount { amount: 2.6, currency: Currency::from_str("EUR").unwrap() }),
+        };
+        let quotes = AdjustedQuotes::new(db.clone(), vec![eur_dividend]);
+        let adjusted = quotes.get_all_quotes_for_ticker(ticker_id, AdjustmentPolicy::Adjusted).await.unwrap();
+        // last price before ex-date is 52.0, i.e. the dividend is 5% of the price
+        assert_fuzzy_eq!(adjusted[0].price, 95.0, tol);
+        assert_fuzzy_eq!(adjusted[1].price, 49.4, tol);
+        assert_fuzzy_eq!(adjusted[2].price, 50.0, tol);
+        let time = make_time(2021, 2, 2, 0, 0, 0).unwrap();
+        let (quote, _) = quotes.get_last_quote_before_by_id(asset_id, time, AdjustmentPolicy::Adjusted).await.unwrap();
+        assert_fuzzy_eq!(quote.price, 49.4, tol);
+
+        // dividends in other currencies than the ticker's currency are rejected
+        let dividend = CorporateAction {
+            asset_id,
+            ex_date: NaiveDate::from_ymd(2021, 3, 1),
+            action: CorporateActionType::Dividend(CashAmount { amount: 3.0, currency: Currency::from_str("USD").unwrap() }),
+        };
+        let quotes = AdjustedQuotes::new(db.clone(), vec![dividend]);
+        assert!(matches!(
+            quotes.get_all_quotes_for_ticker(ticker_id, AdjustmentPolicy::Adjusted).await,
+            Err(DataError::InvalidData(_))
+        ));
+
+        // a zero price before the ex-date can't be used to adjust for a dividend
+        db.insert_quote(&Quote {
+            id: None,
+            ticker: ticker_id,
+            price: 0.0,
+            time: make_time(2021, 2, 26, 18, 0, 0).unwrap(),
+            volume: None,
+            bid: None,
+            ask: None,
+        }).await.unwrap();
+        let quotes = AdjustedQuotes::new(db.clone(), vec![eur_dividend]);
+        assert!(matches!(
+            quotes.adjustment_factor(ticker_id, make_time(2021, 1, 5, 0, 0, 0).unwrap()).await,
+            Err(DataError::InvalidData(_))
+        ));
+    }
+}
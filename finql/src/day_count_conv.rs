@@ -26,6 +26,11 @@ pub enum DayCountConv {
     D30_360,
     #[serde(rename = "30E/360")]
     D30E360,
+    #[serde(rename = "30/360 us")]
+    #[serde(alias = "30/360 nasd")]
+    D30_360US,
+    #[serde(rename = "act/act isda")]
+    ActActISDA,
 }
 
 /// Specify a day count method error,
@@ -80,6 +85,9 @@ impl DayCountConv {
         match self {
             DayCountConv::Act365 => Ok(since(end, start).num_days() as f64 / 365.),
             DayCountConv::Act365l => Ok(DayCountConv::calc_act_365_leap(start, end)),
+            // Days in leap and non-leap years are counted separately, which is exactly
+            // what the act/365leap implementation does
+            DayCountConv::ActActISDA => Ok(DayCountConv::calc_act_365_leap(start, end)),
             DayCountConv::Act360 => Ok(since(end, start).num_days() as f64 / 360.),
             // Check that this method is not applied to scenarios where it does not yield sensible results.
             // E.g. for one-day periods from 30th to 31st of the same month, with zero result
@@ -100,6 +108,15 @@ impl DayCountConv {
                     Ok(yf)
                 }
             }
+            // Same as above
+            DayCountConv::D30_360US => {
+                let yf = DayCountConv::calc_30_360_us(start, end);
+                if yf == 0. && start != end {
+                    Err(DayCountConvError::Impossible360)
+                } else {
+                    Ok(yf)
+                }
+            }
             DayCountConv::ActActICMA => match roll_date {
                 None => Err(DayCountConvError::IcmaMissingRollDate),
                 Some(roll_date) => match time_period {
@@ -121,9 +138,14 @@ impl DayCountConv {
             / DayCountConv::days_in_year(start.year()) as f64
     }
 
+    /// Year fraction of full years and months between two dates, ignoring the days
+    fn years_and_months(start: NaiveDate, end: NaiveDate) -> f64 {
+        (end.year() - start.year()) as f64 + (end.month() as i32 - start.month() as i32) as f64 / 12.
+    }
+
     /// Implementation of 30/360 day count method
     fn calc_30_360(start: NaiveDate, end: NaiveDate) -> f64 {
-        let yf = (end.year() - start.year()) as f64 + (end.month() - start.month()) as f64 / 12.;
+        let yf = DayCountConv::years_and_months(start, end);
         let start_day = std::cmp::min(start.day(), 30) as i32;
         let end_day = if start_day == 30 && end.day() == 31 {
             30
@@ -135,12 +157,32 @@ impl DayCountConv {
 
     /// Implementation of 30E/360 day count method
     fn calc_30_e_360(start: NaiveDate, end: NaiveDate) -> f64 {
-        (end.year() - start.year()) as f64
-            + (end.month() - start.month()) as f64 / 12.
+        DayCountConv::years_and_months(start, end)
             + (std::cmp::min(end.day(), 30) as i32 - std::cmp::min(start.day(), 30) as i32) as f64
                 / 360.
     }
 
+    /// Implementation of 30/360 US (NASD) day count method, which in addition to 30/360
+    /// treats the last day of February as the 30th
+    fn calc_30_360_us(start: NaiveDate, end: NaiveDate) -> f64 {
+        let is_end_of_february = |date: NaiveDate| {
+            date.month() == 2 && date.succ_opt().is_some_and(|next| next.month() == 3)
+        };
+        let mut start_day = start.day();
+        let mut end_day = end.day();
+        if is_end_of_february(start) {
+            if is_end_of_february(end) {
+                end_day = 30;
+            }
+            start_day = 30;
+        }
+        if end_day == 31 && start_day >= 30 {
+            end_day = 30;
+        }
+        let start_day = std::cmp::min(start_day, 30);
+        DayCountConv::years_and_months(start, end) + (end_day as i32 - start_day as i32) as f64 / 360.
+    }
+
     fn calc_act_act_icma(
         start: NaiveDate,
         end: NaiveDate,
@@ -298,6 +340,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn calc_year_fractions_edge_cases() {
+        let tol = 1e-11;
+        let yf = |dcc: DayCountConv, start: NaiveDate, end: NaiveDate| {
+            dcc.year_fraction(start, end, None, None).unwrap()
+        };
+
+        // periods crossing the turn of the year
+        let start = NaiveDate::from_ymd(2019, 11, 30);
+        let end = NaiveDate::from_ymd(2020, 2, 29);
+        assert_fuzzy_eq!(yf(DayCountConv::D30_360, start, end), 89. / 360., tol);
+        assert_fuzzy_eq!(yf(DayCountConv::D30E360, start, end), 89. / 360., tol);
+        assert_fuzzy_eq!(yf(DayCountConv::D30_360US, start, end), 89. / 360., tol);
+
+        // end of February
+        let start = NaiveDate::from_ymd(2019, 2, 28);
+        let end = NaiveDate::from_ymd(2020, 2, 29);
+        assert_fuzzy_eq!(yf(DayCountConv::D30_360, start, end), 361. / 360., tol);
+        assert_fuzzy_eq!(yf(DayCountConv::D30_360US, start, end), 1., tol);
+        let end = NaiveDate::from_ymd(2019, 3, 31);
+        assert_fuzzy_eq!(yf(DayCountConv::D30_360US, start, end), 30. / 360., tol);
+        assert_fuzzy_eq!(yf(DayCountConv::D30E360, start, end), 32. / 360., tol);
+
+        // 31st is treated as 30th on both legs in 30E/360 only
+        let start = NaiveDate::from_ymd(2019, 1, 15);
+        let end = NaiveDate::from_ymd(2019, 3, 31);
+        assert_fuzzy_eq!(yf(DayCountConv::D30E360, start, end), 75. / 360., tol);
+        assert_fuzzy_eq!(yf(DayCountConv::D30_360, start, end), 76. / 360., tol);
+        assert_fuzzy_eq!(yf(DayCountConv::D30_360US, start, end), 76. / 360., tol);
+
+        // act/act ISDA splits periods into leap and non-leap years
+        let start = NaiveDate::from_ymd(2019, 12, 1);
+        let end = NaiveDate::from_ymd(2021, 1, 31);
+        assert_fuzzy_eq!(yf(DayCountConv::ActActISDA, start, end), 31. / 365. + 1. + 30. / 365., tol);
+        let start = NaiveDate::from_ymd(2020, 2, 28);
+        let end = NaiveDate::from_ymd(2020, 3, 1);
+        assert_fuzzy_eq!(yf(DayCountConv::ActActISDA, start, end), 2. / 366., tol);
+    }
+
     #[test]
     fn calc_year_fractions_30_360() {
         let tol = 1e-11;
@@ -1,12 +1,13 @@
 
+use std::collections::HashMap;
 use std::f64;
 
 use argmin::prelude::*;
 use argmin::solver::brent::Brent;
-use chrono::NaiveDate;
+use chrono::{DateTime, Local, NaiveDate};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use finql_data::CashFlow;
+use finql_data::{CashAmount, CashFlow, Currency, CurrencyConverter, CurrencyError, DataError, Quote};
 
 use crate::day_count_conv::DayCountConv;
 use crate::rates::{Compounding, DiscountError, Discounter, FlatRate};
@@ -23,19 +24,162 @@ pub fn get_cash_flows_after(cash_flows: &[CashFlow], date: NaiveDate) -> Vec<Cas
     new_cash_flows
 }
 
+/// Get all cash flows paid between `start` and `end`, in the order given by `cash_flows`.
+/// Whether cash flows paid exactly on `start` or `end` are included is controlled by
+/// `include_start` and `include_end`.
+pub fn get_cash_flows_between(
+    cash_flows: &[CashFlow],
+    start: NaiveDate,
+    end: NaiveDate,
+    include_start: bool,
+    include_end: bool,
+) -> Vec<CashFlow> {
+    cash_flows
+        .iter()
+        .filter(|cf| {
+            (cf.date > start || (include_start && cf.date == start))
+                && (cf.date < end || (include_end && cf.date == end))
+        })
+        .copied()
+        .collect()
+}
+
+/// Merge cash flows with the same date and currency into a single cash flow by summing up
+/// their amounts. The result is sorted by date; cash flows on the same date, but in different
+/// currencies, are kept as separate cash flows in the order of their first occurrence.
+pub fn aggregate_cash_flows(flows: &[CashFlow]) -> Vec<CashFlow> {
+    let mut sorted_flows = flows.to_vec();
+    sorted_flows.sort_by_key(|cf| cf.date);
+    let mut aggregated: Vec<CashFlow> = Vec::new();
+    for cf in sorted_flows {
+        let same_date_start = aggregated.partition_point(|agg| agg.date < cf.date);
+        match aggregated[same_date_start..]
+            .iter_mut()
+            .find(|agg| agg.aggregatable(&cf))
+        {
+            Some(agg) => agg.amount.amount += cf.amount.amount,
+            None => aggregated.push(cf),
+        }
+    }
+    aggregated
+}
+
+/// Regular payment frequencies of cash flow schedules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Frequency {
+    Annual,
+    SemiAnnual,
+    Quarterly,
+    Monthly,
+}
+
+impl Frequency {
+    /// Number of payments per year
+    pub fn per_year(&self) -> u16 {
+        match self {
+            Frequency::Annual => 1,
+            Frequency::SemiAnnual => 2,
+            Frequency::Quarterly => 4,
+            Frequency::Monthly => 12,
+        }
+    }
+
+    /// Length of a single period in months
+    pub fn months(&self) -> i32 {
+        12 / self.per_year() as i32
+    }
+
+    /// Map spacing between two payments in days to a frequency,
+    /// allowing for month length and business day adjustments
+    fn from_days(days: i64) -> Option<Frequency> {
+        match days {
+            27..=34 => Some(Frequency::Monthly),
+            85..=97 => Some(Frequency::Quarterly),
+            175..=190 => Some(Frequency::SemiAnnual),
+            358..=372 => Some(Frequency::Annual),
+            _ => None,
+        }
+    }
+}
+
+/// Infer the payment frequency from the modal spacing between consecutive
+/// payment dates. Several cash flows on the same date (e.g. final coupon and
+/// notional) are counted once, and a single irregular (stub) period is
+/// tolerated. Returns `None` if the schedule has no regular frequency.
+pub fn infer_frequency(flows: &[CashFlow]) -> Option<Frequency> {
+    let mut dates: Vec<NaiveDate> = flows.iter().map(|cf| cf.date).collect();
+    dates.sort();
+    dates.dedup();
+    if dates.len() < 2 {
+        return None;
+    }
+    let spacings = dates.len() - 1;
+    let mut counts = HashMap::new();
+    for pair in dates.windows(2) {
+        if let Some(freq) = Frequency::from_days((pair[1] - pair[0]).num_days()) {
+            *counts.entry(freq).or_insert(0) += 1;
+        }
+    }
+    let (freq, count) = counts.into_iter().max_by_key(|(_, count)| *count)?;
+    // allow for at most one stub period
+    if count + 1 >= spacings && (count > 1 || spacings == 1) {
+        Some(freq)
+    } else {
+        None
+    }
+}
+
 pub trait FixedIncome {
     type Error: std::convert::From<DiscountError>;
 
-    /// Transform product into series of cash flows
+    /// Transform product into series of cash flows. A negative `position` denotes a short
+    /// position and yields the exact negation of the cash flows of the corresponding long position.
+    /// The final redemption is included as given by `notional_cash_flow`. Negative coupons,
+    /// e.g. fees, are kept like any other coupon payment.
     fn rollout_cash_flows(
         &self,
         position: f64,
         calendar_provider: &dyn CalendarProvider,
     ) -> Result<Vec<CashFlow>, Self::Error>;
 
+    /// Final redemption payment of a unit position. The default implementation takes the
+    /// latest cash flow of a unit position, i.e. products whose final coupon and redemption
+    /// are paid at the same date should override this method.
+    fn redemption(&self, calendar_provider: &dyn CalendarProvider) -> Result<CashFlow, Self::Error> {
+        self.rollout_cash_flows(1., calendar_provider)?
+            .into_iter()
+            .max_by_key(|cf| cf.date)
+            .ok_or_else(|| DiscountError::NoCashFlows.into())
+    }
+
+    /// Final redemption payment of the given position, e.g. to be appended to the coupon
+    /// payments when rolling out the cash flows. A negative `position` denotes a short position
+    /// and yields the negated redemption payment.
+    fn notional_cash_flow(
+        &self,
+        position: f64,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<CashFlow, Self::Error> {
+        let mut cash_flow = self.redemption(calendar_provider)?;
+        cash_flow.amount.amount *= position;
+        Ok(cash_flow)
+    }
+
     /// Calculate accrued interest for current coupon period
     fn accrued_interest(&self, today: NaiveDate) -> Result<f64, Self::Error>;
 
+    /// Calculate accrued interest for current coupon period, taking into account an
+    /// ex-dividend period of `ex_div_days` days before the next coupon date. Within this
+    /// period, the seller keeps the next coupon and the accrued interest becomes negative.
+    /// The default implementation ignores the ex-dividend period.
+    fn accrued_interest_ex_div(
+        &self,
+        today: NaiveDate,
+        _ex_div_days: Option<i64>,
+    ) -> Result<f64, Self::Error> {
+        self.accrued_interest(today)
+    }
+
     /// Calculate the yield to maturity (YTM) given a purchase price and date
     fn calculate_ytm(
         &self,
@@ -46,6 +190,172 @@ pub trait FixedIncome {
         let value = calculate_cash_flows_ytm(&cash_flows, purchase_cash_flow)?;
         Ok(value)
     }
+
+    /// Year fraction between two dates according to the product's day count convention,
+    /// used to measure the time to cash flows in risk figures like duration. The default
+    /// implementation uses act/365.
+    fn year_fraction(&self, start: NaiveDate, end: NaiveDate) -> Result<f64, Self::Error> {
+        Ok(year_fraction_act365(start, end))
+    }
+
+    /// Calculate the Macaulay duration in years at the yield to maturity given a purchase
+    /// price and date. The time to each cash flow is measured by `year_fraction`, both for
+    /// solving the yield and for weighting the cash flows.
+    fn macaulay_duration(
+        &self,
+        purchase_cash_flow: &CashFlow,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<f64, Self::Error> {
+        let (_, value, first_moment, _) = yield_moments(self, purchase_cash_flow, calendar_provider)?;
+        Ok(first_moment / value)
+    }
+
+    /// Calculate the convexity at the yield to maturity given a purchase price and date.
+    /// The time to each cash flow is measured by `year_fraction`.
+    fn convexity(
+        &self,
+        purchase_cash_flow: &CashFlow,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<f64, Self::Error> {
+        let (ytm, value, _, second_moment) = yield_moments(self, purchase_cash_flow, calendar_provider)?;
+        Ok(second_moment / value / (1. + ytm).powi(2))
+    }
+
+    /// Calculate the modified duration at the yield to maturity given a purchase price and date.
+    /// The time to each cash flow is measured by `year_fraction`.
+    fn modified_duration(
+        &self,
+        purchase_cash_flow: &CashFlow,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<f64, Self::Error> {
+        let (ytm, value, first_moment, _) = yield_moments(self, purchase_cash_flow, calendar_provider)?;
+        Ok(first_moment / value / (1. + ytm))
+    }
+
+    /// Calculate the z-spread over `curve` given a purchase price and date
+    fn z_spread(
+        &self,
+        purchase_cash_flow: &CashFlow,
+        curve: &dyn Discounter,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<f64, Self::Error> {
+        let cash_flows = self.rollout_cash_flows(1., calendar_provider)?;
+        Ok(calculate_cash_flows_z_spread(&cash_flows, purchase_cash_flow, curve)?)
+    }
+
+    /// Decompose the P&L over the period from the purchase date up to `horizon` on an
+    /// unchanged curve into carry and roll-down. Carry consists of the coupons paid until
+    /// `horizon` and the pull-to-par, i.e. the change in value if the yield to maturity
+    /// stays constant. Roll-down is the additional change in value from repricing the
+    /// remaining cash flows on the unchanged `curve` as the bond ages. Since the price may
+    /// differ from the value on `curve`, the bond is repriced with the z-spread at purchase
+    /// added to `curve`. The purchase cash flow `price` follows the sign convention of `calculate_ytm`.
+    fn carry_and_rolldown(
+        &self,
+        price: &CashFlow,
+        curve: &dyn Discounter,
+        horizon: NaiveDate,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<(f64, f64), Self::Error> {
+        let cash_flows = self.rollout_cash_flows(1., calendar_provider)?;
+        let ytm = calculate_cash_flows_ytm(&cash_flows, price)?;
+        let income: f64 = cash_flows
+            .iter()
+            .filter(|cf| cf.date > price.date && cf.date <= horizon)
+            .map(|cf| cf.amount.amount)
+            .sum();
+        let remaining_cash_flows = get_cash_flows_after(&cash_flows, horizon);
+        let yield_rate = yield_flat_rate(ytm, price.amount.currency);
+        let constant_yield_value = yield_rate
+            .discount_cash_flow_stream(&remaining_cash_flows, horizon)?
+            .amount;
+        let spread = calculate_cash_flows_z_spread(&cash_flows, price, curve)?;
+        let spreaded_curve = SpreadedDiscounter { curve, spread };
+        let curve_value = spreaded_curve
+            .discount_cash_flow_stream(&remaining_cash_flows, horizon)?
+            .amount;
+        let carry = income + constant_yield_value + price.amount.amount;
+        Ok((carry, curve_value - constant_yield_value))
+    }
+
+    /// Calculate the dirty price at `settlement` for a position of 1 from a yield to maturity,
+    /// using the same conventions as `calculate_ytm`, i.e. this is the inverse of `calculate_ytm`.
+    fn dirty_price_from_yield(
+        &self,
+        yield_: f64,
+        settlement: NaiveDate,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<CashAmount, Self::Error> {
+        let cash_flows = self.rollout_cash_flows(1., calendar_provider)?;
+        let currency = cash_flows.first().ok_or(DiscountError::NoCashFlows)?.amount.currency;
+        let future_cash_flows = get_cash_flows_after(&cash_flows, settlement);
+        Ok(yield_flat_rate(yield_, currency).discount_cash_flow_stream(&future_cash_flows, settlement)?)
+    }
+
+    /// Derive the clean quote from a stored dirty quote by subtracting
+    /// the interest accrued as of the quote's date
+    fn clean_quote(&self, dirty_quote: &Quote) -> Result<Quote, Self::Error> {
+        let accrued = self.accrued_interest(dirty_quote.time.naive_local().date())?;
+        Ok(Quote {
+            price: dirty_quote.price - accrued,
+            ..dirty_quote.clone()
+        })
+    }
+}
+
+/// Yield to maturity of a product given a purchase price and date, together with the present
+/// value moments at that yield (see `present_value_moments`), with the time to each cash flow
+/// measured by the product's `year_fraction`
+fn yield_moments<P: FixedIncome + ?Sized>(
+    product: &P,
+    purchase_cash_flow: &CashFlow,
+    calendar_provider: &dyn CalendarProvider,
+) -> Result<(f64, f64, f64, f64), P::Error> {
+    let cash_flows = product.rollout_cash_flows(1., calendar_provider)?;
+    let year_fraction = |start, end| product.year_fraction(start, end);
+    let ytm = calculate_cash_flows_ytm_with_year_fraction(&cash_flows, purchase_cash_flow, year_fraction)?;
+    let (value, first_moment, second_moment) = present_value_moments(
+        &cash_flows, purchase_cash_flow.date, ytm, year_fraction)?;
+    Ok((ytm, value, first_moment, second_moment))
+}
+
+/// Year fraction according to the day count convention used for yield calculations
+fn year_fraction_act365(start: NaiveDate, end: NaiveDate) -> f64 {
+    (end - start).num_days() as f64 / 365.
+}
+
+/// Year fraction according to act/365 as fallible function, see `present_value_moments`
+fn act365(start: NaiveDate, end: NaiveDate) -> Result<f64, DiscountError> {
+    Ok(year_fraction_act365(start, end))
+}
+
+/// Flat rate used to discount cash flows at their yield to maturity
+fn yield_flat_rate(rate: f64, currency: Currency) -> FlatRate {
+    FlatRate::new(rate, DayCountConv::Act365, Compounding::Annual, currency)
+}
+
+/// Parameters of the root finder used to calculate yields to maturity
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YtmSolverConfig {
+    /// Lower bound of the interval searched for the yield
+    pub lower: f64,
+    /// Upper bound of the interval searched for the yield
+    pub upper: f64,
+    /// Tolerance of the yield
+    pub tol: f64,
+    /// Maximum number of iterations
+    pub max_iters: u64,
+}
+
+impl Default for YtmSolverConfig {
+    fn default() -> Self {
+        YtmSolverConfig {
+            lower: 0.,
+            upper: 0.5,
+            tol: 1e-11,
+            max_iters: 100,
+        }
+    }
 }
 
 /// Calculate the internal rate of return of a stream of cash flows
@@ -53,31 +363,374 @@ pub trait FixedIncome {
 /// included and calculates that annual rate, that gives total aggregate zero value
 /// of all cash flows provided as `cash_flows`, if discounted to the payment date
 /// of the first cash flow. It is assumed that all cash flow are in the same currency,
-/// otherwise a `DiscountError` will be returned.
+/// otherwise `DiscountError::CurrencyMismatch` will be returned. The future cash flows must
+/// contain at least one cash flow of the opposite sign of the initial cash flow, and the initial
+/// cash flow and the net of all future cash flows must have opposite signs, e.g. a purchase must
+/// be given as negative cash flow, followed by positive coupon and notional payments.
+/// The yield is searched for between 0% and 50%, see `calculate_cash_flows_ytm_with_config`
+/// for other bounds.
 pub fn calculate_cash_flows_ytm(
     cash_flows: &[CashFlow],
     init_cash_flow: &CashFlow,
 ) -> Result<f64, DiscountError> {
-    let rate = FlatRate::new(
-        0.05,
-        DayCountConv::Act365,
-        Compounding::Annual,
-        init_cash_flow.amount.currency,
-    );
-    let init_param = 0.5;
-    let solver = Brent::new(0., 0.5, 1e-11);
+    calculate_cash_flows_ytm_with_config(cash_flows, init_cash_flow, &YtmSolverConfig::default())
+}
+
+/// Calculate the internal rate of return of a stream of cash flows like `calculate_cash_flows_ytm`,
+/// but search for the yield within the bounds and up to the tolerance given by `config`.
+/// If the yield does not lie within the bounds, `DiscountError::RootNotBracketed` is returned.
+pub fn calculate_cash_flows_ytm_with_config(
+    cash_flows: &[CashFlow],
+    init_cash_flow: &CashFlow,
+    config: &YtmSolverConfig,
+) -> Result<f64, DiscountError> {
+    check_ytm_cash_flows(cash_flows, init_cash_flow)?;
+    let rate = yield_flat_rate(0.05, init_cash_flow.amount.currency);
     let func = FlatRateDiscounter {
         init_cash_flow,
         cash_flows,
         rate,
     };
-    let res = Executor::new(func, solver, init_param).max_iters(100).run();
+    if func.value(config.lower)? * func.value(config.upper)? > 0.0 {
+        return Err(DiscountError::RootNotBracketed {
+            lower: config.lower,
+            upper: config.upper,
+        });
+    }
+    solve_ytm(func, config)
+}
+
+/// Check the cash flows of a yield calculation as described in `calculate_cash_flows_ytm`
+fn check_ytm_cash_flows(cash_flows: &[CashFlow], init_cash_flow: &CashFlow) -> Result<(), DiscountError> {
+    let init_amount = init_cash_flow.amount.amount;
+    let future_cash_flows: Vec<&CashFlow> = cash_flows
+        .iter()
+        .filter(|cf| cf.date > init_cash_flow.date)
+        .collect();
+    if future_cash_flows.is_empty() {
+        return Err(DiscountError::NoFutureCashFlows);
+    }
+    let same_sign = |amount: f64| amount * init_amount > 0.0 || amount == 0.0;
+    if future_cash_flows.iter().all(|cf| same_sign(cf.amount.amount)) {
+        return Err(DiscountError::NoSignChange);
+    }
+    let future_amount: f64 = future_cash_flows.iter().map(|cf| cf.amount.amount).sum();
+    if init_amount * future_amount >= 0.0 {
+        return Err(DiscountError::InvalidSignConvention);
+    }
+    Ok(())
+}
+
+/// Find the root of the aggregate value given by `func` within the bounds of `config`
+fn solve_ytm<O>(func: O, config: &YtmSolverConfig) -> Result<f64, DiscountError>
+where
+    O: ArgminOp<Param = f64, Output = f64, Float = f64>,
+{
+    let init_param = config.upper;
+    let solver = Brent::new(config.lower, config.upper, config.tol);
+    let res = Executor::new(func, solver, init_param)
+        .max_iters(config.max_iters)
+        .run();
     match res {
-        Ok(val) => Ok(val.state.get_param()),
-        Err(_) => Err(DiscountError),
+        Ok(val) if val.state.termination_reason != TerminationReason::MaxItersReached => {
+            Ok(val.state.get_param())
+        }
+        _ => Err(DiscountError::NotConverged {
+            iters: config.max_iters,
+        }),
     }
 }
 
+/// Calculate the internal rate of return of a stream of cash flows like `calculate_cash_flows_ytm`,
+/// but measure the time to each cash flow by `year_fraction` instead of act/365, such that
+/// discounting the cash flows at the resulting yield with `present_value_moments` and the same
+/// `year_fraction` gives back the purchase price
+fn calculate_cash_flows_ytm_with_year_fraction<E, F>(
+    cash_flows: &[CashFlow],
+    init_cash_flow: &CashFlow,
+    year_fraction: F,
+) -> Result<f64, E>
+where
+    E: From<DiscountError>,
+    F: Fn(NaiveDate, NaiveDate) -> Result<f64, E>,
+{
+    check_ytm_cash_flows(cash_flows, init_cash_flow)?;
+    let currency = init_cash_flow.amount.currency;
+    let mut timed_amounts = Vec::new();
+    for cf in get_cash_flows_after(cash_flows, init_cash_flow.date) {
+        if cf.amount.currency != currency {
+            return Err(DiscountError::CurrencyMismatch { expected: currency, found: cf.amount.currency }.into());
+        }
+        timed_amounts.push((year_fraction(init_cash_flow.date, cf.date)?, cf.amount.amount));
+    }
+    let func = YearFractionDiscounter {
+        init_amount: init_cash_flow.amount.amount,
+        timed_amounts: &timed_amounts,
+    };
+    let config = YtmSolverConfig::default();
+    if func.value(config.lower) * func.value(config.upper) > 0.0 {
+        return Err(DiscountError::RootNotBracketed {
+            lower: config.lower,
+            upper: config.upper,
+        }.into());
+    }
+    Ok(solve_ytm(func, &config)?)
+}
+
+/// Calculate the internal rate of return of a stream of cash flows like `calculate_cash_flows_ytm`,
+/// but return the yield with the given compounding method instead of an annually compounded
+/// rate, e.g. `Compounding::SemiAnnual` for the bond-equivalent yield quoted for semi-annual bonds.
+pub fn calculate_cash_flows_ytm_with_compounding(
+    cash_flows: &[CashFlow],
+    init_cash_flow: &CashFlow,
+    compounding: Compounding,
+) -> Result<f64, DiscountError> {
+    let ytm = calculate_cash_flows_ytm(cash_flows, init_cash_flow)?;
+    Ok(Compounding::Annual.convert_rate(ytm, compounding))
+}
+
+/// Calculate the internal rate of return of a stream of cash flows like `calculate_cash_flows_ytm`,
+/// but round the result to a multiple of `bp` basis points. A value of zero disables rounding.
+pub fn calculate_cash_flows_ytm_rounded(
+    cash_flows: &[CashFlow],
+    init_cash_flow: &CashFlow,
+    bp: u32,
+) -> Result<f64, DiscountError> {
+    let ytm = calculate_cash_flows_ytm(cash_flows, init_cash_flow)?;
+    if bp == 0 {
+        return Ok(ytm);
+    }
+    let step = bp as f64 / 10000.;
+    Ok((ytm / step).round() * step)
+}
+
+/// Calculate the internal rate of return of a stream of cash flows like `calculate_cash_flows_ytm`,
+/// but allow cash flows in currencies other than the currency of `init_cash_flow`. These are
+/// converted with the exchange rates given by `currency_converter` as of `time` before solving;
+/// cash flows already in the currency of `init_cash_flow` are used unchanged. If an exchange
+/// rate is not available, `DataError::FxRateMissing` naming the currency pair is returned.
+pub async fn calculate_cash_flows_ytm_converted<E: From<DiscountError> + From<DataError>>(
+    cash_flows: &[CashFlow],
+    init_cash_flow: &CashFlow,
+    currency_converter: &(dyn CurrencyConverter + Send + Sync),
+    time: DateTime<Local>,
+) -> Result<f64, E> {
+    let currency = init_cash_flow.amount.currency;
+    let mut converted_cash_flows = Vec::with_capacity(cash_flows.len());
+    for cf in cash_flows {
+        let mut cf = *cf;
+        if cf.amount.currency != currency {
+            let fx_rate = currency_converter
+                .fx_rate(cf.amount.currency, currency, time)
+                .await
+                .map_err(|_| DataError::FxRateMissing(format!("{}/{}", cf.amount.currency, currency)))?;
+            cf.amount = CashAmount {
+                amount: cf.amount.amount * fx_rate,
+                currency,
+            };
+        }
+        converted_cash_flows.push(cf);
+    }
+    Ok(calculate_cash_flows_ytm(&converted_cash_flows, init_cash_flow)?)
+}
+
+/// Present value and its first and second moments with respect to time, i.e. the sums of
+/// `t * PV` and `t * (t + 1) * PV` over all cash flows after `today`, discounted at the yield `ytm`
+/// with annual compounding, where the time `t` to each cash flow is given by `year_fraction`
+fn present_value_moments<E, F>(
+    cash_flows: &[CashFlow],
+    today: NaiveDate,
+    ytm: f64,
+    year_fraction: F,
+) -> Result<(f64, f64, f64), E>
+where
+    E: From<DiscountError>,
+    F: Fn(NaiveDate, NaiveDate) -> Result<f64, E>,
+{
+    let future_cash_flows = get_cash_flows_after(cash_flows, today);
+    let currency = future_cash_flows
+        .first()
+        .ok_or(DiscountError::NoFutureCashFlows)?
+        .amount
+        .currency;
+    let mut value = 0.;
+    let mut first_moment = 0.;
+    let mut second_moment = 0.;
+    for cf in &future_cash_flows {
+        if cf.amount.currency != currency {
+            return Err(DiscountError::CurrencyMismatch { expected: currency, found: cf.amount.currency }.into());
+        }
+        let t = year_fraction(today, cf.date)?;
+        let present_value = cf.amount.amount * Compounding::Annual.discount_factor(ytm, t);
+        value += present_value;
+        first_moment += t * present_value;
+        second_moment += t * (t + 1.) * present_value;
+    }
+    Ok((value, first_moment, second_moment))
+}
+
+/// Calculate the Macaulay duration in years, i.e. the present value weighted average time
+/// of a stream of cash flows at `today`, discounted at the yield `ytm` with the conventions
+/// of `calculate_cash_flows_ytm`. Cash flows up to `today` are ignored.
+pub fn cash_flows_macaulay_duration(
+    cash_flows: &[CashFlow],
+    today: NaiveDate,
+    ytm: f64,
+) -> Result<f64, DiscountError> {
+    let (value, first_moment, _) = present_value_moments(cash_flows, today, ytm, act365)?;
+    Ok(first_moment / value)
+}
+
+/// Calculate the modified duration of a stream of cash flows at `today`, discounted at the
+/// yield `ytm` with the conventions of `calculate_cash_flows_ytm`. Cash flows up to `today`
+/// are ignored.
+pub fn cash_flows_modified_duration(
+    cash_flows: &[CashFlow],
+    today: NaiveDate,
+    ytm: f64,
+) -> Result<f64, DiscountError> {
+    Ok(cash_flows_macaulay_duration(cash_flows, today, ytm)? / (1. + ytm))
+}
+
+/// Calculate the convexity, i.e. the second derivative of the present value with respect to
+/// the yield relative to the present value, of a stream of cash flows at `today`, discounted
+/// at the yield `ytm` with the conventions of `calculate_cash_flows_ytm`. Cash flows up to
+/// `today` are ignored.
+pub fn cash_flows_convexity(
+    cash_flows: &[CashFlow],
+    today: NaiveDate,
+    ytm: f64,
+) -> Result<f64, DiscountError> {
+    let (value, _, second_moment) = present_value_moments(cash_flows, today, ytm, act365)?;
+    Ok(second_moment / value / (1. + ytm).powi(2))
+}
+
+/// Calculate the z-spread of a stream of cash flows, i.e. the constant annual spread which
+/// added to the rates of `curve` gives total aggregate zero value of all cash flows, including
+/// the initial cash flow, discounted to the payment date of the initial cash flow.
+/// The same sign convention as in `calculate_cash_flows_ytm` applies.
+pub fn calculate_cash_flows_z_spread(
+    cash_flows: &[CashFlow],
+    init_cash_flow: &CashFlow,
+    curve: &dyn Discounter,
+) -> Result<f64, DiscountError> {
+    if get_cash_flows_after(cash_flows, init_cash_flow.date).is_empty() {
+        return Err(DiscountError::NoFutureCashFlows);
+    }
+    let solver = Brent::new(-0.5, 0.5, 1e-11);
+    let func = SpreadDiscounter {
+        init_cash_flow,
+        cash_flows,
+        curve,
+    };
+    let res = Executor::new(func, solver, 0.).max_iters(100).run();
+    match res {
+        Ok(val) if val.state.termination_reason != TerminationReason::MaxItersReached => {
+            Ok(val.state.get_param())
+        }
+        _ => Err(DiscountError::NotConverged { iters: 100 }),
+    }
+}
+
+/// Calculate the present value of a position in a product, discounting all cash flows after `time`
+/// on `curve` in the cash flows' currency and converting the resulting present value to
+/// `base` currency at the spot rate at `time`.
+pub async fn present_value_in_base<E: From<DiscountError> + From<CurrencyError>>(
+    product: &(dyn FixedIncome<Error = E> + Sync),
+    position: f64,
+    curve: &(dyn Discounter + Sync),
+    currency_converter: &(dyn CurrencyConverter + Send + Sync),
+    base: Currency,
+    time: DateTime<Local>,
+    calendar_provider: &(dyn CalendarProvider + Sync),
+) -> Result<CashAmount, E> {
+    let today = time.naive_local().date();
+    let cash_flows = product.rollout_cash_flows(position, calendar_provider)?;
+    let present_value = curve.discount_cash_flow_stream(&get_cash_flows_after(&cash_flows, today), today)?;
+    let mut value = CashAmount { amount: 0., currency: base };
+    value.add(present_value, time, currency_converter, false).await?;
+    Ok(value)
+}
+
+/// Metric to rank bonds by in `screen_bonds`
+pub enum ScreenMetric<'a> {
+    /// Yield to maturity
+    Yield,
+    /// Z-spread over the given curve
+    ZSpread(&'a dyn Discounter),
+    /// Modified duration
+    Duration,
+}
+
+/// Result of `screen_bonds`, all indices refer to the list of screened bonds
+#[derive(Debug, Default, PartialEq)]
+pub struct BondScreen {
+    /// Pairs of index and metric, sorted by descending metric
+    pub ranked: Vec<(usize, f64)>,
+    /// Pairs of index and error message of bonds the metric could not be calculated for
+    pub failed: Vec<(usize, String)>,
+}
+
+/// Rank bonds given together with their purchase cash flow by the chosen metric.
+/// Bonds for which the metric could not be calculated are reported separately.
+pub fn screen_bonds<E: From<DiscountError> + std::fmt::Display>(
+    bonds: &[(Box<dyn FixedIncome<Error = E>>, CashFlow)],
+    calendar_provider: &dyn CalendarProvider,
+    metric: ScreenMetric,
+) -> BondScreen {
+    let mut screen = BondScreen::default();
+    for (idx, (bond, price)) in bonds.iter().enumerate() {
+        let value = match metric {
+            ScreenMetric::Yield => bond.calculate_ytm(price, calendar_provider),
+            ScreenMetric::ZSpread(curve) => bond.z_spread(price, curve, calendar_provider),
+            ScreenMetric::Duration => bond.modified_duration(price, calendar_provider),
+        };
+        match value {
+            Ok(value) => screen.ranked.push((idx, value)),
+            Err(err) => screen.failed.push((idx, err.to_string())),
+        }
+    }
+    screen.ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    screen
+}
+
+/// Calculate the net present value of a stream of cash flows at `today` for each of the given
+/// annual rates, e.g. to plot the NPV profile and locate internal rates of return.
+/// Cash flows before `today` are ignored. Returns pairs of rate and NPV.
+pub fn npv_profile(
+    cash_flows: &[CashFlow],
+    today: NaiveDate,
+    rates: &[f64],
+) -> Result<Vec<(f64, f64)>, DiscountError> {
+    let currency = match cash_flows.first() {
+        Some(cf) => cf.amount.currency,
+        None => return Ok(rates.iter().map(|r| (*r, 0.0)).collect()),
+    };
+    let cash_flows: Vec<CashFlow> = cash_flows
+        .iter()
+        .filter(|cf| cf.date >= today)
+        .copied()
+        .collect();
+    rates
+        .iter()
+        .map(|r| {
+            let rate = yield_flat_rate(*r, currency);
+            Ok((*r, rate.discount_cash_flow_stream(&cash_flows, today)?.amount))
+        })
+        .collect()
+}
+
+/// Calculate the present value at `discount_date` of all cash flows after `discount_date`,
+/// discounted with the given `rate`, e.g. a `FlatRate` or a `ZeroCurve`. The sum is returned
+/// in the rate's currency; a `DiscountError` is returned if any cash flow is in a different currency.
+pub fn present_value<D: Discounter + ?Sized>(
+    cash_flows: &[CashFlow],
+    discount_date: NaiveDate,
+    rate: &D,
+) -> Result<CashAmount, DiscountError> {
+    rate.discount_cash_flow_stream(&get_cash_flows_after(cash_flows, discount_date), discount_date)
+}
+
 /// Calculate discounted value for given flat rate
 /// Since `argmin` requires `Serialize` and `Deserialize`,
 /// we can't use reference here but must clone all data to this struct
@@ -97,8 +750,15 @@ impl<'a> ArgminOp for FlatRateDiscounter<'a> {
     type Jacobian = ();
 
     fn apply(&self, p: &Self::Param) -> Result<Self::Output, Error> {
+        Ok(self.value(*p)?)
+    }
+}
+
+impl<'a> FlatRateDiscounter<'a> {
+    /// Aggregate value of all cash flows discounted at the given rate
+    fn value(&self, rate: f64) -> Result<f64, DiscountError> {
         let mut discount_rate = self.rate;
-        discount_rate.rate = *p;
+        discount_rate.rate = rate;
         let mut sum = self.init_cash_flow.amount.amount;
         let today = self.init_cash_flow.date;
         for cf in self.cash_flows {
@@ -134,6 +794,129 @@ impl<'de> Deserialize<'de> for FlatRateDiscounter<'de> {
     }
 }
 
+/// Calculate the discounted value of cash flows given as pairs of year fraction and amount
+#[derive(Clone)]
+struct YearFractionDiscounter<'a> {
+    init_amount: f64,
+    timed_amounts: &'a [(f64, f64)],
+}
+
+impl<'a> ArgminOp for YearFractionDiscounter<'a> {
+    type Float = f64;
+    type Param = f64;
+    type Output = f64;
+    type Hessian = ();
+    type Jacobian = ();
+
+    fn apply(&self, p: &Self::Param) -> Result<Self::Output, Error> {
+        Ok(self.value(*p))
+    }
+}
+
+impl<'a> YearFractionDiscounter<'a> {
+    /// Aggregate value of all cash flows discounted at the given annually compounded rate
+    fn value(&self, rate: f64) -> f64 {
+        self.timed_amounts
+            .iter()
+            .fold(self.init_amount, |sum, (t, amount)| sum + amount * Compounding::Annual.discount_factor(rate, *t))
+    }
+}
+
+/// Dummy implementation of Serialize
+impl<'a> Serialize for YearFractionDiscounter<'a> {
+    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Err(serde::ser::Error::custom(
+            "serialization is disabled".to_string()
+        ))
+    }
+}
+
+/// Dummy implementation fo Deserialize
+impl<'de> Deserialize<'de> for YearFractionDiscounter<'de> {
+    fn deserialize<D>(_deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Err(serde::de::Error::custom(
+            "deserialization is disabled".to_string()
+        ))
+    }
+}
+
+/// Curve with a constant annual spread added to all rates
+struct SpreadedDiscounter<'a> {
+    curve: &'a dyn Discounter,
+    spread: f64,
+}
+
+impl<'a> Discounter for SpreadedDiscounter<'a> {
+    fn discount_factor(&self, today: NaiveDate, pay_date: NaiveDate) -> f64 {
+        self.curve.discount_factor(today, pay_date)
+            * (1. + self.spread).powf(-year_fraction_act365(today, pay_date))
+    }
+
+    fn currency(&self) -> Currency {
+        self.curve.currency()
+    }
+}
+
+/// Calculate discounted value for given spread over a curve
+#[derive(Clone)]
+struct SpreadDiscounter<'a> {
+    init_cash_flow: &'a CashFlow,
+    cash_flows: &'a [CashFlow],
+    curve: &'a dyn Discounter,
+}
+
+impl<'a> ArgminOp for SpreadDiscounter<'a> {
+    type Float = f64;
+    type Param = f64;
+    type Output = f64;
+    type Hessian = ();
+    type Jacobian = ();
+
+    fn apply(&self, p: &Self::Param) -> Result<Self::Output, Error> {
+        let discounter = SpreadedDiscounter {
+            curve: self.curve,
+            spread: *p,
+        };
+        let today = self.init_cash_flow.date;
+        let mut sum = self.init_cash_flow.amount.amount;
+        for cf in self.cash_flows {
+            if cf.date > today {
+                sum += discounter.discount_cash_flow(cf, today)?.amount;
+            }
+        }
+        Ok(sum)
+    }
+}
+
+/// Dummy implementation of Serialize
+impl<'a> Serialize for SpreadDiscounter<'a> {
+    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Err(serde::ser::Error::custom(
+            "serialization is disabled".to_string()
+        ))
+    }
+}
+
+/// Dummy implementation fo Deserialize
+impl<'de> Deserialize<'de> for SpreadDiscounter<'de> {
+    fn deserialize<D>(_deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Err(serde::de::Error::custom(
+            "deserialization is disabled".to_string()
+        ))
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -144,6 +927,9 @@ mod tests {
     use finql_data::{Currency, CashAmount, CashFlow};
 
     use super::*;
+    use crate::bond::BondError;
+    use crate::bond::fixtures::BondSpec;
+    use crate::calendar::SimpleCalendar;
     use crate::fx_rates::SimpleCurrencyConverter;
 
     #[test]
@@ -155,6 +941,527 @@ mod tests {
 
         let ytm = calculate_cash_flows_ytm(&cash_flows, &init_cash_flow).unwrap();
         assert_fuzzy_eq!(ytm, 0.05, tol);
+
+        // purchase must be given as outflow
+        let init_cash_flow = CashFlow::new(1000., curr, NaiveDate::from_ymd(2020, 10, 1));
+        let err = calculate_cash_flows_ytm(&cash_flows, &init_cash_flow).unwrap_err();
+        assert_eq!(err, DiscountError::NoSignChange);
+        let mixed_cash_flows = vec![
+            CashFlow::new(-50., curr, NaiveDate::from_ymd(2021, 4, 1)),
+            CashFlow::new(1100., curr, NaiveDate::from_ymd(2021, 10, 1)),
+        ];
+        let err = calculate_cash_flows_ytm(&mixed_cash_flows, &init_cash_flow).unwrap_err();
+        assert_eq!(err, DiscountError::InvalidSignConvention);
+        assert!(err.to_string().contains("negative"));
+
+        // cash flows in different currencies
+        let usd = Currency::from_str("USD").unwrap();
+        let init_cash_flow = CashFlow::new(-1000., usd, NaiveDate::from_ymd(2020, 10, 1));
+        let err = calculate_cash_flows_ytm(&cash_flows, &init_cash_flow).unwrap_err();
+        assert_eq!(err, DiscountError::CurrencyMismatch { expected: usd, found: curr });
+
+        // too few iterations
+        let init_cash_flow = CashFlow::new(-1000., curr, NaiveDate::from_ymd(2020, 10, 1));
+        let config = YtmSolverConfig {
+            max_iters: 2,
+            ..Default::default()
+        };
+        let err = calculate_cash_flows_ytm_with_config(&cash_flows, &init_cash_flow, &config).unwrap_err();
+        assert_eq!(err, DiscountError::NotConverged { iters: 2 });
+    }
+
+    #[test]
+    fn yield_to_maturity_of_distressed_bond() {
+        let tol = 1e-9;
+        let curr = Currency::from_str("EUR").unwrap();
+        let cash_flows = vec![CashFlow::new(1000., curr, NaiveDate::from_ymd(2021, 10, 1))];
+        let init_cash_flow = CashFlow::new(-400., curr, NaiveDate::from_ymd(2020, 10, 1));
+
+        let err = calculate_cash_flows_ytm(&cash_flows, &init_cash_flow).unwrap_err();
+        assert_eq!(err, DiscountError::RootNotBracketed { lower: 0., upper: 0.5 });
+
+        let config = YtmSolverConfig {
+            upper: 5.,
+            ..Default::default()
+        };
+        let ytm = calculate_cash_flows_ytm_with_config(&cash_flows, &init_cash_flow, &config).unwrap();
+        assert_fuzzy_eq!(ytm, 1.5, tol);
+    }
+
+    #[test]
+    fn yield_to_maturity_without_future_cash_flows() {
+        let curr = Currency::from_str("EUR").unwrap();
+        let init_cash_flow = CashFlow::new(-1000., curr, NaiveDate::from_ymd(2020, 10, 1));
+        let err = calculate_cash_flows_ytm(&[], &init_cash_flow).unwrap_err();
+        assert_eq!(err, DiscountError::NoFutureCashFlows);
+
+        // all cash flows are already paid
+        let cash_flows = vec![
+            CashFlow::new(50., curr, NaiveDate::from_ymd(2019, 10, 1)),
+            CashFlow::new(1050., curr, NaiveDate::from_ymd(2020, 10, 1)),
+        ];
+        let err = calculate_cash_flows_ytm(&cash_flows, &init_cash_flow).unwrap_err();
+        assert_eq!(err, DiscountError::NoFutureCashFlows);
+        assert!(err.to_string().contains("no cash flows after"));
+    }
+
+    #[tokio::test]
+    async fn yield_to_maturity_of_cross_currency_cash_flows() {
+        let tol = 1e-11;
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let mut fx_converter = SimpleCurrencyConverter::new();
+        fx_converter.insert_fx_rate(usd, eur, 0.8);
+        let time = Local.ymd(2020, 10, 1).and_hms(18, 0, 0);
+        let init_cash_flow = CashFlow::new(-1000., eur, NaiveDate::from_ymd(2020, 10, 1));
+
+        // same currency needs no conversion
+        let cash_flows = vec![CashFlow::new(1050., eur, NaiveDate::from_ymd(2021, 10, 1))];
+        let empty_converter = SimpleCurrencyConverter::new();
+        let ytm = calculate_cash_flows_ytm_converted::<BondError>(&cash_flows, &init_cash_flow, &empty_converter, time)
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(ytm, 0.05, tol);
+
+        let cash_flows = vec![
+            CashFlow::new(50., usd, NaiveDate::from_ymd(2021, 10, 1)),
+            CashFlow::new(1010., eur, NaiveDate::from_ymd(2021, 10, 1)),
+        ];
+        let ytm = calculate_cash_flows_ytm_converted::<BondError>(&cash_flows, &init_cash_flow, &fx_converter, time)
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(ytm, 0.05, tol);
+
+        // missing exchange rate
+        let err = calculate_cash_flows_ytm_converted::<BondError>(&cash_flows, &init_cash_flow, &empty_converter, time)
+            .await
+            .unwrap_err();
+        match err {
+            BondError::DataFailure(DataError::FxRateMissing(pair)) => assert_eq!(pair, "USD/EUR"),
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn semi_annual_yield_to_maturity() {
+        let curr = Currency::from_str("EUR").unwrap();
+        let init_cash_flow = CashFlow::new(-1000., curr, NaiveDate::from_ymd(2020, 10, 1));
+        let mut cash_flows = Vec::new();
+        for year in 2021..=2025 {
+            cash_flows.push(CashFlow::new(25., curr, NaiveDate::from_ymd(year, 4, 1)));
+            cash_flows.push(CashFlow::new(25., curr, NaiveDate::from_ymd(year, 10, 1)));
+        }
+        cash_flows.push(CashFlow::new(1000., curr, NaiveDate::from_ymd(2025, 10, 1)));
+
+        // bond bought at par yields its coupon rate as bond-equivalent yield
+        let annual = calculate_cash_flows_ytm(&cash_flows, &init_cash_flow).unwrap();
+        let semi_annual = calculate_cash_flows_ytm_with_compounding(
+            &cash_flows,
+            &init_cash_flow,
+            Compounding::SemiAnnual,
+        )
+        .unwrap();
+        assert_fuzzy_eq!(annual, 0.050625, 1e-4);
+        assert_fuzzy_eq!(semi_annual, 0.05, 1e-4);
+        assert_fuzzy_eq!((1. + semi_annual / 2.).powi(2), 1. + annual, 1e-11);
+        let continuous = Compounding::SemiAnnual.convert_rate(semi_annual, Compounding::Continuous);
+        assert_fuzzy_eq!(Compounding::Continuous.convert_rate(continuous, Compounding::Annual), annual, 1e-11);
+    }
+
+    #[test]
+    fn rounded_yield_to_maturity() {
+        let tol = 1e-11;
+        let curr = Currency::from_str("EUR").unwrap();
+        let cash_flows = vec![CashFlow::new(1050.012, curr, NaiveDate::from_ymd(2021, 10, 1))];
+        let init_cash_flow = CashFlow::new(-1000., curr, NaiveDate::from_ymd(2020, 10, 1));
+
+        let ytm = calculate_cash_flows_ytm(&cash_flows, &init_cash_flow).unwrap();
+        assert_fuzzy_eq!(ytm, 0.050012, tol);
+        let ytm = calculate_cash_flows_ytm_rounded(&cash_flows, &init_cash_flow, 1).unwrap();
+        assert_fuzzy_eq!(ytm, 0.0500, tol);
+        let ytm = calculate_cash_flows_ytm_rounded(&cash_flows, &init_cash_flow, 0).unwrap();
+        assert_fuzzy_eq!(ytm, 0.050012, tol);
+
+        let cash_flows = vec![CashFlow::new(1050.3, curr, NaiveDate::from_ymd(2021, 10, 1))];
+        let ytm = calculate_cash_flows_ytm_rounded(&cash_flows, &init_cash_flow, 5).unwrap();
+        assert_fuzzy_eq!(ytm, 0.0505, tol);
+    }
+
+    #[test]
+    fn present_value_at_given_rate() {
+        let tol = 1e-11;
+        let curr = Currency::from_str("EUR").unwrap();
+        let today = NaiveDate::from_ymd(2020, 10, 1);
+        let cash_flows = vec![
+            CashFlow::new(-1000., curr, today),
+            CashFlow::new(1050., curr, NaiveDate::from_ymd(2021, 10, 1)),
+        ];
+        let rate = FlatRate::new(0.05, DayCountConv::Act365, Compounding::Annual, curr);
+        let value = present_value(&cash_flows, today, &rate).unwrap();
+        assert_eq!(value.currency, curr);
+        assert_fuzzy_eq!(value.amount, 1000., tol);
+
+        let usd = Currency::from_str("USD").unwrap();
+        let cash_flows = vec![CashFlow::new(1050., usd, NaiveDate::from_ymd(2021, 10, 1))];
+        assert_eq!(
+            present_value(&cash_flows, today, &rate).unwrap_err(),
+            DiscountError::CurrencyMismatch { expected: curr, found: usd }
+        );
+    }
+
+    #[test]
+    fn npv_profile_of_bond() {
+        let curr = Currency::from_str("EUR").unwrap();
+        let today = NaiveDate::from_ymd(2020, 10, 1);
+        let cash_flows = vec![
+            CashFlow::new(-1000., curr, today),
+            CashFlow::new(50., curr, NaiveDate::from_ymd(2021, 10, 1)),
+            CashFlow::new(50., curr, NaiveDate::from_ymd(2022, 10, 1)),
+            CashFlow::new(1050., curr, NaiveDate::from_ymd(2023, 10, 1)),
+        ];
+        let rates: Vec<f64> = (0..=10).map(|i| i as f64 / 100.).collect();
+        let profile = npv_profile(&cash_flows, today, &rates).unwrap();
+        assert_eq!(profile.len(), rates.len());
+        assert_fuzzy_eq!(profile[0].1, 150., 1e-11);
+        assert!(profile.windows(2).all(|w| w[0].1 > w[1].1));
+        let zero_crossings = profile.windows(2).filter(|w| w[0].1 > 0. && w[1].1 <= 0.).count();
+        assert_eq!(zero_crossings, 1);
+        let ytm = calculate_cash_flows_ytm(&cash_flows[1..], &cash_flows[0]).unwrap();
+        let crossing = profile.windows(2).find(|w| w[0].1 > 0. && w[1].1 <= 0.).unwrap();
+        assert!(crossing[0].0 - 1e-8 <= ytm && ytm <= crossing[1].0 + 1e-8);
+    }
+
+    fn annual_bond(rate: f64) -> Box<dyn FixedIncome<Error = BondError>> {
+        annual_bond_with_day_count(rate, "act/365")
+    }
+
+    /// Bond with annual coupons, issued 2020-10-01 and maturing 2023-10-01
+    fn annual_bond_spec(rate: f64, day_count: &str) -> BondSpec<'_> {
+        BondSpec {
+            rate,
+            coupon_date: "01.10",
+            period: "1Y",
+            day_count,
+            issue_date: "2020-10-01",
+            maturity: "2023-10-01",
+            ..Default::default()
+        }
+    }
+
+    fn annual_bond_with_day_count(rate: f64, day_count: &str) -> Box<dyn FixedIncome<Error = BondError>> {
+        Box::new(annual_bond_spec(rate, day_count).bond())
+    }
+
+    /// Minimal product paying a fixed amount per period, followed by the redemption
+    struct MockProduct {
+        coupons: Vec<f64>,
+    }
+
+    impl FixedIncome for MockProduct {
+        type Error = DiscountError;
+
+        fn rollout_cash_flows(
+            &self,
+            position: f64,
+            calendar_provider: &dyn CalendarProvider,
+        ) -> Result<Vec<CashFlow>, DiscountError> {
+            let curr = Currency::from_str("EUR").unwrap();
+            let mut cash_flows: Vec<CashFlow> = self
+                .coupons
+                .iter()
+                .enumerate()
+                .filter(|(_, coupon)| **coupon != 0.)
+                .map(|(i, coupon)| CashFlow::new(position * coupon, curr, NaiveDate::from_ymd(2021 + i as i32, 1, 1)))
+                .collect();
+            cash_flows.push(self.notional_cash_flow(position, calendar_provider)?);
+            Ok(cash_flows)
+        }
+
+        fn redemption(&self, _calendar_provider: &dyn CalendarProvider) -> Result<CashFlow, DiscountError> {
+            let curr = Currency::from_str("EUR").unwrap();
+            Ok(CashFlow::new(100., curr, NaiveDate::from_ymd(2020 + self.coupons.len() as i32, 1, 1)))
+        }
+
+        fn accrued_interest(&self, _today: NaiveDate) -> Result<f64, DiscountError> {
+            Ok(0.)
+        }
+    }
+
+    /// Product with a fixed list of cash flows of a unit position
+    struct FixedCashFlows {
+        cash_flows: Vec<CashFlow>,
+    }
+
+    impl FixedIncome for FixedCashFlows {
+        type Error = DiscountError;
+
+        fn rollout_cash_flows(
+            &self,
+            position: f64,
+            _calendar_provider: &dyn CalendarProvider,
+        ) -> Result<Vec<CashFlow>, DiscountError> {
+            Ok(self
+                .cash_flows
+                .iter()
+                .map(|cf| CashFlow::new(position * cf.amount.amount, cf.amount.currency, cf.date))
+                .collect())
+        }
+
+        fn accrued_interest(&self, _today: NaiveDate) -> Result<f64, DiscountError> {
+            Ok(0.)
+        }
+    }
+
+    #[test]
+    fn notional_cash_flow_contract() {
+        let tol = 1e-11;
+        let curr = Currency::from_str("EUR").unwrap();
+        let calendar = SimpleCalendar::default();
+        let maturity = NaiveDate::from_ymd(2023, 1, 1);
+
+        let zero_coupon = MockProduct { coupons: vec![0., 0., 0.] };
+        let cash_flows = zero_coupon.rollout_cash_flows(2., &calendar).unwrap();
+        assert_eq!(cash_flows.len(), 1);
+        assert!(cash_flows[0].fuzzy_cash_flows_cmp_eq(&CashFlow::new(200., curr, maturity), tol));
+
+        // short positions flip the sign of all cash flows, including fees
+        let with_fee = MockProduct { coupons: vec![5., -1., 5.] };
+        let long = with_fee.rollout_cash_flows(1., &calendar).unwrap();
+        let short = with_fee.rollout_cash_flows(-1., &calendar).unwrap();
+        assert_eq!(long.len(), 4);
+        for (l, s) in long.iter().zip(short.iter()) {
+            assert!(s.fuzzy_cash_flows_cmp_eq(&-*l, tol));
+        }
+        assert!(with_fee
+            .notional_cash_flow(-3., &calendar)
+            .unwrap()
+            .fuzzy_cash_flows_cmp_eq(&CashFlow::new(-300., curr, maturity), tol));
+
+        // redemption of a bond is its final principal payment
+        let bond = annual_bond(0.);
+        assert!(bond
+            .notional_cash_flow(-2., &calendar)
+            .unwrap()
+            .fuzzy_cash_flows_cmp_eq(&CashFlow::new(-2000., curr, NaiveDate::from_ymd(2023, 10, 1)), tol));
+
+        // products without own redemption method derive it from their cash flows
+        let fixed = FixedCashFlows { cash_flows: vec![
+            CashFlow::new(5., curr, NaiveDate::from_ymd(2022, 1, 1)),
+            CashFlow::new(105., curr, maturity),
+        ]};
+        assert!(fixed
+            .notional_cash_flow(2., &calendar)
+            .unwrap()
+            .fuzzy_cash_flows_cmp_eq(&CashFlow::new(210., curr, maturity), tol));
+        let empty = FixedCashFlows { cash_flows: Vec::new() };
+        assert!(matches!(empty.redemption(&calendar), Err(DiscountError::NoCashFlows)));
+    }
+
+    #[test]
+    fn screen_bonds_by_metric() {
+        let tol = 1e-8;
+        let curr = Currency::from_str("EUR").unwrap();
+        let calendar = SimpleCalendar::default();
+        let price = CashFlow::new(-1000., curr, NaiveDate::from_ymd(2020, 10, 1));
+        let bonds = vec![
+            (annual_bond(4.), price),
+            (annual_bond(2.), price),
+            (annual_bond(6.), price),
+            // invalid sign convention
+            (annual_bond(3.), CashFlow::new(1000., curr, price.date)),
+        ];
+
+        let screen = screen_bonds(&bonds, &calendar, ScreenMetric::Yield);
+        let ranking: Vec<usize> = screen.ranked.iter().map(|(idx, _)| *idx).collect();
+        assert_eq!(ranking, vec![2, 0, 1]);
+        assert_fuzzy_eq!(screen.ranked[0].1, 0.06, tol);
+        assert_eq!(screen.failed.len(), 1);
+        assert_eq!(screen.failed[0].0, 3);
+
+        // over a flat curve the spread is the excess of the yield over the curve's rate
+        let curve = FlatRate::new(0.03, DayCountConv::Act365, Compounding::Annual, curr);
+        let screen = screen_bonds(&bonds, &calendar, ScreenMetric::ZSpread(&curve));
+        assert_eq!(screen.ranked[0].0, 2);
+        assert_fuzzy_eq!(screen.ranked[0].1, 1.06 / 1.03 - 1., tol);
+        assert_fuzzy_eq!(screen.ranked[2].1, 1.02 / 1.03 - 1., tol);
+
+        // lower coupons have longer duration
+        let screen = screen_bonds(&bonds, &calendar, ScreenMetric::Duration);
+        let ranking: Vec<usize> = screen.ranked.iter().map(|(idx, _)| *idx).collect();
+        assert_eq!(ranking, vec![1, 0, 2]);
+        assert!(screen.ranked[0].1 < 3.);
+    }
+
+    #[tokio::test]
+    async fn present_value_of_foreign_bond() {
+        let tol = 1e-8;
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let calendar = SimpleCalendar::default();
+        let bond = BondSpec { currency: "USD", ..annual_bond_spec(5., "act/365") }.bond();
+        let usd_curve = FlatRate::new(0.02, DayCountConv::Act365, Compounding::Annual, usd);
+        let mut fx_converter = SimpleCurrencyConverter::new();
+        fx_converter.insert_fx_rate(usd, eur, 0.85);
+        let time = Local.ymd(2021, 1, 4).and_hms(18, 0, 0);
+
+        let usd_value = usd_curve
+            .discount_cash_flow_stream(&bond.rollout_cash_flows(2., &calendar).unwrap(), time.naive_local().date())
+            .unwrap();
+        let eur_value = present_value_in_base(&bond, 2., &usd_curve, &fx_converter, eur, time, &calendar)
+            .await
+            .unwrap();
+        assert_eq!(eur_value.currency, eur);
+        assert_fuzzy_eq!(eur_value.amount, usd_value.amount * 0.85, tol);
+
+        // discounting on a curve in a different currency fails
+        let eur_curve = FlatRate::new(0.02, DayCountConv::Act365, Compounding::Annual, eur);
+        let err = present_value_in_base(&bond, 2., &eur_curve, &fx_converter, eur, time, &calendar)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, BondError::DiscountingFailure(DiscountError::CurrencyMismatch { .. })));
+    }
+
+    #[test]
+    fn duration_and_convexity() {
+        let tol = 1e-8;
+        let curr = Currency::from_str("EUR").unwrap();
+        let calendar = SimpleCalendar::default();
+        let settlement = NaiveDate::from_ymd(2020, 10, 1);
+        let price = CashFlow::new(-950., curr, settlement);
+
+        // single remaining cash flow
+        let zero_bond = annual_bond(0.);
+        let maturity = year_fraction_act365(settlement, NaiveDate::from_ymd(2023, 10, 1));
+        assert_fuzzy_eq!(zero_bond.macaulay_duration(&price, &calendar).unwrap(), maturity, tol);
+
+        let bond = annual_bond(4.);
+        let ytm = bond.calculate_ytm(&price, &calendar).unwrap();
+        let macaulay = bond.macaulay_duration(&price, &calendar).unwrap();
+        assert!(macaulay < maturity);
+        assert_fuzzy_eq!(
+            bond.modified_duration(&price, &calendar).unwrap(),
+            macaulay / (1. + ytm),
+            tol
+        );
+
+        // compare with finite differences of the price
+        let h = 1e-4;
+        let value = |y: f64| bond.dirty_price_from_yield(y, settlement, &calendar).unwrap().amount;
+        let first_derivative = (value(ytm + h) - value(ytm - h)) / (2. * h);
+        let second_derivative = (value(ytm + h) + value(ytm - h) - 2. * value(ytm)) / (h * h);
+        assert_fuzzy_eq!(
+            -first_derivative / value(ytm),
+            bond.modified_duration(&price, &calendar).unwrap(),
+            1e-6
+        );
+        assert_fuzzy_eq!(
+            second_derivative / value(ytm),
+            bond.convexity(&price, &calendar).unwrap(),
+            1e-3
+        );
+
+        // time is measured with the bond's day count convention
+        let settlement = NaiveDate::from_ymd(2020, 11, 15);
+        let price = CashFlow::new(-950., curr, settlement);
+        let zero_bond = annual_bond_with_day_count(0., "30/360");
+        let maturity = 2. + 11. / 12. - 14. / 360.;
+        assert_fuzzy_eq!(zero_bond.macaulay_duration(&price, &calendar).unwrap(), maturity, tol);
+    }
+
+    #[test]
+    fn duration_of_30_360_coupon_bond() {
+        let tol = 1e-8;
+        let curr = Currency::from_str("EUR").unwrap();
+        let calendar = SimpleCalendar::default();
+        let settlement = NaiveDate::from_ymd(2020, 11, 15);
+        let price = CashFlow::new(-950., curr, settlement);
+        let bond = annual_bond_with_day_count(4., "30/360");
+        let cash_flows = bond.rollout_cash_flows(1., &calendar).unwrap();
+        let year_fraction = |start, end| bond.year_fraction(start, end);
+
+        // the yield is solved with the bond's day count convention, reproducing the price
+        let ytm = calculate_cash_flows_ytm_with_year_fraction(&cash_flows, &price, year_fraction).unwrap();
+        assert!((ytm - bond.calculate_ytm(&price, &calendar).unwrap()).abs() > 1e-6);
+        let value = |y: f64| present_value_moments(&cash_flows, settlement, y, year_fraction).unwrap().0;
+        assert_fuzzy_eq!(value(ytm), 950., 1e-6);
+
+        let macaulay = bond.macaulay_duration(&price, &calendar).unwrap();
+        let modified = bond.modified_duration(&price, &calendar).unwrap();
+        assert_fuzzy_eq!(modified, macaulay / (1. + ytm), tol);
+        let h = 1e-4;
+        let first_derivative = (value(ytm + h) - value(ytm - h)) / (2. * h);
+        let second_derivative = (value(ytm + h) + value(ytm - h) - 2. * value(ytm)) / (h * h);
+        assert_fuzzy_eq!(-first_derivative / value(ytm), modified, 1e-6);
+        assert_fuzzy_eq!(second_derivative / value(ytm), bond.convexity(&price, &calendar).unwrap(), 1e-3);
+    }
+
+    #[test]
+    fn cash_flows_in_date_range() {
+        let curr = Currency::from_str("EUR").unwrap();
+        let start = NaiveDate::from_ymd(2021, 1, 1);
+        let end = NaiveDate::from_ymd(2021, 12, 31);
+        let flows = vec![
+            CashFlow::new(4., curr, end),
+            CashFlow::new(1., curr, NaiveDate::from_ymd(2020, 12, 31)),
+            CashFlow::new(2., curr, start),
+            CashFlow::new(3., curr, NaiveDate::from_ymd(2021, 6, 30)),
+            CashFlow::new(5., curr, NaiveDate::from_ymd(2022, 1, 1)),
+        ];
+        let amounts = |flows: Vec<CashFlow>| -> Vec<f64> { flows.iter().map(|cf| cf.amount.amount).collect() };
+        assert_eq!(amounts(get_cash_flows_between(&flows, start, end, true, true)), vec![4., 2., 3.]);
+        assert_eq!(amounts(get_cash_flows_between(&flows, start, end, false, true)), vec![4., 3.]);
+        assert_eq!(amounts(get_cash_flows_between(&flows, start, end, true, false)), vec![2., 3.]);
+        assert_eq!(amounts(get_cash_flows_between(&flows, start, end, false, false)), vec![3.]);
+    }
+
+    #[test]
+    fn aggregate_same_day_cash_flows() {
+        let tol = 1e-11;
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let maturity = NaiveDate::from_ymd(2023, 10, 1);
+        let flows = vec![
+            CashFlow::new(1000., eur, maturity),
+            CashFlow::new(50., eur, NaiveDate::from_ymd(2022, 10, 1)),
+            CashFlow::new(20., usd, maturity),
+            CashFlow::new(50., eur, maturity),
+        ];
+        let aggregated = aggregate_cash_flows(&flows);
+        assert_eq!(aggregated.len(), 3);
+        assert!(aggregated[0].fuzzy_cash_flows_cmp_eq(&CashFlow::new(50., eur, NaiveDate::from_ymd(2022, 10, 1)), tol));
+        assert!(aggregated[1].fuzzy_cash_flows_cmp_eq(&CashFlow::new(1050., eur, maturity), tol));
+        assert!(aggregated[2].fuzzy_cash_flows_cmp_eq(&CashFlow::new(20., usd, maturity), tol));
+        assert!(aggregate_cash_flows(&[]).is_empty());
+    }
+
+    #[test]
+    fn infer_cash_flow_frequency() {
+        let curr = Currency::from_str("EUR").unwrap();
+        let semi_annual: Vec<CashFlow> = [
+            (2020, 4, 1),
+            (2020, 10, 1),
+            (2021, 4, 1),
+            (2021, 10, 1),
+            (2021, 10, 1),
+        ]
+        .iter()
+        .map(|(y, m, d)| CashFlow::new(25., curr, NaiveDate::from_ymd(*y, *m, *d)))
+        .collect();
+        assert_eq!(infer_frequency(&semi_annual), Some(Frequency::SemiAnnual));
+        assert_eq!(Frequency::SemiAnnual.per_year(), 2);
+
+        let irregular: Vec<CashFlow> = [
+            (2020, 1, 7),
+            (2020, 3, 19),
+            (2020, 11, 2),
+            (2021, 1, 15),
+            (2021, 9, 30),
+        ]
+        .iter()
+        .map(|(y, m, d)| CashFlow::new(25., curr, NaiveDate::from_ymd(*y, *m, *d)))
+        .collect();
+        assert_eq!(infer_frequency(&irregular), None);
+        assert_eq!(infer_frequency(&semi_annual[..1]), None);
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
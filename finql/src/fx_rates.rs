@@ -1,37 +1,45 @@
 ///! Calculation of fx rates based on currency quotes
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use std::sync::Arc;
-use std::sync::RwLock;
+use std::sync::{Mutex, RwLock};
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local, NaiveDate};
 use async_trait::async_trait;
 
-use finql_data::{Asset, Currency, CurrencyConverter, CurrencyError, DataError, QuoteHandler, Quote, Ticker};
+use finql_data::{Asset, CashAmount, Currency, CurrencyConverter, CurrencyError, DataError, QuoteHandler, Quote, Ticker};
 
 
-/// Insert fx rate quote in database including the inverse quote
-pub async fn insert_fx_quote(
-    fx_rate: f64,
+/// Order a currency pair canonically (alphabetically), such that each pair is stored only once.
+/// Returns the ordered pair and whether the order has been swapped.
+pub fn canonical_fx_pair(foreign: Currency, base: Currency) -> (Currency, Currency, bool) {
+    if foreign.to_string() <= base.to_string() {
+        (foreign, base, false)
+    } else {
+        (base, foreign, true)
+    }
+}
+
+/// Get or create the asset and ticker storing quotes for the given currency pair
+async fn insert_fx_ticker_if_new(
     foreign: Currency,
     base: Currency,
-    time: DateTime<Local>,
-    quotes: Arc<dyn QuoteHandler+Send+Sync>,
-) -> Result<(), DataError> {
+    quotes: &Arc<dyn QuoteHandler+Send+Sync>,
+) -> Result<usize, DataError> {
     let foreign_id = quotes
-        .insert_asset(&Asset {
+        .insert_asset_if_new(&Asset {
             id: None,
             name: foreign.to_string(),
             wkn: None,
             isin: None,
             note: None,
-        })
-        .await.unwrap();
-    let currency_pair = format!("{}/{}", foreign, base);
-    let ticker_id = quotes
-        .insert_ticker(&Ticker {
+        }, false)
+        .await?;
+    quotes
+        .insert_if_new_ticker(&Ticker {
             id: None,
-            name: currency_pair,
+            name: format!("{}/{}", foreign, base),
             asset: foreign_id,
             source: "manual".to_string(),
             priority: 10,
@@ -40,83 +48,218 @@ pub async fn insert_fx_quote(
             tz: None,
             cal: None,
         })
-        .await.unwrap();
-    quotes.insert_quote(&Quote {
-        id: None,
-        ticker: ticker_id,
-        price: fx_rate,
-        time,
-        volume: None,
-    }).await.unwrap();
-    // Insert inverse fx quote
-    let base_id = quotes
-        .insert_asset(&Asset {
-            id: None,
-            name: base.to_string(),
-            wkn: None,
-            isin: None,
-            note: None,
-        })
-        .await.unwrap();
-    let currency_pair = format!("{}/{}", base, foreign);
-    let ticker_id = quotes
-        .insert_ticker(&Ticker {
-            id: None,
-            name: currency_pair,
-            asset: base_id,
-            source: "manual".to_string(),
-            priority: 10,
-            currency: foreign,
-            factor: 1.0,
-            tz: None,
-            cal: None,
-        })
-        .await.unwrap();
+        .await
+}
+
+/// Insert fx rate quote in database. The quote is only stored for the canonically ordered
+/// currency pair (see `canonical_fx_pair`), inverting the rate if required.
+pub async fn insert_fx_quote(
+    fx_rate: f64,
+    foreign: Currency,
+    base: Currency,
+    time: DateTime<Local>,
+    quotes: Arc<dyn QuoteHandler+Send+Sync>,
+) -> Result<(), DataError> {
+    let (foreign, base, inverted) = canonical_fx_pair(foreign, base);
+    let price = if inverted { 1.0 / fx_rate } else { fx_rate };
+    let ticker_id = insert_fx_ticker_if_new(foreign, base, &quotes).await?;
     quotes.insert_quote(&Quote {
         id: None,
         ticker: ticker_id,
-        price: 1.0 / fx_rate,
+        price,
         time,
         volume: None,
-    }).await.unwrap();
+        bid: None,
+        ask: None,
+    }).await?;
     Ok(())
 }
 
+/// Migrate fx quotes stored for both directions of a currency pair to the canonically
+/// ordered pair. Quotes of non-canonical fx tickers are inverted and moved to the canonical
+/// ticker, unless a quote for the same time already exists, and the non-canonical tickers are
+/// removed. Returns the number of removed tickers.
+pub async fn normalize_fx_quotes(quotes: Arc<dyn QuoteHandler+Send+Sync>) -> Result<usize, DataError> {
+    let mut removed = 0;
+    for ticker in quotes.get_all_ticker().await? {
+        let (foreign, base) = match ticker.name.split_once('/') {
+            Some((foreign, base)) => (foreign, base),
+            None => continue,
+        };
+        let (foreign, base) = match (Currency::from_str(foreign), Currency::from_str(base)) {
+            (Ok(foreign), Ok(base)) => (foreign, base),
+            _ => continue,
+        };
+        let (_, _, inverted) = canonical_fx_pair(foreign, base);
+        let ticker_id = match ticker.id {
+            Some(id) => id,
+            None => continue,
+        };
+        if !inverted || ticker.currency != base {
+            continue;
+        }
+        let canonical_id = insert_fx_ticker_if_new(base, foreign, &quotes).await?;
+        let existing_times: HashSet<DateTime<Local>> = quotes
+            .get_all_quotes_for_ticker(canonical_id)
+            .await?
+            .iter()
+            .map(|q| q.time)
+            .collect();
+        for quote in quotes.get_all_quotes_for_ticker(ticker_id).await? {
+            if !existing_times.contains(&quote.time) && quote.price != 0.0 {
+                quotes.insert_quote(&Quote {
+                    id: None,
+                    ticker: canonical_id,
+                    price: 1.0 / quote.price,
+                    time: quote.time,
+                    volume: quote.volume,
+                    // the inverse rate swaps bid and ask
+                    bid: quote.ask.map(|ask| 1.0 / ask),
+                    ask: quote.bid.map(|bid| 1.0 / bid),
+                }).await?;
+            }
+            if let Some(id) = quote.id {
+                quotes.delete_quote(id).await?;
+            }
+        }
+        quotes.delete_ticker(ticker_id).await?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Get the most recent stored fx rate before `time` together with the time it was quoted at.
+/// The rate is looked up via the ticker of the currency pair, since the asset of a currency may
+/// hold the tickers of several pairs. Fx rates are stored for one direction of the currency
+/// pair only, so the inverse rate of the opposite pair is returned if required. A zero rate
+/// can't be inverted and is treated as missing.
+pub(crate) async fn last_fx_quote(
+    foreign: Currency,
+    base: Currency,
+    time: DateTime<Local>,
+    quotes: &(dyn QuoteHandler+Send+Sync),
+) -> Result<(f64, DateTime<Local>), DataError> {
+    for (first, second, inverted) in [(foreign, base, false), (base, foreign, true)] {
+        let ticker_id = match quotes.get_ticker_id(&format!("{}/{}", first, second)).await {
+            Some(ticker_id) => ticker_id,
+            None => continue,
+        };
+        if let Ok(fx_quote) = quotes.get_last_quote_before_for_ticker(ticker_id, time).await {
+            if inverted && fx_quote.price == 0.0 {
+                break;
+            }
+            let rate = if inverted { 1.0 / fx_quote.price } else { fx_quote.price };
+            return Ok((rate, fx_quote.time));
+        }
+    }
+    Err(DataError::FxRateMissing(format!("{}/{}", foreign, base)))
+}
+
+/// Get the most recent stored fx rate before `time` (or the inverse rate of the opposite pair),
+/// but fail with `DataError::Stale` if it has been quoted more than `max_age` before `time`
+pub async fn get_fx_rate_within(
+    foreign: Currency,
+    base: Currency,
+    time: DateTime<Local>,
+    max_age: Duration,
+    quotes: Arc<dyn QuoteHandler+Send+Sync>,
+) -> Result<f64, DataError> {
+    if foreign == base {
+        return Ok(1.0);
+    }
+    let (rate, quote_time) = last_fx_quote(foreign, base, time, quotes.as_ref()).await?;
+    if time - quote_time > max_age {
+        return Err(DataError::Stale(format!(
+            "fx rate {}/{} quoted at {}", foreign, base, quote_time
+        )));
+    }
+    Ok(rate)
+}
+
+/// Convert all amounts to the `target` currency with the fx rates at `time` given by the currency
+/// converter and return their sum. Each required fx rate is requested only once. If any fx rate is
+/// missing, `DataError::FxRateMissing` naming the currency pair is returned.
+pub async fn convert_all(
+    amounts: &[CashAmount],
+    target: Currency,
+    time: DateTime<Local>,
+    currency_converter: &(dyn CurrencyConverter+Send+Sync),
+) -> Result<CashAmount, DataError> {
+    let mut fx_rates: HashMap<String, f64> = HashMap::new();
+    let mut total = 0.0;
+    for amount in amounts {
+        let fx_rate = if amount.currency == target {
+            1.0
+        } else if let Some(fx_rate) = fx_rates.get(&amount.currency.to_string()) {
+            *fx_rate
+        } else {
+            let fx_rate = currency_converter
+                .fx_rate(amount.currency, target, time)
+                .await
+                .map_err(|_| DataError::FxRateMissing(format!("{}/{}", amount.currency, target)))?;
+            fx_rates.insert(amount.currency.to_string(), fx_rate);
+            fx_rate
+        };
+        total += amount.amount * fx_rate;
+    }
+    Ok(CashAmount {
+        amount: total,
+        currency: target,
+    })
+}
 
 /// Currency converter based of stored list of exchange rates, ignoring dates
 pub struct SimpleCurrencyConverter {
     fx_rates: RwLock<HashMap<String,f64>>,
+    /// Currency used to triangulate fx rates of pairs without direct rate
+    fx_pivot: Option<Currency>,
 }
 
 #[async_trait]
 impl CurrencyConverter for SimpleCurrencyConverter {
     async fn fx_rate(&self, foreign_currency: Currency, domestic_currency: Currency, _time: DateTime<Local>) -> Result<f64, CurrencyError> {
-        let currency_string = format!("{}/{}", &foreign_currency.to_string(), &domestic_currency.to_string());
-        if let Ok(fx_store) = self.fx_rates.read() {
-            if fx_store.contains_key(&currency_string) {
-                Ok(fx_store[&currency_string])
-            } else {
-                Err(CurrencyError::ConversionFailed)
-            }
-        } else {
-            Err(CurrencyError::ConversionFailed)
+        let fx_store = self.fx_rates.read().map_err(|_| CurrencyError::ConversionFailed)?;
+        let rate = |foreign: Currency, domestic: Currency| {
+            fx_store.get(&format!("{}/{}", foreign, domestic)).copied()
+        };
+        if let Some(fx_rate) = rate(foreign_currency, domestic_currency) {
+            return Ok(fx_rate);
         }
+        // triangulate via pivot currency
+        self.fx_pivot
+            .and_then(|pivot| Some(rate(foreign_currency, pivot)? * rate(pivot, domestic_currency)?))
+            .ok_or(CurrencyError::ConversionFailed)
     }
 }
 
 impl SimpleCurrencyConverter {
     /// Create new container
     pub fn new() -> SimpleCurrencyConverter {
-        SimpleCurrencyConverter{ fx_rates: RwLock::new(HashMap::new()) }
+        SimpleCurrencyConverter{
+            fx_rates: RwLock::new(HashMap::new()),
+            fx_pivot: Some(Currency::from_str("USD").unwrap()),
+        }
+    }
+
+    /// Set the currency used to triangulate fx rates if no direct rate of a currency pair
+    /// is available (USD by default), or disable triangulation with `None`
+    pub fn set_fx_pivot(&mut self, pivot: Option<Currency>) {
+        self.fx_pivot = pivot;
     }
 
-    /// Insert or update the price of 1 unit of foreign currency in terms of domestic currency and its inverse rate
+    /// Insert or update the price of 1 unit of foreign currency in terms of domestic currency and its inverse rate.
+    /// The inverse of a zero rate is not stored, i.e. conversion in the opposite direction will fail.
     pub fn insert_fx_rate(&mut self, foreign_currency: Currency, domestic_currency: Currency, fx_rate: f64) {
         let for_key = foreign_currency.to_string();
         let dom_key = domestic_currency.to_string();
         if let Ok(mut fx_store) = self.fx_rates.write() {
             fx_store.insert(format!("{}/{}", for_key, dom_key), fx_rate);
-            fx_store.insert(format!("{}/{}", dom_key, for_key), 1./fx_rate);            
+            let inverse_key = format!("{}/{}", dom_key, for_key);
+            if fx_rate == 0.0 {
+                fx_store.remove(&inverse_key);
+            } else {
+                fx_store.insert(inverse_key, 1./fx_rate);
+            }
         }
     }
 }
@@ -127,6 +270,39 @@ impl Default for SimpleCurrencyConverter{
     }
 }
 
+/// Currency converter memoizing the fx rates of the wrapped converter, e.g. a `Market`, for the
+/// duration of a single portfolio valuation. Rates are cached per currency pair and date, i.e.
+/// the rate found for the first request of a date is used for all later requests of that date.
+pub struct FxCache<C: CurrencyConverter> {
+    converter: C,
+    fx_rates: Mutex<HashMap<(String, String, NaiveDate), f64>>,
+}
+
+impl<C: CurrencyConverter> FxCache<C> {
+    pub fn new(converter: C) -> FxCache<C> {
+        FxCache {
+            converter,
+            fx_rates: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl<C: CurrencyConverter + Send + Sync> CurrencyConverter for FxCache<C> {
+    async fn fx_rate(&self, foreign_currency: Currency, domestic_currency: Currency, time: DateTime<Local>) -> Result<f64, CurrencyError> {
+        if foreign_currency == domestic_currency {
+            return Ok(1.0);
+        }
+        let key = (foreign_currency.to_string(), domestic_currency.to_string(), time.naive_local().date());
+        if let Some(fx_rate) = self.fx_rates.lock().map_err(|_| CurrencyError::ConversionFailed)?.get(&key) {
+            return Ok(*fx_rate);
+        }
+        let fx_rate = self.converter.fx_rate(foreign_currency, domestic_currency, time).await?;
+        self.fx_rates.lock().map_err(|_| CurrencyError::ConversionFailed)?.insert(key, fx_rate);
+        Ok(fx_rate)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -134,7 +310,7 @@ mod tests {
     use std::str::FromStr;
 
     use chrono::offset::TimeZone;
-    use chrono::Local;
+    use chrono::{Duration, Local};
 
     use finql_sqlite::SqliteDBPool;
     use crate::market::Market;
@@ -161,4 +337,287 @@ mod tests {
         let fx = market.fx_rate(usd, eur, time).await.unwrap();
         assert_fuzzy_eq!(fx, 0.9, tol);
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn reject_stale_fx_rate() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let fx_db = db_pool.get_conection().await.unwrap();
+        fx_db.init().await.unwrap();
+        let qh: Arc<dyn QuoteHandler+Send+Sync> = Arc::new(fx_db);
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let quote_time = Local.ymd(2021, 1, 4).and_hms(18, 0, 0);
+        insert_fx_quote(1.25, eur, usd, quote_time, qh.clone()).await.unwrap();
+        let market = Market::new(qh);
+        let max_age = Duration::days(1);
+
+        let time = Local.ymd(2021, 1, 5).and_hms(12, 0, 0);
+        assert_eq!(market.fx_rate_fresh(eur, usd, time, max_age).await, Ok(1.25));
+        assert_eq!(market.fx_rate_fresh(usd, eur, time, max_age).await, Ok(0.8));
+
+        let time = Local.ymd(2021, 3, 1).and_hms(12, 0, 0);
+        assert_eq!(market.fx_rate_fresh(eur, usd, time, max_age).await, Err(CurrencyError::StaleRate));
+        // the permissive lookup still uses the stale rate
+        assert_eq!(market.fx_rate(eur, usd, time).await, Ok(1.25));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn fx_pair_stored_once() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let fx_db = db_pool.get_conection().await.unwrap();
+        fx_db.init().await.unwrap();
+        let qh: Arc<dyn QuoteHandler+Send+Sync> = Arc::new(fx_db);
+        let tol = 1.0e-11_f64;
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let time1 = Local.ymd(2021, 1, 4).and_hms(18, 0, 0);
+        let time2 = Local.ymd(2021, 1, 5).and_hms(18, 0, 0);
+        insert_fx_quote(1.25, eur, usd, time1, qh.clone()).await.unwrap();
+        insert_fx_quote(0.75, usd, eur, time2, qh.clone()).await.unwrap();
+
+        let tickers = qh.get_all_ticker().await.unwrap();
+        assert_eq!(tickers.len(), 1);
+        assert_eq!(tickers[0].name, "EUR/USD");
+        assert_eq!(qh.get_all_quotes_for_ticker(tickers[0].id.unwrap()).await.unwrap().len(), 2);
+
+        let market = Market::new(qh);
+        let eur_usd = market.fx_rate(eur, usd, time2).await.unwrap();
+        let usd_eur = market.fx_rate(usd, eur, time2).await.unwrap();
+        assert_fuzzy_eq!(usd_eur, 0.75, tol);
+        assert_fuzzy_eq!(eur_usd * usd_eur, 1.0, tol);
+        assert_fuzzy_eq!(market.fx_rate(eur, usd, time1).await.unwrap(), 1.25, tol);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn normalize_bidirectional_fx_quotes() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let fx_db = db_pool.get_conection().await.unwrap();
+        fx_db.init().await.unwrap();
+        let qh: Arc<dyn QuoteHandler+Send+Sync> = Arc::new(fx_db);
+        let tol = 1.0e-11_f64;
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let time1 = Local.ymd(2021, 1, 4).and_hms(18, 0, 0);
+        let time2 = Local.ymd(2021, 1, 5).and_hms(18, 0, 0);
+        // legacy data with both directions stored
+        insert_fx_quote(1.25, eur, usd, time1, qh.clone()).await.unwrap();
+        let usd_id = qh.insert_asset(&Asset { id: None, name: "USD".to_string(), wkn: None, isin: None, note: None })
+            .await.unwrap();
+        let legacy_id = qh.insert_ticker(&Ticker {
+                id: None,
+                name: "USD/EUR".to_string(),
+                asset: usd_id,
+                source: "manual".to_string(),
+                priority: 10,
+                currency: eur,
+                factor: 1.0,
+                tz: None,
+                cal: None,
+            }).await.unwrap();
+        for (price, time) in [(0.8, time1), (0.5, time2)] {
+            qh.insert_quote(&Quote { id: None, ticker: legacy_id, price, time, volume: None, bid: None, ask: None }).await.unwrap();
+        }
+
+        assert_eq!(normalize_fx_quotes(qh.clone()).await.unwrap(), 1);
+        let tickers = qh.get_all_ticker().await.unwrap();
+        assert_eq!(tickers.len(), 1);
+        let quotes = qh.get_all_quotes_for_ticker(tickers[0].id.unwrap()).await.unwrap();
+        assert_eq!(quotes.len(), 2);
+        let market = Market::new(qh.clone());
+        assert_fuzzy_eq!(market.fx_rate(eur, usd, time2).await.unwrap(), 2.0, tol);
+        assert_fuzzy_eq!(market.fx_rate(usd, eur, time2).await.unwrap(), 0.5, tol);
+        // nothing left to do
+        assert_eq!(normalize_fx_quotes(qh).await.unwrap(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn triangulate_fx_rate_via_pivot() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let fx_db = db_pool.get_conection().await.unwrap();
+        fx_db.init().await.unwrap();
+        let qh: Arc<dyn QuoteHandler+Send+Sync> = Arc::new(fx_db);
+        let tol = 1.0e-11_f64;
+        let nok = Currency::from_str("NOK").unwrap();
+        let sek = Currency::from_str("SEK").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let eur = Currency::from_str("EUR").unwrap();
+        let time1 = Local.ymd(2021, 1, 4).and_hms(18, 0, 0);
+        let time2 = Local.ymd(2021, 1, 5).and_hms(18, 0, 0);
+        insert_fx_quote(8.5, usd, nok, time1, qh.clone()).await.unwrap();
+        insert_fx_quote(8.2, usd, sek, time2, qh.clone()).await.unwrap();
+
+        let mut market = Market::new(qh);
+        let time = Local.ymd(2021, 1, 6).and_hms(12, 0, 0);
+        assert_fuzzy_eq!(market.fx_rate(nok, sek, time).await.unwrap(), 8.2 / 8.5, tol);
+        assert_fuzzy_eq!(market.fx_rate(sek, nok, time).await.unwrap(), 8.5 / 8.2, tol);
+        // the older leg determines the age of the triangulated rate
+        assert_eq!(
+            market.fx_rate_fresh(nok, sek, time, Duration::days(1)).await,
+            Err(CurrencyError::StaleRate)
+        );
+        assert_eq!(market.fx_rate(nok, eur, time).await, Err(CurrencyError::ConversionFailed));
+        market.set_fx_pivot(None);
+        assert_eq!(market.fx_rate(nok, sek, time).await, Err(CurrencyError::ConversionFailed));
+
+        let mut converter = SimpleCurrencyConverter::new();
+        converter.insert_fx_rate(usd, nok, 8.5);
+        converter.insert_fx_rate(sek, usd, 1.0 / 8.2);
+        assert_fuzzy_eq!(converter.fx_rate(nok, sek, time).await.unwrap(), 8.2 / 8.5, tol);
+        assert_eq!(converter.fx_rate(nok, eur, time).await, Err(CurrencyError::ConversionFailed));
+        converter.set_fx_pivot(Some(eur));
+        assert_eq!(converter.fx_rate(nok, sek, time).await, Err(CurrencyError::ConversionFailed));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn invert_fx_rate_of_opposite_pair() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let fx_db = db_pool.get_conection().await.unwrap();
+        fx_db.init().await.unwrap();
+        let qh: Arc<dyn QuoteHandler+Send+Sync> = Arc::new(fx_db);
+        let tol = 1.0e-11_f64;
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let chf = Currency::from_str("CHF").unwrap();
+        let time = Local.ymd(2021, 1, 4).and_hms(18, 0, 0);
+        insert_fx_quote(1.25, eur, usd, time, qh.clone()).await.unwrap();
+        // stored as CHF/EUR, the canonical order of the pair
+        insert_fx_quote(0.0, chf, eur, time, qh.clone()).await.unwrap();
+
+        let market = Market::new(qh);
+        assert_fuzzy_eq!(market.fx_rate(usd, eur, time).await.unwrap(), 0.8, tol);
+        assert_eq!(market.fx_rate(chf, eur, time).await, Ok(0.0));
+        assert_eq!(market.fx_rate(eur, chf, time).await, Err(CurrencyError::ConversionFailed));
+
+        let mut converter = SimpleCurrencyConverter::new();
+        converter.insert_fx_rate(eur, usd, 1.25);
+        assert_fuzzy_eq!(converter.fx_rate(usd, eur, time).await.unwrap(), 0.8, tol);
+        converter.insert_fx_rate(chf, eur, 0.0);
+        assert_eq!(converter.fx_rate(eur, chf, time).await, Err(CurrencyError::ConversionFailed));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn fx_pairs_sharing_an_asset() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let fx_db = db_pool.get_conection().await.unwrap();
+        fx_db.init().await.unwrap();
+        let qh: Arc<dyn QuoteHandler+Send+Sync> = Arc::new(fx_db);
+        let tol = 1.0e-11_f64;
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let jpy = Currency::from_str("JPY").unwrap();
+        // both pairs are stored as tickers of the asset EUR
+        insert_fx_quote(1.25, eur, usd, Local.ymd(2021, 1, 4).and_hms(9, 0, 0), qh.clone()).await.unwrap();
+        insert_fx_quote(130.0, eur, jpy, Local.ymd(2021, 1, 4).and_hms(10, 0, 0), qh.clone()).await.unwrap();
+        assert_eq!(qh.get_all_assets().await.unwrap().len(), 1);
+
+        let market = Market::new(qh);
+        let time = Local.ymd(2021, 1, 4).and_hms(12, 0, 0);
+        assert_fuzzy_eq!(market.fx_rate(eur, usd, time).await.unwrap(), 1.25, tol);
+        assert_fuzzy_eq!(market.fx_rate(usd, eur, time).await.unwrap(), 0.8, tol);
+        assert_fuzzy_eq!(market.fx_rate(jpy, eur, time).await.unwrap(), 1.0 / 130.0, tol);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn fx_rate_within_max_age() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let fx_db = db_pool.get_conection().await.unwrap();
+        fx_db.init().await.unwrap();
+        let qh: Arc<dyn QuoteHandler+Send+Sync> = Arc::new(fx_db);
+        let tol = 1.0e-11_f64;
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let jpy = Currency::from_str("JPY").unwrap();
+        // Friday evening
+        let quote_time = Local.ymd(2021, 1, 8).and_hms(18, 0, 0);
+        insert_fx_quote(1.25, eur, usd, quote_time, qh.clone()).await.unwrap();
+        let max_age = Duration::hours(24);
+
+        let time = Local.ymd(2021, 1, 9).and_hms(12, 0, 0);
+        let fx = get_fx_rate_within(usd, eur, time, max_age, qh.clone()).await.unwrap();
+        assert_fuzzy_eq!(fx, 0.8, tol);
+        // the rate of Friday is too old for Monday noon
+        let time = Local.ymd(2021, 1, 11).and_hms(12, 0, 0);
+        assert!(matches!(
+            get_fx_rate_within(eur, usd, time, max_age, qh.clone()).await,
+            Err(DataError::Stale(_))
+        ));
+        assert!(matches!(
+            get_fx_rate_within(eur, jpy, time, max_age, qh).await,
+            Err(DataError::FxRateMissing(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn convert_basket_of_amounts() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let fx_db = db_pool.get_conection().await.unwrap();
+        fx_db.init().await.unwrap();
+        let qh: Arc<dyn QuoteHandler+Send+Sync> = Arc::new(fx_db);
+        let tol = 1.0e-11_f64;
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let chf = Currency::from_str("CHF").unwrap();
+        let jpy = Currency::from_str("JPY").unwrap();
+        let time = Local.ymd(2021, 1, 4).and_hms(18, 0, 0);
+        insert_fx_quote(1.25, eur, usd, time, qh.clone()).await.unwrap();
+        insert_fx_quote(1.1, chf, eur, time, qh.clone()).await.unwrap();
+
+        let market = Market::new(qh);
+        let amounts = vec![
+            CashAmount { amount: 100.0, currency: eur },
+            CashAmount { amount: 50.0, currency: usd },
+            CashAmount { amount: 10.0, currency: chf },
+            CashAmount { amount: 25.0, currency: usd },
+        ];
+        let total = convert_all(&amounts, eur, time, &market).await.unwrap();
+        assert_eq!(total.currency, eur);
+        assert_fuzzy_eq!(total.amount, 100.0 + 75.0 * 0.8 + 10.0 * 1.1, tol);
+
+        let mut amounts = amounts;
+        amounts.push(CashAmount { amount: 1000.0, currency: jpy });
+        match convert_all(&amounts, eur, time, &market).await {
+            Err(DataError::FxRateMissing(pair)) => assert_eq!(pair, "JPY/EUR"),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // crosses without direct quote are triangulated by the converter
+        let nok = Currency::from_str("NOK").unwrap();
+        insert_fx_quote(8.5, usd, nok, time, market.db()).await.unwrap();
+        let amounts = vec![CashAmount { amount: 85.0, currency: nok }];
+        let total = convert_all(&amounts, eur, time, &market).await.unwrap();
+        assert_fuzzy_eq!(total.amount, 8.0, tol);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn cache_fx_rates_per_date() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let fx_db = db_pool.get_conection().await.unwrap();
+        fx_db.init().await.unwrap();
+        let qh: Arc<dyn QuoteHandler+Send+Sync> = Arc::new(fx_db);
+        let tol = 1.0e-11_f64;
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let jpy = Currency::from_str("JPY").unwrap();
+        insert_fx_quote(1.25, eur, usd, Local.ymd(2021, 1, 4).and_hms(9, 0, 0), qh.clone()).await.unwrap();
+
+        let cache = FxCache::new(Market::new(qh.clone()));
+        let morning = Local.ymd(2021, 1, 4).and_hms(12, 0, 0);
+        assert_fuzzy_eq!(cache.fx_rate(eur, usd, morning).await.unwrap(), 1.25, tol);
+        assert_fuzzy_eq!(cache.fx_rate(usd, eur, morning).await.unwrap(), 0.8, tol);
+
+        // rates stored later on are not seen for cached dates
+        insert_fx_quote(1.5, eur, usd, Local.ymd(2021, 1, 4).and_hms(15, 0, 0), qh).await.unwrap();
+        let evening = Local.ymd(2021, 1, 4).and_hms(18, 0, 0);
+        assert_fuzzy_eq!(cache.fx_rate(eur, usd, evening).await.unwrap(), 1.25, tol);
+        let next_day = Local.ymd(2021, 1, 5).and_hms(18, 0, 0);
+        assert_fuzzy_eq!(cache.fx_rate(eur, usd, next_day).await.unwrap(), 1.5, tol);
+        assert_eq!(cache.fx_rate(eur, jpy, next_day).await, Err(CurrencyError::ConversionFailed));
+
+        // crosses are triangulated by the wrapped converter
+        let nok = Currency::from_str("NOK").unwrap();
+        let sek = Currency::from_str("SEK").unwrap();
+        insert_fx_quote(8.5, usd, nok, next_day, cache.converter.db()).await.unwrap();
+        insert_fx_quote(8.2, usd, sek, next_day, cache.converter.db()).await.unwrap();
+        assert_fuzzy_eq!(cache.fx_rate(nok, sek, next_day).await.unwrap(), 8.2 / 8.5, tol);
+    }
 }
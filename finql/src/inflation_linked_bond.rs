@@ -0,0 +1,203 @@
+//! Inflation linked bonds, i.e. bonds whose coupon and principal payments
+//! are scaled by the ratio of an inflation index at the payment date and
+//! the base index value at issue.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use finql_data::cash_flow::CashFlow;
+
+use crate::bond::{Bond, BondError};
+use crate::calendar::CalendarProvider;
+use crate::fixed_income::FixedIncome;
+
+/// Break-even inflation, i.e. the difference of the nominal and the real yield
+/// of bonds with the same maturity
+pub fn break_even_inflation(nominal_ytm: f64, real_ytm: f64) -> f64 {
+    nominal_ytm - real_ytm
+}
+
+/// Calculate the break-even inflation from a nominal and a real (index-linked) bond
+/// of the same maturity, given their purchase cash flows. For inflation linked bonds,
+/// the real bond is the nominal specification `InflationLinkedBond::bond` with the
+/// real (not indexed) price.
+pub fn bonds_break_even_inflation<N, R>(
+    nominal: &N,
+    nominal_purchase: &CashFlow,
+    real: &R,
+    real_purchase: &CashFlow,
+    calendar_provider: &dyn CalendarProvider,
+) -> Result<f64, N::Error>
+where
+    N: FixedIncome,
+    R: FixedIncome<Error = N::Error>,
+{
+    let nominal_ytm = nominal.calculate_ytm(nominal_purchase, calendar_provider)?;
+    let real_ytm = real.calculate_ytm(real_purchase, calendar_provider)?;
+    Ok(break_even_inflation(nominal_ytm, real_ytm))
+}
+
+/// Bond with cash flows linked to an inflation index
+#[derive(Deserialize, Serialize, Debug)]
+pub struct InflationLinkedBond {
+    /// Nominal bond specification
+    pub bond: Bond,
+    /// Index value at issue, cash flows are scaled by `index / base_index`
+    pub base_index: f64,
+    /// Known index values, sorted by date
+    pub index_series: Vec<(NaiveDate, f64)>,
+}
+
+impl InflationLinkedBond {
+    pub fn new(bond: Bond, base_index: f64, index_series: Vec<(NaiveDate, f64)>) -> InflationLinkedBond {
+        InflationLinkedBond {
+            bond,
+            base_index,
+            index_series,
+        }
+    }
+
+    /// Index value at the given date, linearly interpolated between the
+    /// surrounding index values
+    pub fn index_value(&self, date: NaiveDate) -> Result<f64, BondError> {
+        let pos = self.index_series.iter().position(|(d, _)| *d >= date)
+            .ok_or(BondError::MissingIndexValue(date))?;
+        let (end_date, end_value) = self.index_series[pos];
+        if end_date == date {
+            return Ok(end_value);
+        }
+        if pos == 0 {
+            return Err(BondError::MissingIndexValue(date));
+        }
+        let (start_date, start_value) = self.index_series[pos - 1];
+        let fraction = date.signed_duration_since(start_date).num_days() as f64
+            / end_date.signed_duration_since(start_date).num_days() as f64;
+        Ok(start_value + fraction * (end_value - start_value))
+    }
+
+    /// Ratio of the index value at the given date and the base index
+    pub fn index_ratio(&self, date: NaiveDate) -> Result<f64, BondError> {
+        Ok(self.index_value(date)? / self.base_index)
+    }
+}
+
+impl FixedIncome for InflationLinkedBond {
+    type Error = BondError;
+
+    /// Roll out the nominal cash flows and scale each by the index ratio at its payment date
+    fn rollout_cash_flows(
+        &self,
+        position: f64,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<Vec<CashFlow>, BondError> {
+        let mut cfs = self.bond.rollout_cash_flows(position, calendar_provider)?;
+        for cf in &mut cfs {
+            cf.amount.amount *= self.index_ratio(cf.date)?;
+        }
+        Ok(cfs)
+    }
+
+    fn redemption(&self, calendar_provider: &dyn CalendarProvider) -> Result<CashFlow, BondError> {
+        let mut cf = self.bond.redemption(calendar_provider)?;
+        cf.amount.amount *= self.index_ratio(cf.date)?;
+        Ok(cf)
+    }
+
+    fn accrued_interest(&self, today: NaiveDate) -> Result<f64, BondError> {
+        Ok(self.bond.accrued_interest(today)? * self.index_ratio(today)?)
+    }
+
+    fn year_fraction(&self, start: NaiveDate, end: NaiveDate) -> Result<f64, BondError> {
+        self.bond.year_fraction(start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use crate::bond::fixtures::BondSpec;
+    use crate::calendar::SimpleCalendar;
+
+    fn nominal_bond() -> Bond {
+        BondSpec::default().bond()
+    }
+
+    #[test]
+    fn inflation_linked_cash_flows() {
+        let tol = 1e-11;
+        let calendar = SimpleCalendar::default();
+        let nominal_cash_flows = nominal_bond().rollout_cash_flows(1., &calendar).unwrap();
+
+        let flat_index = vec![
+            (NaiveDate::from_ymd(2019, 10, 1), 100.),
+            (NaiveDate::from_ymd(2021, 10, 1), 100.),
+        ];
+        let linker = InflationLinkedBond::new(nominal_bond(), 100., flat_index);
+        let cash_flows = linker.rollout_cash_flows(1., &calendar).unwrap();
+        assert_eq!(cash_flows.len(), nominal_cash_flows.len());
+        for (cf, nominal_cf) in cash_flows.iter().zip(nominal_cash_flows.iter()) {
+            assert!(cf.fuzzy_cash_flows_cmp_eq(nominal_cf, tol));
+        }
+
+        let index = vec![
+            (NaiveDate::from_ymd(2019, 10, 1), 102.),
+            (NaiveDate::from_ymd(2021, 10, 1), 102.),
+        ];
+        let linker = InflationLinkedBond::new(nominal_bond(), 100., index);
+        let cash_flows = linker.rollout_cash_flows(1., &calendar).unwrap();
+        for (cf, nominal_cf) in cash_flows.iter().zip(nominal_cash_flows.iter()) {
+            assert_eq!(cf.date, nominal_cf.date);
+            assert_fuzzy_eq!(cf.amount.amount, 1.02 * nominal_cf.amount.amount, tol);
+        }
+    }
+
+    #[test]
+    fn break_even_inflation_from_yields() {
+        let tol = 1e-11;
+        assert_fuzzy_eq!(break_even_inflation(0.04, 0.015), 0.025, tol);
+
+        let bond = |rate: f64| -> Bond {
+            BondSpec {
+                rate,
+                coupon_date: "01.01",
+                period: "1Y",
+                issue_date: "2021-01-01",
+                maturity: "2022-01-01",
+                ..Default::default()
+            }.bond()
+        };
+        let calendar = SimpleCalendar::default();
+        let curr = finql_data::Currency::from_str("EUR").unwrap();
+        let purchase = CashFlow::new(-1000., curr, NaiveDate::from_ymd(2021, 1, 1));
+        let linker = InflationLinkedBond::new(bond(1.5), 100., Vec::new());
+        let break_even = bonds_break_even_inflation(
+            &bond(4.),
+            &purchase,
+            &linker.bond,
+            &purchase,
+            &calendar,
+        )
+        .unwrap();
+        assert_fuzzy_eq!(break_even, 0.025, 1e-8);
+    }
+
+    #[test]
+    fn index_interpolation() {
+        let tol = 1e-11;
+        let index = vec![
+            (NaiveDate::from_ymd(2020, 1, 1), 100.),
+            (NaiveDate::from_ymd(2020, 1, 11), 101.),
+        ];
+        let linker = InflationLinkedBond::new(nominal_bond(), 100., index);
+        assert_fuzzy_eq!(linker.index_value(NaiveDate::from_ymd(2020, 1, 1)).unwrap(), 100., tol);
+        assert_fuzzy_eq!(linker.index_value(NaiveDate::from_ymd(2020, 1, 5)).unwrap(), 100.4, tol);
+        assert_fuzzy_eq!(linker.index_ratio(NaiveDate::from_ymd(2020, 1, 11)).unwrap(), 1.01, tol);
+        assert!(linker.index_value(NaiveDate::from_ymd(2019, 12, 31)).is_err());
+        assert!(linker.index_value(NaiveDate::from_ymd(2020, 1, 12)).is_err());
+
+        // cash flows beyond the last known index value can't be rolled out
+        let calendar = SimpleCalendar::default();
+        assert!(linker.rollout_cash_flows(1., &calendar).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Interpolation of values given at a set of knots
+//! All interpolation methods expect the knots to be sorted by strictly increasing
+//! x values and extrapolate flat, i.e. values left of the first knot equal the value
+//! of the first knot, and values right of the last knot equal the value of the last knot.
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum InterpolationError {
+    NoKnots,
+    UnsortedKnots,
+    NonPositiveValue,
+}
+
+impl fmt::Display for InterpolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpolationError::NoKnots => write!(f, "no knots given for interpolation"),
+            InterpolationError::UnsortedKnots => {
+                write!(f, "knots must be sorted by strictly increasing x values")
+            }
+            InterpolationError::NonPositiveValue => {
+                write!(f, "log-linear interpolation requires positive values")
+            }
+        }
+    }
+}
+
+impl Error for InterpolationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+/// Method to interpolate between knots given as pairs of x and y values
+pub trait Interpolator {
+    /// Calculate the interpolated value at `x`
+    fn interpolate(&self, knots: &[(f64, f64)], x: f64) -> Result<f64, InterpolationError>;
+}
+
+/// Locate the segment containing `x`. Returns either the value of the boundary knot
+/// if `x` lies outside the knots, or the index `i` such that `knots[i].0 <= x < knots[i+1].0`.
+fn find_segment(knots: &[(f64, f64)], x: f64) -> Result<Result<usize, f64>, InterpolationError> {
+    let first = knots.first().ok_or(InterpolationError::NoKnots)?;
+    if knots.windows(2).any(|w| w[0].0 >= w[1].0) {
+        return Err(InterpolationError::UnsortedKnots);
+    }
+    let last = knots.last().unwrap();
+    if x <= first.0 {
+        return Ok(Err(first.1));
+    }
+    if x >= last.0 {
+        return Ok(Err(last.1));
+    }
+    // first knot is left of x, i.e. the partition point is at least 1
+    Ok(Ok(knots.partition_point(|(knot_x, _)| *knot_x <= x) - 1))
+}
+
+/// Linear interpolation between neighbouring knots
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Linear;
+
+impl Interpolator for Linear {
+    fn interpolate(&self, knots: &[(f64, f64)], x: f64) -> Result<f64, InterpolationError> {
+        match find_segment(knots, x)? {
+            Err(value) => Ok(value),
+            Ok(i) => {
+                let (x0, y0) = knots[i];
+                let (x1, y1) = knots[i + 1];
+                Ok(y0 + (x - x0) / (x1 - x0) * (y1 - y0))
+            }
+        }
+    }
+}
+
+/// Linear interpolation of the logarithm of the values, e.g. for discount factors
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogLinear;
+
+impl Interpolator for LogLinear {
+    fn interpolate(&self, knots: &[(f64, f64)], x: f64) -> Result<f64, InterpolationError> {
+        if knots.iter().any(|(_, y)| *y <= 0.0) {
+            return Err(InterpolationError::NonPositiveValue);
+        }
+        let log_knots: Vec<(f64, f64)> = knots.iter().map(|(x, y)| (*x, y.ln())).collect();
+        Ok(Linear.interpolate(&log_knots, x)?.exp())
+    }
+}
+
+/// Natural cubic spline, i.e. with vanishing second derivative at the first and last knot
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CubicSpline;
+
+impl CubicSpline {
+    /// Second derivatives of the spline at the knots
+    fn second_derivatives(knots: &[(f64, f64)]) -> Vec<f64> {
+        let n = knots.len();
+        let mut m = vec![0.0; n];
+        if n < 3 {
+            return m;
+        }
+        // Solve the tridiagonal system for the inner knots by forward elimination
+        // and back substitution
+        let mut diag = vec![0.0; n];
+        let mut rhs = vec![0.0; n];
+        for i in 1..n - 1 {
+            let h0 = knots[i].0 - knots[i - 1].0;
+            let h1 = knots[i + 1].0 - knots[i].0;
+            diag[i] = 2.0 * (h0 + h1);
+            rhs[i] = 6.0
+                * ((knots[i + 1].1 - knots[i].1) / h1 - (knots[i].1 - knots[i - 1].1) / h0);
+            if i > 1 {
+                let factor = h0 / diag[i - 1];
+                diag[i] -= factor * h0;
+                rhs[i] -= factor * rhs[i - 1];
+            }
+        }
+        for i in (1..n - 1).rev() {
+            let h1 = knots[i + 1].0 - knots[i].0;
+            m[i] = (rhs[i] - h1 * m[i + 1]) / diag[i];
+        }
+        m
+    }
+}
+
+impl Interpolator for CubicSpline {
+    fn interpolate(&self, knots: &[(f64, f64)], x: f64) -> Result<f64, InterpolationError> {
+        match find_segment(knots, x)? {
+            Err(value) => Ok(value),
+            Ok(i) => {
+                let m = Self::second_derivatives(knots);
+                let (x0, y0) = knots[i];
+                let (x1, y1) = knots[i + 1];
+                let h = x1 - x0;
+                let a = (x1 - x) / h;
+                let b = (x - x0) / h;
+                Ok(a * y0
+                    + b * y1
+                    + ((a * a * a - a) * m[i] + (b * b * b - b) * m[i + 1]) * h * h / 6.0)
+            }
+        }
+    }
+}
+
+/// Piecewise constant interpolation using the value of the previous knot
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StepPrevious;
+
+impl Interpolator for StepPrevious {
+    fn interpolate(&self, knots: &[(f64, f64)], x: f64) -> Result<f64, InterpolationError> {
+        match find_segment(knots, x)? {
+            Err(value) => Ok(value),
+            Ok(i) => Ok(knots[i].1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_and_step_interpolation() {
+        let tol = 1e-11;
+        let knots = [(1.0, 2.0), (2.0, 4.0), (4.0, 3.0)];
+        assert_fuzzy_eq!(Linear.interpolate(&knots, 1.5).unwrap(), 3.0, tol);
+        assert_fuzzy_eq!(Linear.interpolate(&knots, 3.0).unwrap(), 3.5, tol);
+        assert_fuzzy_eq!(Linear.interpolate(&knots, 0.0).unwrap(), 2.0, tol);
+        assert_fuzzy_eq!(Linear.interpolate(&knots, 5.0).unwrap(), 3.0, tol);
+        assert_fuzzy_eq!(StepPrevious.interpolate(&knots, 1.5).unwrap(), 2.0, tol);
+        assert_fuzzy_eq!(StepPrevious.interpolate(&knots, 2.0).unwrap(), 4.0, tol);
+        assert_fuzzy_eq!(StepPrevious.interpolate(&knots, 3.9).unwrap(), 4.0, tol);
+        assert_fuzzy_eq!(
+            LogLinear.interpolate(&[(0.0, 1.0), (2.0, 0.25)], 1.0).unwrap(),
+            0.5,
+            tol
+        );
+
+        assert_eq!(Linear.interpolate(&[], 1.0), Err(InterpolationError::NoKnots));
+        assert_eq!(
+            Linear.interpolate(&[(2.0, 1.0), (1.0, 2.0)], 1.5),
+            Err(InterpolationError::UnsortedKnots)
+        );
+        assert_eq!(
+            LogLinear.interpolate(&[(0.0, 1.0), (1.0, 0.0)], 0.5),
+            Err(InterpolationError::NonPositiveValue)
+        );
+    }
+
+    #[test]
+    fn cubic_spline_interpolation() {
+        let tol = 1e-11;
+        let knots = [(0.0, 0.0), (1.0, 1.0), (2.0, 0.0), (3.0, 1.0)];
+        for (x, y) in &knots {
+            assert_fuzzy_eq!(CubicSpline.interpolate(&knots, *x).unwrap(), *y, tol);
+        }
+        // reference values of the natural cubic spline
+        assert_fuzzy_eq!(CubicSpline.interpolate(&knots, 0.5).unwrap(), 0.75, tol);
+        assert_fuzzy_eq!(CubicSpline.interpolate(&knots, 1.5).unwrap(), 0.5, tol);
+        assert_fuzzy_eq!(CubicSpline.interpolate(&knots, 2.5).unwrap(), 0.25, tol);
+
+        // first derivative is continuous at inner knots
+        let eps = 1e-6;
+        for x in [1.0, 2.0] {
+            let left = (CubicSpline.interpolate(&knots, x).unwrap()
+                - CubicSpline.interpolate(&knots, x - eps).unwrap())
+                / eps;
+            let right = (CubicSpline.interpolate(&knots, x + eps).unwrap()
+                - CubicSpline.interpolate(&knots, x).unwrap())
+                / eps;
+            assert_fuzzy_eq!(left, right, 1e-4);
+        }
+    }
+}
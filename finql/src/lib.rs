@@ -40,16 +40,21 @@ pub mod macros;
 // module exports
 pub mod bond;
 pub mod calendar;
+pub mod clock;
+pub mod corporate_actions;
 pub mod coupon_date;
 pub mod day_adjust;
 pub mod day_count_conv;
 pub mod fixed_income;
 pub mod fx_rates;
 pub mod helpers;
+pub mod inflation_linked_bond;
+pub mod interpolation;
 pub mod market;
 pub mod market_quotes;
 pub mod portfolio;
 pub mod rates;
+pub mod schedule;
 pub mod time_period;
 pub mod strategy;
 pub mod time_series;
@@ -4,20 +4,24 @@
 /// Market data consist of non-static data, like interest rates,
 /// asset prices, or foreign exchange rates.
 use std::ops::Deref;
+use std::str::FromStr;
 use std::sync::Arc;
 
-use chrono::{DateTime, NaiveDate, Local, Weekday};
+use chrono::{DateTime, Duration, NaiveDate, Local, Weekday};
 use std::collections::BTreeMap;
 
 use async_trait::async_trait;
 use thiserror::Error;
 
-use finql_data::{Currency, CurrencyConverter, CurrencyError, QuoteHandler, date_time_helper::naive_date_to_date_time};
+use finql_data::{Currency, CurrencyConverter, CurrencyError, DataError, QuoteHandler,
+    date_time_helper::naive_date_to_date_time};
 use crate::time_period::TimePeriod;
 
-use crate::calendar::{Calendar, Holiday, NthWeek};
+use crate::calendar::{Calendar, CalendarNotFound, CalendarProvider, Holiday, NthWeek};
+use crate::clock::{Clock, SystemClock};
+use crate::fx_rates;
 use crate::market_quotes;
-use crate::market_quotes::{MarketQuoteProvider, MarketDataSourceError};
+use crate::market_quotes::{MarketQuoteError, MarketQuoteProvider, MarketDataSourceError};
 
 /// Error related to market data object
 #[derive(Error, Debug)]
@@ -38,6 +42,12 @@ pub enum MarketError {
     MarketDataSourceError(#[from] MarketDataSourceError),
 }
 
+/// Settlement after a fixed number of business days, e.g. T+2 for most equities
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettlementConvention {
+    pub business_days: i64,
+}
+
 /// Container or adaptor to market data
 #[derive(Clone)]
 pub struct Market {
@@ -46,6 +56,8 @@ pub struct Market {
     provider: BTreeMap<String, Arc<dyn MarketQuoteProvider+Sync+Send>>,
     /// Quotes database
     db: Arc<dyn QuoteHandler+Sync+Send>,
+    /// Currency used to triangulate fx rates of pairs without direct quotes
+    fx_pivot: Option<Currency>,
 }
 
 impl Market {
@@ -55,9 +67,16 @@ impl Market {
             calendars: generate_calendars(),
             provider: BTreeMap::new(),
             db,
+            fx_pivot: Some(Currency::from_str("USD").unwrap()),
         }
     }
 
+    /// Set the currency used to triangulate fx rates if no direct quote of a currency pair
+    /// is available (USD by default), or disable triangulation with `None`
+    pub fn set_fx_pivot(&mut self, pivot: Option<Currency>) {
+        self.fx_pivot = pivot;
+    }
+
     pub fn db(&self) -> Arc<dyn QuoteHandler+Sync+Send> {
         self.db.clone()
     }
@@ -144,6 +163,106 @@ impl Market {
         Ok(())
     }
 
+    /// Settlement date of a trade, i.e. the trade date moved forward by the convention's
+    /// number of business days of the given calendar
+    pub fn settlement_date(&self, trade_date: NaiveDate, conv: SettlementConvention, cal: &Calendar) -> NaiveDate {
+        cal.add_bdays(trade_date, conv.business_days)
+    }
+
+    /// Fill the gaps in the quote history of an asset's main ticker, i.e. its ticker with the
+    /// highest priority, with quotes fetched from `provider`. Quotes already stored or fetched
+    /// more than once are skipped. Returns the number of inserted quotes.
+    pub async fn fill_gaps(
+        &self,
+        asset_id: usize,
+        provider: &(dyn MarketQuoteProvider + Send + Sync),
+        cal: &Calendar,
+    ) -> Result<usize, MarketError> {
+        self.fill_gaps_with_clock(asset_id, provider, cal, &SystemClock).await
+    }
+
+    /// Same as `fill_gaps`, but search for gaps up to the current date of the given clock
+    pub async fn fill_gaps_with_clock(
+        &self,
+        asset_id: usize,
+        provider: &(dyn MarketQuoteProvider + Send + Sync),
+        cal: &Calendar,
+        clock: &(dyn Clock + Sync),
+    ) -> Result<usize, MarketError> {
+        let ticker = self.db.get_all_ticker_for_asset(asset_id).await?
+            .into_iter()
+            .min_by_key(|ticker| ticker.priority)
+            .ok_or_else(|| DataError::NotFound(format!("no ticker found for asset {}", asset_id)))?;
+        let mut summaries = market_quotes::backfill::backfill_with_clock(
+            provider,
+            self.db.clone(),
+            &[ticker],
+            cal,
+            std::time::Duration::ZERO,
+            1,
+            clock,
+        ).await;
+        // exactly one summary is returned per ticker
+        let summary = summaries.pop().unwrap();
+        if !summary.errors.is_empty() {
+            return Err(MarketQuoteError::UnexpectedError(summary.errors.join("; ")).into());
+        }
+        Ok(summary.quotes_added)
+    }
+
+    /// Get the most recent fx rate before `time` together with the time it was quoted at.
+    /// If the currency pair is not quoted directly, the rate is triangulated via the pivot
+    /// currency, using the older of both quote times.
+    async fn fx_quote(
+        &self,
+        foreign: Currency,
+        base: Currency,
+        time: DateTime<Local>,
+    ) -> Result<(f64, DateTime<Local>), CurrencyError> {
+        if foreign == base {
+            return Ok((1.0, time));
+        }
+        if let Ok(direct) = self.direct_fx_quote(foreign, base, time).await {
+            return Ok(direct);
+        }
+        match self.fx_pivot {
+            Some(pivot) if pivot != foreign && pivot != base => {
+                let (foreign_rate, foreign_time) = self.direct_fx_quote(foreign, pivot, time).await?;
+                let (base_rate, base_time) = self.direct_fx_quote(pivot, base, time).await?;
+                Ok((foreign_rate * base_rate, foreign_time.min(base_time)))
+            }
+            _ => Err(CurrencyError::ConversionFailed),
+        }
+    }
+
+    /// Get the most recent fx rate before `time` stored for the currency pair itself
+    async fn direct_fx_quote(
+        &self,
+        foreign: Currency,
+        base: Currency,
+        time: DateTime<Local>,
+    ) -> Result<(f64, DateTime<Local>), CurrencyError> {
+        fx_rates::last_fx_quote(foreign, base, time, self.db.deref())
+            .await
+            .map_err(|_| CurrencyError::ConversionFailed)
+    }
+
+    /// Get the fx rate like `fx_rate`, but fail with `CurrencyError::StaleRate` if the
+    /// most recent fx rate before `time` is older than `max_age`
+    pub async fn fx_rate_fresh(
+        &self,
+        foreign: Currency,
+        base: Currency,
+        time: DateTime<Local>,
+        max_age: Duration,
+    ) -> Result<f64, CurrencyError> {
+        let (rate, quote_time) = self.fx_quote(foreign, base, time).await?;
+        if time - quote_time > max_age {
+            return Err(CurrencyError::StaleRate);
+        }
+        Ok(rate)
+    }
+
     pub async fn get_asset_price(&self, asset_id: usize, currency: Currency, date: NaiveDate) -> Result<f64, MarketError> {
         let quote_curr = self.db.get_last_quote_before_by_id(asset_id, naive_date_to_date_time(&date, 18, None)?).await;
         let (price, quote_currency) = if let Ok((quote, currency)) = quote_curr {
@@ -168,6 +287,12 @@ impl Market {
     }
 }
 
+impl CalendarProvider for Market {
+    fn get_calendar(&self, calendar_name: &str) -> Result<&Calendar, CalendarNotFound> {
+        self.calendars.get(calendar_name).ok_or(CalendarNotFound {})
+    }
+}
+
 #[async_trait]
 impl CurrencyConverter for Market {
     async fn fx_rate(
@@ -176,18 +301,7 @@ impl CurrencyConverter for Market {
         base: Currency,
         time: DateTime<Local>,
     ) -> Result<f64, CurrencyError> {
-        if foreign == base {
-            return Ok(1.0);
-        } else {
-            let (fx_quote, quote_currency) = self.db.deref().deref()
-                .get_last_quote_before(&foreign.to_string(), time)
-                .await
-                .map_err(|_| CurrencyError::ConversionFailed)?;
-            if quote_currency == base {
-                return Ok(fx_quote.price);
-            }
-        }
-        Err(CurrencyError::ConversionFailed)
+        Ok(self.fx_quote(foreign, base, time).await?.0)
     }
 }
 
@@ -354,3 +468,76 @@ pub fn generate_calendars() -> BTreeMap<String, Calendar> {
 
     calendars
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    use finql_data::{Asset, Quote, Ticker, date_time_helper::make_time};
+    use finql_sqlite::SqliteDBPool;
+
+    use crate::clock::FixedClock;
+    use crate::market_quotes::mock::MockProvider;
+
+    #[tokio::test]
+    async fn settlement_dates() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        let market = Market::new(Arc::new(db));
+        let cal = market.get_calendar("TARGET").unwrap();
+        let t2 = SettlementConvention { business_days: 2 };
+        let t1 = SettlementConvention { business_days: 1 };
+        // Thursday before Easter, Good Friday and Easter Monday are TARGET holidays
+        let trade_date = NaiveDate::from_ymd_opt(2021, 4, 1).unwrap();
+        assert_eq!(market.settlement_date(trade_date, t2, cal), NaiveDate::from_ymd_opt(2021, 4, 7).unwrap());
+        assert_eq!(market.settlement_date(trade_date, t1, cal), NaiveDate::from_ymd_opt(2021, 4, 6).unwrap());
+        let trade_date = NaiveDate::from_ymd_opt(2021, 11, 10).unwrap();
+        assert_eq!(market.settlement_date(trade_date, t2, cal), NaiveDate::from_ymd_opt(2021, 11, 12).unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn fill_quote_gaps_of_asset() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let db: Arc<dyn QuoteHandler + Send + Sync> = Arc::new(db);
+        let asset_id = db
+            .insert_asset(&Asset { id: None, name: "GAPPED".to_string(), wkn: None, isin: None, note: None })
+            .await.unwrap();
+        let ticker_id = db.insert_ticker(&Ticker {
+            id: None,
+            asset: asset_id,
+            name: "GAPPED".to_string(),
+            currency: Currency::from_str("EUR").unwrap(),
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        }).await.unwrap();
+        for day in [8, 12] {
+            db.insert_quote(&Quote {
+                id: None,
+                ticker: ticker_id,
+                price: 10.0,
+                time: make_time(2021, 11, day, 18, 0, 0).unwrap(),
+                volume: None,
+                bid: None,
+                ask: None,
+            }).await.unwrap();
+        }
+
+        let market = Market::new(db.clone());
+        let cal = market.get_calendar("TARGET").unwrap();
+        let clock = FixedClock::new(make_time(2021, 11, 12, 20, 0, 0).unwrap());
+        let provider = MockProvider::with_days((8..=12).collect());
+        let inserted = market.fill_gaps_with_clock(asset_id, &provider, cal, &clock).await.unwrap();
+        assert_eq!(inserted, 3);
+        assert_eq!(db.get_all_quotes_for_ticker(ticker_id).await.unwrap().len(), 5);
+        // no gaps left
+        let inserted = market.fill_gaps_with_clock(asset_id, &provider, cal, &clock).await.unwrap();
+        assert_eq!(inserted, 0);
+        assert!(market.fill_gaps(asset_id + 1, &provider, cal).await.is_err());
+    }
+}
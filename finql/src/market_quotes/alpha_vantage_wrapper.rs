@@ -33,6 +33,8 @@ impl MarketQuoteProvider for AlphaVantage {
             price: alpha_quote.price(),
             time,
             volume: Some(alpha_quote.volume() as f64),
+            bid: None,
+            ask: None,
         })
     }
     /// Fetch historic quotes between start and end date
@@ -59,6 +61,8 @@ impl MarketQuoteProvider for AlphaVantage {
                     price: quote.close(),
                     time,
                     volume: Some(quote.volume() as f64),
+                    bid: None,
+                    ask: None,
                 })
             }
         }
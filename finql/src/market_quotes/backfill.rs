@@ -0,0 +1,291 @@
+//! Backfill of missing quotes
+//! For each ticker, gaps in the stored quote history are detected and the missing
+//! ranges are fetched from a market data provider, respecting the provider's rate limit.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDate};
+use futures::stream::{self, StreamExt};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use finql_data::{Quote, QuoteHandler, Ticker};
+use finql_data::date_time_helper::naive_date_to_date_time;
+
+use super::{validate_quote_currency, MarketQuoteError, MarketQuoteProvider};
+use crate::calendar::Calendar;
+use crate::clock::{Clock, SystemClock};
+use crate::time_series::TimeSeries;
+
+/// Result of backfilling the quotes of a single ticker
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackfillSummary {
+    pub ticker_id: usize,
+    /// Number of gaps found in the quote history
+    pub gaps: usize,
+    /// Number of quotes fetched and stored
+    pub quotes_added: usize,
+    pub errors: Vec<String>,
+}
+
+/// Enforce a minimum interval between two consecutive requests
+struct RateLimiter {
+    interval: Duration,
+    last_request: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    fn new(interval: Duration) -> RateLimiter {
+        RateLimiter {
+            interval,
+            last_request: Mutex::new(None),
+        }
+    }
+
+    /// Wait until the next request is allowed
+    async fn wait(&self) {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(last) = *last_request {
+            tokio::time::sleep_until(last + self.interval).await;
+        }
+        *last_request = Some(Instant::now());
+    }
+}
+
+/// Fill gaps in the quote history of all given tickers with quotes from `provider`.
+/// Requests are issued at most every `rate_limit` and at most `concurrency` requests are
+/// pending at the same time. Tickers whose quotes the provider reports in another currency
+/// are skipped (see `validate_quote_currency`). Fetched quotes are validated (positive price
+/// within the gap) and deduplicated before being stored.
+pub async fn backfill(
+    provider: &(dyn MarketQuoteProvider + Send + Sync),
+    handler: Arc<dyn QuoteHandler + Send + Sync>,
+    tickers: &[Ticker],
+    cal: &Calendar,
+    rate_limit: Duration,
+    concurrency: usize,
+) -> Vec<BackfillSummary> {
+    backfill_with_clock(provider, handler, tickers, cal, rate_limit, concurrency, &SystemClock).await
+}
+
+/// Same as `backfill`, but search for gaps up to the current date of the given clock
+pub async fn backfill_with_clock(
+    provider: &(dyn MarketQuoteProvider + Send + Sync),
+    handler: Arc<dyn QuoteHandler + Send + Sync>,
+    tickers: &[Ticker],
+    cal: &Calendar,
+    rate_limit: Duration,
+    concurrency: usize,
+    clock: &(dyn Clock + Sync),
+) -> Vec<BackfillSummary> {
+    let mut summaries = Vec::new();
+    let mut known_times = Vec::new();
+    let mut requests = Vec::new();
+    for (idx, ticker) in tickers.iter().enumerate() {
+        let mut summary = BackfillSummary {
+            ticker_id: ticker.id.unwrap_or_default(),
+            gaps: 0,
+            quotes_added: 0,
+            errors: Vec::new(),
+        };
+        let mut times = HashSet::new();
+        match ticker.id {
+            None => summary.errors.push("ticker is not yet stored to database".to_string()),
+            Some(ticker_id) => match handler.get_all_quotes_for_ticker(ticker_id).await {
+                Err(err) => summary.errors.push(err.to_string()),
+                Ok(quotes) => {
+                    times = quotes.iter().map(|q| q.time).collect();
+                    let series = TimeSeries::from_quotes(&ticker.name, &quotes, None);
+                    match series.find_gaps_with_clock(cal, clock) {
+                        Err(err) => summary.errors.push(err.to_string()),
+                        Ok(gaps) => {
+                            summary.gaps = gaps.len();
+                            requests.extend(gaps.into_iter().map(|gap| (idx, gap)));
+                        }
+                    }
+                }
+            },
+        }
+        summaries.push(summary);
+        known_times.push(times);
+    }
+
+    let limiter = RateLimiter::new(rate_limit);
+    let limiter = &limiter;
+    let mut rejected = HashSet::new();
+    for idx in requests.iter().map(|(idx, _)| *idx).collect::<HashSet<_>>() {
+        limiter.wait().await;
+        if let Err(err) = validate_quote_currency(provider, &tickers[idx]).await {
+            summaries[idx].errors.push(err.to_string());
+            rejected.insert(idx);
+        }
+    }
+    requests.retain(|(idx, _)| !rejected.contains(idx));
+    let mut results = stream::iter(requests)
+        .map(|(idx, gap)| async move {
+            limiter.wait().await;
+            (idx, gap, fetch_gap(provider, &tickers[idx], gap).await)
+        })
+        .buffer_unordered(concurrency.max(1));
+
+    while let Some((idx, (start, end), result)) = results.next().await {
+        let ticker = &tickers[idx];
+        let summary = &mut summaries[idx];
+        let quotes = match result {
+            Ok(quotes) => quotes,
+            Err(err) => {
+                summary.errors.push(format!("fetching {} to {} failed: {}", start, end, err));
+                continue;
+            }
+        };
+        let mut new_quotes = Vec::new();
+        for mut quote in quotes {
+            let date = quote.time.naive_local().date();
+            if !quote.price.is_finite() || quote.price <= 0.0 || date < start || date > end {
+                continue;
+            }
+            if !known_times[idx].insert(quote.time) {
+                continue;
+            }
+            quote.id = None;
+            quote.ticker = summary.ticker_id;
+            quote.price *= ticker.factor;
+            new_quotes.push(quote);
+        }
+        if new_quotes.is_empty() {
+            continue;
+        }
+        match handler.insert_quotes(&new_quotes).await {
+            Ok(ids) => summary.quotes_added += ids.len(),
+            Err(err) => summary.errors.push(err.to_string()),
+        }
+    }
+    summaries
+}
+
+/// Fetch quote history for all days of the given gap
+async fn fetch_gap(
+    provider: &(dyn MarketQuoteProvider + Send + Sync),
+    ticker: &Ticker,
+    (start, end): (NaiveDate, NaiveDate),
+) -> Result<Vec<Quote>, MarketQuoteError> {
+    let start: DateTime<Local> = naive_date_to_date_time(&start, 0, ticker.tz.clone())?;
+    let end: DateTime<Local> = naive_date_to_date_time(&end, 23, ticker.tz.clone())?;
+    provider.fetch_quote_history(ticker, start, end).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::atomic::Ordering;
+
+    use chrono::Weekday;
+
+    use finql_data::{Asset, Currency};
+    use finql_data::date_time_helper::make_time;
+    use finql_sqlite::SqliteDBPool;
+
+    use crate::calendar::Holiday;
+    use crate::clock::FixedClock;
+    use crate::market_quotes::mock::{MockHistory, MockProvider};
+
+    async fn insert_ticker(db: Arc<dyn QuoteHandler + Send + Sync>, name: &str, days: &[u32]) -> Ticker {
+        let asset_id = db
+            .insert_asset(&Asset { id: None, name: name.to_string(), wkn: None, isin: None, note: None })
+            .await.unwrap();
+        let mut ticker = Ticker {
+            id: None,
+            asset: asset_id,
+            name: name.to_string(),
+            currency: Currency::from_str("EUR").unwrap(),
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        };
+        ticker.id = Some(db.insert_ticker(&ticker).await.unwrap());
+        for day in days {
+            db.insert_quote(&Quote {
+                id: None,
+                ticker: ticker.id.unwrap(),
+                price: 10.0,
+                time: make_time(2021, 11, *day, 18, 0, 0).unwrap(),
+                volume: None,
+                bid: None,
+                ask: None,
+            }).await.unwrap();
+        }
+        ticker
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn backfill_gapped_ticker_only() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let db: Arc<dyn QuoteHandler + Send + Sync> = Arc::new(db);
+        let gapped = insert_ticker(db.clone(), "GAPPED", &[8, 12]).await;
+        let complete = insert_ticker(db.clone(), "COMPLETE", &[8, 9, 10, 11, 12]).await;
+
+        let holidays = vec![Holiday::WeekDay(Weekday::Sat), Holiday::WeekDay(Weekday::Sun)];
+        let cal = Calendar::calc_calendar(&holidays, 2021, 2021);
+        let provider = MockProvider::new(MockHistory::BusinessDays(cal.clone()));
+        let clock = FixedClock::new(make_time(2021, 11, 12, 20, 0, 0).unwrap());
+
+        let summaries = backfill_with_clock(
+            &provider,
+            db.clone(),
+            &[gapped.clone(), complete.clone()],
+            &cal,
+            Duration::from_millis(1),
+            2,
+            &clock,
+        ).await;
+        assert_eq!(provider.history_requests.load(Ordering::SeqCst), 1);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0], BackfillSummary {
+            ticker_id: gapped.id.unwrap(),
+            gaps: 1,
+            quotes_added: 3,
+            errors: Vec::new(),
+        });
+        assert_eq!(summaries[1].gaps, 0);
+        assert_eq!(summaries[1].quotes_added, 0);
+        assert!(summaries[1].errors.is_empty());
+        assert_eq!(db.get_all_quotes_for_ticker(gapped.id.unwrap()).await.unwrap().len(), 5);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn skip_tickers_quoted_in_other_currency() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let db: Arc<dyn QuoteHandler + Send + Sync> = Arc::new(db);
+        let gapped = insert_ticker(db.clone(), "GAPPED", &[8, 12]).await;
+
+        let holidays = vec![Holiday::WeekDay(Weekday::Sat), Holiday::WeekDay(Weekday::Sun)];
+        let cal = Calendar::calc_calendar(&holidays, 2021, 2021);
+        let provider = MockProvider::new(MockHistory::BusinessDays(cal.clone()))
+            .currency(Currency::from_str("USD").unwrap());
+        let clock = FixedClock::new(make_time(2021, 11, 12, 20, 0, 0).unwrap());
+
+        let summaries = backfill_with_clock(
+            &provider,
+            db.clone(),
+            std::slice::from_ref(&gapped),
+            &cal,
+            Duration::from_millis(1),
+            2,
+            &clock,
+        ).await;
+        assert_eq!(provider.history_requests.load(Ordering::SeqCst), 0);
+        assert_eq!(summaries[0].gaps, 1);
+        assert_eq!(summaries[0].quotes_added, 0);
+        assert_eq!(summaries[0].errors.len(), 1);
+        assert_eq!(db.get_all_quotes_for_ticker(gapped.id.unwrap()).await.unwrap().len(), 2);
+    }
+}
@@ -149,6 +149,8 @@ impl MarketQuoteProvider for Comdirect {
             price,
             time,
             volume: None,
+            bid: None,
+            ask: None,
         })
     }
     /// Fetch historic quotes between start and end date
@@ -169,6 +171,8 @@ impl MarketQuoteProvider for Comdirect {
                 price: quote.close,
                 time: quote.date,
                 volume: quote.volume,
+                bid: None,
+                ask: None,
             })
         }
         Ok(quotes)
@@ -0,0 +1,160 @@
+//! Market data provider reading quotes from local CSV files
+//! Quotes of each ticker are read from a file `<ticker name>.csv` within a given
+//! directory, e.g. for reproducible backtests without accessing any external service.
+//! The files must have a header line with the columns `date,open,high,low,close,volume`.
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+
+use finql_data::{CashFlow, Quote, Ticker,
+    date_time_helper::{parse_date, naive_date_to_date_time}};
+
+use super::{MarketQuoteError, MarketQuoteProvider};
+
+/// Single row of a quote file
+#[derive(Debug, Deserialize)]
+struct CsvQuote {
+    date: String,
+    close: f64,
+    volume: Option<f64>,
+}
+
+pub struct CsvProvider {
+    dir: PathBuf,
+}
+
+impl CsvProvider {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> CsvProvider {
+        CsvProvider { dir: dir.into() }
+    }
+
+    /// Read all quotes of the ticker in the order given in the file
+    fn read_quotes(&self, ticker: &Ticker) -> Result<Vec<Quote>, MarketQuoteError> {
+        let path = self.dir.join(format!("{}.csv", ticker.name));
+        let mut reader = csv::Reader::from_path(&path).map_err(|err| {
+            MarketQuoteError::FileReadFailed(format!("{}: {}", path.display(), err))
+        })?;
+        let mut quotes = Vec::new();
+        for (idx, row) in reader.deserialize().enumerate() {
+            // first line is the header
+            let line = idx + 2;
+            let row: CsvQuote = row.map_err(|err| {
+                MarketQuoteError::FileReadFailed(format!("{} line {}: {}", path.display(), line, err))
+            })?;
+            let date = parse_date(&row.date).map_err(|err| {
+                MarketQuoteError::FileReadFailed(format!("{} line {}: {}", path.display(), line, err))
+            })?;
+            quotes.push(Quote {
+                id: None,
+                ticker: ticker.id.unwrap(),
+                price: row.close,
+                time: naive_date_to_date_time(&date, 18, ticker.tz.clone())?,
+                volume: row.volume,
+                bid: None,
+                ask: None,
+            });
+        }
+        Ok(quotes)
+    }
+}
+
+#[async_trait]
+impl MarketQuoteProvider for CsvProvider {
+    /// Fetch the quote of the last row of the ticker's file
+    async fn fetch_latest_quote(&self, ticker: &Ticker) -> Result<Quote, MarketQuoteError> {
+        self.read_quotes(ticker)?.pop().ok_or_else(|| {
+            MarketQuoteError::FileReadFailed(format!("no quotes found for ticker {}", ticker.name))
+        })
+    }
+
+    /// Fetch all quotes with dates between the dates of start and end (inclusive)
+    async fn fetch_quote_history(
+        &self,
+        ticker: &Ticker,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<Quote>, MarketQuoteError> {
+        let start = start.naive_local().date();
+        let end = end.naive_local().date();
+        Ok(self
+            .read_quotes(ticker)?
+            .into_iter()
+            .filter(|quote| {
+                let date = quote.time.naive_local().date();
+                date >= start && date <= end
+            })
+            .collect())
+    }
+
+    /// Quote files contain no dividends
+    async fn fetch_dividend_history(
+        &self,
+        _ticker: &Ticker,
+        _start: DateTime<Local>,
+        _end: DateTime<Local>,
+    ) -> Result<Vec<CashFlow>, MarketQuoteError> {
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    use finql_data::Currency;
+    use finql_data::date_time_helper::make_time;
+
+    #[tokio::test]
+    async fn read_quotes_from_csv_files() {
+        let dir = std::env::temp_dir().join(format!("finql_csv_provider_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("GOOD.csv"), "date,open,high,low,close,volume\n\
+            2021-11-08,10.0,10.5,9.5,10.2,1000\n\
+            2021-11-09,10.2,10.8,10.1,10.7,\n\
+            2021-11-10,10.7,11.0,10.3,10.4,1500\n").unwrap();
+        std::fs::write(dir.join("BAD.csv"), "date,open,high,low,close,volume\n\
+            2021-11-08,10.0,10.5,9.5,10.2,1000\n\
+            2021-11-09,10.2,10.8,10.1,n/a,900\n").unwrap();
+
+        let mut ticker = Ticker {
+            id: Some(1),
+            asset: 1,
+            name: "GOOD".to_string(),
+            currency: Currency::from_str("EUR").unwrap(),
+            source: "csv".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        };
+        let provider = CsvProvider::new(&dir);
+        let latest = provider.fetch_latest_quote(&ticker).await.unwrap();
+        assert_eq!(latest.price, 10.4);
+        assert_eq!(latest.volume, Some(1500.0));
+
+        let quotes = provider.fetch_quote_history(
+            &ticker,
+            make_time(2021, 11, 9, 0, 0, 0).unwrap(),
+            make_time(2021, 11, 10, 0, 0, 0).unwrap(),
+        ).await.unwrap();
+        let prices: Vec<f64> = quotes.iter().map(|q| q.price).collect();
+        assert_eq!(prices, vec![10.7, 10.4]);
+        assert_eq!(quotes[0].volume, None);
+
+        ticker.name = "BAD".to_string();
+        match provider.fetch_latest_quote(&ticker).await {
+            Err(MarketQuoteError::FileReadFailed(msg)) => assert!(msg.contains("line 3")),
+            other => panic!("unexpected result {:?}", other),
+        }
+        ticker.name = "MISSING".to_string();
+        assert!(matches!(
+            provider.fetch_latest_quote(&ticker).await,
+            Err(MarketQuoteError::FileReadFailed(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,45 +3,189 @@ use std::str::FromStr;
 use async_trait::async_trait;
 use chrono::{DateTime, Local};
 use eodhistoricaldata_api as eod_api;
+use log::warn;
+use serde_json::Value;
 use finql_data::{CashFlow, Currency, Quote, Ticker, 
         date_time_helper::{
-            date_time_from_str_standard, 
-            date_from_str, 
+            parse_date,
             unix_to_date_time,
             naive_date_to_date_time,
         }
     };
 
-use super::{MarketQuoteError, MarketQuoteProvider};
+use super::{is_transient_status, MarketQuoteError, MarketQuoteProvider, QuoteBatch};
+
+const EOD_URL: &str = "https://eodhistoricaldata.com/api";
+/// Maximum number of tickers requested at once from the real-time endpoint
+const BULK_REQUEST_SIZE: usize = 15;
+
+/// Selection of the price used for historic quotes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AdjustmentMode {
+    /// Closing price as traded
+    #[default]
+    Raw,
+    /// Closing price adjusted for splits and dividends
+    Adjusted,
+}
 
 pub struct EODHistData {
     connector: eod_api::EodHistConnector,
+    token: String,
+    adjustment: AdjustmentMode,
 }
 
 impl EODHistData {
     pub fn new(token: String) -> EODHistData {
+        EODHistData::with_adjustment(token, AdjustmentMode::default())
+    }
+
+    pub fn with_adjustment(token: String, adjustment: AdjustmentMode) -> EODHistData {
         EODHistData {
-            connector: eod_api::EodHistConnector::new(token),
+            connector: eod_api::EodHistConnector::new(token.clone()),
+            token,
+            adjustment,
+        }
+    }
+
+    /// Price of a historic quote according to the adjustment mode. If the adjusted price
+    /// is missing, the raw closing price is used instead and a warning is logged.
+    fn historic_price(&self, ticker: &Ticker, quote: &eod_api::HistoricQuote) -> Option<f64> {
+        match self.adjustment {
+            AdjustmentMode::Raw => quote.close,
+            AdjustmentMode::Adjusted => {
+                if quote.adjusted_close.is_finite() && quote.adjusted_close > 0.0 {
+                    Some(quote.adjusted_close)
+                } else {
+                    warn!("no adjusted close for {} at {}, using raw close instead", ticker.name, quote.date);
+                    quote.close
+                }
+            }
+        }
+    }
+}
+
+/// Report failed connections, rate limiting and server overload as transient errors
+fn map_eod_error(err: eod_api::EodHistDataError) -> MarketQuoteError {
+    match &err {
+        eod_api::EodHistDataError::FetchFailed(status) if is_transient_status(status.as_u16()) => {
+            MarketQuoteError::Transient(err.to_string())
+        }
+        eod_api::EodHistDataError::ConnectionFailed => MarketQuoteError::Transient(err.to_string()),
+        _ => err.into(),
+    }
+}
+
+/// Request JSON data from the given URL. The URL contains the API token and is therefore
+/// removed from any error returned.
+async fn get_json(url: &str) -> Result<Value, reqwest::Error> {
+    let resp = reqwest::get(url).await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|err| err.without_url())?;
+    resp.json::<Value>().await.map_err(|err| err.without_url())
+}
+
+/// Classify the HTTP status of a response to the user endpoint
+fn check_user_status(status: u16) -> Result<(), MarketQuoteError> {
+    match status {
+        200..=299 => Ok(()),
+        401 | 403 => Err(MarketQuoteError::AuthFailed(format!("API token rejected with status {}", status))),
+        status if is_transient_status(status) => {
+            Err(MarketQuoteError::Transient(format!("request failed with status {}", status)))
+        }
+        status => Err(MarketQuoteError::UnexpectedError(format!("request failed with status {}", status))),
+    }
+}
+
+/// Parse the currency code returned by the fundamentals endpoint, which is empty
+/// or "NA" if no currency is known
+fn parse_currency_code(resp: &Value) -> Result<Option<Currency>, MarketQuoteError> {
+    match resp.as_str() {
+        None | Some("") | Some("NA") => Ok(None),
+        Some(code) => Ok(Some(Currency::from_str(code)?)),
+    }
+}
+
+fn real_time_quote_to_quote(ticker_id: usize, eod_quote: &eod_api::RealTimeQuote) -> Quote {
+    Quote {
+        id: None,
+        ticker: ticker_id,
+        price: eod_quote.close,
+        time: unix_to_date_time(eod_quote.timestamp),
+        volume: Some(eod_quote.volume as f64),
+        bid: None,
+        ask: None,
+    }
+}
+
+/// Assign the quotes of a bulk real-time response to the requested tickers. The response's
+/// ticker codes may carry an additional exchange suffix, e.g. "AAPL.US" for ticker "AAPL".
+/// Entries which could not be parsed, e.g. for unknown tickers, are reported as errors.
+fn assign_bulk_quotes(tickers: &[Ticker], resp: Value, batch: &mut QuoteBatch) {
+    let values = match resp {
+        Value::Array(values) => values,
+        value => vec![value],
+    };
+    let eod_quotes: Vec<eod_api::RealTimeQuote> = values
+        .into_iter()
+        .filter_map(|value| serde_json::from_value(value).ok())
+        .collect();
+    for ticker in tickers {
+        let eod_quote = eod_quotes.iter().find(|q| {
+            q.code == ticker.name || q.code.split('.').next() == Some(ticker.name.as_str())
+        });
+        match (ticker.id, eod_quote) {
+            (Some(id), Some(eod_quote)) => batch.quotes.push(real_time_quote_to_quote(id, eod_quote)),
+            (None, _) => batch.errors.push((
+                ticker.name.clone(),
+                MarketQuoteError::UnexpectedError("ticker is not yet stored to database".to_string()),
+            )),
+            (_, None) => batch.errors.push((
+                ticker.name.clone(),
+                MarketQuoteError::UnexpectedError(format!("no quote delivered for ticker {}", ticker.name)),
+            )),
         }
     }
 }
 
 #[async_trait]
 impl MarketQuoteProvider for EODHistData {
+    /// Validate the API token by requesting the account's user data
+    async fn validate(&self) -> Result<(), MarketQuoteError> {
+        let url = format!("{}/user?api_token={}&fmt=json", EOD_URL, self.token);
+        let resp = reqwest::get(&url).await
+            .map_err(|err| MarketQuoteError::Transient(err.without_url().to_string()))?;
+        check_user_status(resp.status().as_u16())
+    }
+
     /// Fetch latest quote
     async fn fetch_latest_quote(&self, ticker: &Ticker) -> Result<Quote, MarketQuoteError> {
         let eod_quote = self
             .connector
-            .get_latest_quote(&ticker.name).await?;
+            .get_latest_quote(&ticker.name).await
+            .map_err(map_eod_error)?;
+        Ok(real_time_quote_to_quote(ticker.id.unwrap(), &eod_quote))
+    }
 
-        let time = unix_to_date_time(eod_quote.timestamp as u64);
-        Ok(Quote {
-            id: None,
-            ticker: ticker.id.unwrap(),
-            price: eod_quote.close,
-            time,
-            volume: Some(eod_quote.volume as f64),
-        })
+    /// Fetch latest quotes via the bulk real-time endpoint, requesting several tickers at once
+    async fn fetch_latest_quotes(&self, tickers: &[Ticker]) -> Result<QuoteBatch, MarketQuoteError> {
+        let mut batch = QuoteBatch::default();
+        for chunk in tickers.chunks(BULK_REQUEST_SIZE) {
+            let mut url = format!("{}/real-time/{}?api_token={}&fmt=json", EOD_URL, chunk[0].name, self.token);
+            if chunk.len() > 1 {
+                let others: Vec<&str> = chunk[1..].iter().map(|t| t.name.as_str()).collect();
+                url = format!("{}&s={}", url, others.join(","));
+            }
+            match get_json(&url).await {
+                Ok(resp) => assign_bulk_quotes(chunk, resp, &mut batch),
+                Err(err) => {
+                    for ticker in chunk {
+                        batch.errors.push((ticker.name.clone(), MarketQuoteError::UnexpectedError(err.to_string())));
+                    }
+                }
+            }
+        }
+        Ok(batch)
     }
 
     /// Fetch historic quotes between start and end date
@@ -57,25 +201,43 @@ impl MarketQuoteProvider for EODHistData {
                 &ticker.name,
                 start.naive_local().date(),
                 end.naive_local().date(),
-            ).await?;
+            ).await
+            .map_err(map_eod_error)?;
 
         let mut quotes = Vec::new();
         for quote in &eod_quotes {
-            let time = date_time_from_str_standard(&quote.date, 18, ticker.tz.clone())?;
+            let time = naive_date_to_date_time(&parse_date(&quote.date)?, 18, ticker.tz.clone())?;
             let volume = quote.volume.map(|vol| vol as f64);
-            if let Some(price) = quote.close {
+            if let Some(price) = self.historic_price(ticker, quote) {
                 quotes.push(Quote {
                     id: None,
                     ticker: ticker.id.unwrap(),
                     price,
                     time,
                     volume,
+                    bid: None,
+                    ask: None,
                 })
             }
         }
         Ok(quotes)
     }
 
+    /// Fetch the trading currency of the ticker, e.g. GBX for most shares listed in London
+    async fn fetch_quote_currency(&self, ticker: &Ticker) -> Result<Option<Currency>, MarketQuoteError> {
+        let url = format!(
+            "{}/fundamentals/{}?filter=General::CurrencyCode&api_token={}&fmt=json",
+            EOD_URL, ticker.name, self.token
+        );
+        let resp = get_json(&url).await.map_err(|err| match err.status() {
+            Some(status) if is_transient_status(status.as_u16()) => {
+                MarketQuoteError::Transient(format!("request failed with status {}", status))
+            }
+            _ => err.into(),
+        })?;
+        parse_currency_code(&resp)
+    }
+
     /// Fetch historic dividend payments between start and end date
     async fn fetch_dividend_history(
         &self,
@@ -88,10 +250,11 @@ impl MarketQuoteProvider for EODHistData {
             .get_dividend_history(
                 &ticker.name,
                 start.naive_local().date()
-            ).await?;
+            ).await
+            .map_err(map_eod_error)?;
         let mut div_cash_flows = Vec::new();
         for div in dividends_since_start {
-            let pay_date = date_from_str(&div.payment_date,"%Y-%m-%d")?;
+            let pay_date = parse_date(&div.payment_date)?;
             if naive_date_to_date_time(&pay_date, 18, ticker.tz.clone())? <= end {
                 let currency = Currency::from_str(&div.currency)?;
                 div_cash_flows.push(CashFlow::new(div.value, currency, pay_date));
@@ -131,6 +294,87 @@ mod tests {
         assert!(quote.price != 0.0);
     }
 
+    #[test]
+    fn assign_quotes_of_bulk_response() {
+        let make_ticker = |id, name: &str| Ticker {
+            id: Some(id),
+            asset: 1,
+            name: name.to_string(),
+            currency: Currency::from_str("USD").unwrap(),
+            source: MarketDataSource::EodHistData.to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        };
+        let tickers = vec![make_ticker(1, "AAPL"), make_ticker(2, "XXXX"), make_ticker(3, "VOD.LSE")];
+        let resp = serde_json::json!([
+            { "code": "AAPL.US", "timestamp": 1636059600, "gmtoffset": 0, "open": 151.0, "high": 152.4,
+              "low": 150.6, "close": 151.3, "volume": 60394613, "previousClose": 150.8, "change": 0.5,
+              "change_p": 0.3 },
+            { "code": "XXXX.US", "timestamp": "NA", "gmtoffset": 0, "open": "NA", "high": "NA",
+              "low": "NA", "close": "NA", "volume": "NA", "previousClose": "NA", "change": "NA",
+              "change_p": "NA" },
+            { "code": "VOD.LSE", "timestamp": 1636059600, "gmtoffset": 0, "open": 105.0, "high": 106.2,
+              "low": 104.8, "close": 105.9, "volume": 41020577, "previousClose": 105.1, "change": 0.8,
+              "change_p": 0.8 }
+        ]);
+        let mut batch = QuoteBatch::default();
+        assign_bulk_quotes(&tickers, resp, &mut batch);
+        let prices: Vec<(usize, f64)> = batch.quotes.iter().map(|q| (q.ticker, q.price)).collect();
+        assert_eq!(prices, vec![(1, 151.3), (3, 105.9)]);
+        assert_eq!(batch.errors.len(), 1);
+        assert_eq!(batch.errors[0].0, "XXXX");
+    }
+
+    #[test]
+    fn classify_validation_status() {
+        assert!(check_user_status(200).is_ok());
+        assert!(matches!(check_user_status(401), Err(MarketQuoteError::AuthFailed(_))));
+        assert!(matches!(check_user_status(403), Err(MarketQuoteError::AuthFailed(_))));
+        assert!(matches!(check_user_status(429), Err(MarketQuoteError::Transient(_))));
+        assert!(matches!(check_user_status(404), Err(MarketQuoteError::UnexpectedError(_))));
+    }
+
+    #[test]
+    fn parse_reported_currency() {
+        let gbx = Currency::from_str("GBX").unwrap();
+        assert_eq!(parse_currency_code(&serde_json::json!("GBX")).unwrap(), Some(gbx));
+        assert_eq!(parse_currency_code(&serde_json::json!("NA")).unwrap(), None);
+        assert_eq!(parse_currency_code(&serde_json::json!({})).unwrap(), None);
+        assert!(parse_currency_code(&serde_json::json!("POUND")).is_err());
+    }
+
+    #[test]
+    fn select_adjusted_or_raw_price() {
+        let ticker = Ticker {
+            id: Some(1),
+            asset: 1,
+            name: "AAPL".to_string(),
+            currency: Currency::from_str("USD").unwrap(),
+            source: MarketDataSource::EodHistData.to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        };
+        let quote = |close: Option<f64>, adjusted_close: f64| eod_api::HistoricQuote {
+            date: "2020-01-02".to_string(),
+            open: None,
+            high: None,
+            low: None,
+            close,
+            adjusted_close,
+            volume: None,
+        };
+        let raw = EODHistData::new(String::new());
+        let adjusted = EODHistData::with_adjustment(String::new(), AdjustmentMode::Adjusted);
+        assert_eq!(raw.historic_price(&ticker, &quote(Some(300.35), 74.57)), Some(300.35));
+        assert_eq!(raw.historic_price(&ticker, &quote(None, 74.57)), None);
+        assert_eq!(adjusted.historic_price(&ticker, &quote(Some(300.35), 74.57)), Some(74.57));
+        assert_eq!(adjusted.historic_price(&ticker, &quote(Some(300.35), 0.0)), Some(300.35));
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn test_eod_fetch_history() {
         let token = "OeAFFmMliFG5orCUuwAKQ8l4WWFQ67YX".to_string();
@@ -152,4 +396,13 @@ mod tests {
         assert_eq!(quotes.len(), 21);
         assert!(quotes[0].price != 0.0);
     }
+
+    #[tokio::test]
+    async fn request_errors_hide_token() {
+        // nothing listens on port 1, i.e. the connection is refused
+        let err = get_json("http://127.0.0.1:1/real-time/AAPL.US?api_token=SECRET&fmt=json")
+            .await
+            .unwrap_err();
+        assert!(!err.to_string().contains("SECRET"));
+    }
 }
@@ -43,6 +43,8 @@ impl MarketQuoteProvider for GuruFocus {
             price: quote.price.into(),
             time,
             volume: Some(quote.todays_volume.into()),
+            bid: None,
+            ask: None,
         })
     }
     /// Fetch historic quotes between start and end date
@@ -70,6 +72,8 @@ impl MarketQuoteProvider for GuruFocus {
                 price: *price,
                 time,
                 volume: None,
+                bid: None,
+                ask: None,
             })
         }
         Ok(quotes)
@@ -0,0 +1,138 @@
+//! Market data provider for testing
+//! The `MockProvider` delivers quotes of a fixed price at 18:00 without any network
+//! access and counts the requests it receives.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+
+use finql_data::{CashFlow, Currency, Quote, Ticker};
+use finql_data::date_time_helper::{make_time, naive_date_to_date_time};
+
+use super::{MarketQuoteError, MarketQuoteProvider};
+use crate::calendar::Calendar;
+
+/// Price of all quotes delivered by the `MockProvider`
+pub(crate) const MOCK_PRICE: f64 = 10.0;
+
+/// Days for which the `MockProvider` delivers quote histories
+pub(crate) enum MockHistory {
+    /// The given days of November 2021, regardless of the requested range
+    Days(Vec<u32>),
+    /// Each business day of the requested range, each quote being delivered twice
+    BusinessDays(Calendar),
+}
+
+/// Provider delivering quotes of `MOCK_PRICE` at 18:00
+pub(crate) struct MockProvider {
+    history: MockHistory,
+    currency: Option<Currency>,
+    /// Number of requests of the latest quote failing before a quote is delivered
+    failures: usize,
+    transient: bool,
+    /// Number of requests of the latest quote received so far
+    pub latest_requests: AtomicUsize,
+    /// Number of requests of quote histories received so far
+    pub history_requests: AtomicUsize,
+}
+
+impl MockProvider {
+    pub(crate) fn new(history: MockHistory) -> MockProvider {
+        MockProvider {
+            history,
+            currency: None,
+            failures: 0,
+            transient: false,
+            latest_requests: AtomicUsize::new(0),
+            history_requests: AtomicUsize::new(0),
+        }
+    }
+
+    /// Provider delivering quotes on the given days of November 2021
+    pub(crate) fn with_days(days: Vec<u32>) -> MockProvider {
+        MockProvider::new(MockHistory::Days(days))
+    }
+
+    /// Report the given currency as quote currency of all tickers
+    pub(crate) fn currency(mut self, currency: Currency) -> MockProvider {
+        self.currency = Some(currency);
+        self
+    }
+
+    /// Fail the first `failures` requests of the latest quote with a transient or permanent error
+    pub(crate) fn failing(mut self, failures: usize, transient: bool) -> MockProvider {
+        self.failures = failures;
+        self.transient = transient;
+        self
+    }
+
+    fn quote(ticker: &Ticker, time: DateTime<Local>) -> Quote {
+        Quote {
+            id: None,
+            ticker: ticker.id.unwrap(),
+            price: MOCK_PRICE,
+            time,
+            volume: None,
+            bid: None,
+            ask: None,
+        }
+    }
+}
+
+#[async_trait]
+impl MarketQuoteProvider for MockProvider {
+    /// Deliver a quote of November 8, 2021, tickers not yet stored to the database are unknown
+    async fn fetch_latest_quote(&self, ticker: &Ticker) -> Result<Quote, MarketQuoteError> {
+        let requests = self.latest_requests.fetch_add(1, Ordering::SeqCst) + 1;
+        if requests <= self.failures {
+            return Err(if self.transient {
+                MarketQuoteError::Transient("too many requests".to_string())
+            } else {
+                MarketQuoteError::UnexpectedError("symbol not found".to_string())
+            });
+        }
+        if ticker.id.is_none() {
+            return Err(MarketQuoteError::UnexpectedError("unknown ticker".to_string()));
+        }
+        Ok(MockProvider::quote(ticker, make_time(2021, 11, 8, 18, 0, 0).unwrap()))
+    }
+
+    async fn fetch_quote_history(
+        &self,
+        ticker: &Ticker,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<Quote>, MarketQuoteError> {
+        self.history_requests.fetch_add(1, Ordering::SeqCst);
+        let mut quotes = Vec::new();
+        match &self.history {
+            MockHistory::Days(days) => {
+                for day in days {
+                    quotes.push(MockProvider::quote(ticker, make_time(2021, 11, *day, 18, 0, 0).unwrap()));
+                }
+            }
+            MockHistory::BusinessDays(cal) => {
+                for date in cal.business_days(start.naive_local().date(), end.naive_local().date()) {
+                    let quote = MockProvider::quote(ticker, naive_date_to_date_time(&date, 18, None)?);
+                    quotes.push(quote.clone());
+                    quotes.push(quote);
+                }
+            }
+        }
+        Ok(quotes)
+    }
+
+    async fn fetch_quote_currency(&self, _ticker: &Ticker) -> Result<Option<Currency>, MarketQuoteError> {
+        Ok(self.currency)
+    }
+
+    async fn fetch_dividend_history(
+        &self,
+        _ticker: &Ticker,
+        _start: DateTime<Local>,
+        _end: DateTime<Local>,
+    ) -> Result<Vec<CashFlow>, MarketQuoteError> {
+        Ok(Vec::new())
+    }
+}
@@ -9,13 +9,19 @@ use serde_json;
 use thiserror::Error;
 use alpha_vantage;
 use gurufocus_api;
-use finql_data::{QuoteHandler, CashFlow, Quote, Ticker};
+use finql_data::{QuoteHandler, CashFlow, Currency, Quote, Ticker};
 
 
 pub mod alpha_vantage_wrapper;
+pub mod backfill;
 pub mod comdirect;
+pub mod csv_provider;
 pub mod eod_historical_data;
 pub mod guru_focus;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod reconcile;
+pub mod retry;
 pub mod yahoo;
 
 #[derive(Error, Debug)]
@@ -42,16 +48,62 @@ pub enum MarketQuoteError {
     GuruFocusError(#[from] gurufocus_api::GuruFocusError),
     #[error("JSON parsing error")]
     JSONError(#[from] serde_json::Error),
+    #[error("Provider reports quotes in {found}, but ticker is configured for {expected}")]
+    CurrencyMismatch { expected: Currency, found: Currency },
+    #[error("Authentication at market data provider failed: '{0}'")]
+    AuthFailed(String),
+    #[error("Reading quotes from file failed: '{0}'")]
+    FileReadFailed(String),
+    #[error("Transient error, request may be retried: '{0}'")]
+    Transient(String),
     #[error("Unexpected error: '{0}'")]
     UnexpectedError(String),
 }
 
+/// HTTP status codes indicating that a request may succeed if retried later
+pub(crate) fn is_transient_status(status: u16) -> bool {
+    matches!(status, 429 | 502 | 503 | 504)
+}
+
+
+/// Quotes fetched for several tickers at once
+#[derive(Debug, Default)]
+pub struct QuoteBatch {
+    /// Quotes fetched successfully
+    pub quotes: Vec<Quote>,
+    /// Names of the tickers whose quote could not be fetched, together with the reason
+    pub errors: Vec<(String, MarketQuoteError)>,
+}
 
 /// General interface for market data quotes provider
+/// Prices are returned in the units delivered by the provider, i.e. without applying the
+/// ticker's `factor`. The factor is applied when quotes are stored, e.g. by `update_ticker`.
 #[async_trait]
 pub trait MarketQuoteProvider: Send+Sync {
+    /// Check connectivity and credentials before sending any further requests.
+    /// Returns `MarketQuoteError::AuthFailed` if the provider rejects the credentials.
+    async fn validate(&self) -> Result<(), MarketQuoteError> {
+        Ok(())
+    }
+
     /// Fetch latest quote
     async fn fetch_latest_quote(&self, ticker: &Ticker) -> Result<Quote, MarketQuoteError>;
+
+    /// Fetch the latest quotes of several tickers. A ticker whose quote could not be fetched
+    /// is reported in the batch's errors instead of aborting the whole batch.
+    /// By default, `fetch_latest_quote` is called for each ticker, providers with bulk
+    /// endpoints should override this method.
+    async fn fetch_latest_quotes(&self, tickers: &[Ticker]) -> Result<QuoteBatch, MarketQuoteError> {
+        let mut batch = QuoteBatch::default();
+        for ticker in tickers {
+            match self.fetch_latest_quote(ticker).await {
+                Ok(quote) => batch.quotes.push(quote),
+                Err(err) => batch.errors.push((ticker.name.clone(), err)),
+            }
+        }
+        Ok(batch)
+    }
+
     /// Fetch historic quotes between start and end date
     async fn fetch_quote_history(
         &self,
@@ -60,6 +112,12 @@ pub trait MarketQuoteProvider: Send+Sync {
         end: DateTime<Local>,
     ) -> Result<Vec<Quote>, MarketQuoteError>;
 
+    /// Fetch the currency the provider reports quotes of the ticker in, if the provider
+    /// supports this. By default, `None` is returned, i.e. the ticker's currency is assumed.
+    async fn fetch_quote_currency(&self, _ticker: &Ticker) -> Result<Option<Currency>, MarketQuoteError> {
+        Ok(None)
+    }
+
     /// Fetch historic dividends (if any), returning a vector of cash flows, each cash flow representing a dividend payment per single stock
     async fn fetch_dividend_history(
         &self,
@@ -69,18 +127,44 @@ pub trait MarketQuoteProvider: Send+Sync {
     ) -> Result<Vec<CashFlow>, MarketQuoteError>;
 }
 
+/// Check that the currency reported by the provider, if any, matches the ticker's currency.
+/// Tickers with a `factor` other than 1 convert the provider's prices into the ticker's
+/// currency unit (e.g. GBX into GBP) and are therefore not checked.
+pub async fn validate_quote_currency(
+    provider: &(dyn MarketQuoteProvider + Send + Sync),
+    ticker: &Ticker,
+) -> Result<(), MarketQuoteError> {
+    if ticker.factor != 1.0 {
+        return Ok(());
+    }
+    match provider.fetch_quote_currency(ticker).await? {
+        Some(found) if found != ticker.currency => Err(MarketQuoteError::CurrencyMismatch {
+            expected: ticker.currency,
+            found,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Fetch the latest quote of the ticker and store it, scaled by the ticker's factor.
+/// Nothing is stored if the provider reports quotes in a currency other than the ticker's,
+/// see `validate_quote_currency`.
 pub async fn update_ticker<'a>(
     provider: &(dyn MarketQuoteProvider + Send + Sync),
     ticker: &Ticker,
     db: Arc<dyn QuoteHandler+Send+Sync+'a>,
 ) -> Result<(), MarketQuoteError> {
+    validate_quote_currency(provider, ticker).await?;
     let mut quote = provider.fetch_latest_quote(ticker).await?;
     quote.price *= ticker.factor;
-    db.insert_quote(&quote).await?;
+    db.upsert_quote(&quote).await?;
     Ok(())
 }
 
 
+/// Fetch the quotes of the ticker between start and end and store them, scaled by the ticker's factor.
+/// Quotes already stored for the same time are overwritten, so that overlapping ranges can be updated.
+/// Nothing is stored if the provider reports quotes in a currency other than the ticker's.
 pub async fn update_ticker_history<'a>(
     provider: &(dyn MarketQuoteProvider + Send + Sync),
     ticker: &Ticker,
@@ -88,11 +172,12 @@ pub async fn update_ticker_history<'a>(
     start: DateTime<Local>,
     end: DateTime<Local>,
 ) -> Result<(), MarketQuoteError> {
+    validate_quote_currency(provider, ticker).await?;
     let mut quotes = provider.fetch_quote_history(ticker, start, end).await?;
-    for mut quote in &mut quotes {
+    for quote in &mut quotes {
         quote.price *= ticker.factor;
-        db.insert_quote(quote).await?;
     }
+    db.upsert_quotes(&quotes).await?;
     Ok(())
 }
 
@@ -179,6 +264,8 @@ mod tests {
     use finql_data::{Asset, Currency, QuoteHandler};
     use finql_sqlite::SqliteDBPool;
 
+    use mock::MockProvider;
+
     struct DummyProvider {}
 
     #[async_trait]
@@ -190,6 +277,8 @@ mod tests {
                 price: 1.23,
                 time: Local.ymd(2020, 1, 1).and_hms_milli(0, 0, 0, 0),
                 volume: None,
+                bid: None,
+                ask: None,
             })
         }
 
@@ -210,8 +299,10 @@ mod tests {
                     price,
                     time: date,
                     volume: None,
+                    bid: None,
+                    ask: None,
                 });
-                date = date + Duration::days(1);
+                date += Duration::days(1);
                 price *= (0.0001 + 0.2 * rng.gen::<f64>()).exp();
             }
             Ok(quotes)
@@ -265,6 +356,102 @@ mod tests {
         assert_fuzzy_eq!(quotes[0].price, 1.23, tol);
     }
 
+    #[tokio::test]
+    async fn fetch_latest_quotes_of_several_tickers() {
+        let make_ticker = |id: Option<usize>, name: &str| Ticker {
+            id,
+            asset: 1,
+            name: name.to_string(),
+            currency: Currency::from_str("EUR").unwrap(),
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        };
+        let tickers = vec![
+            make_ticker(Some(1), "A"),
+            make_ticker(None, "B"),
+            make_ticker(Some(3), "C"),
+        ];
+        // the mock provider fails for tickers without id
+        let provider = MockProvider::with_days(Vec::new());
+        let batch = provider.fetch_latest_quotes(&tickers).await.unwrap();
+        let ids: Vec<usize> = batch.quotes.iter().map(|q| q.ticker).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(batch.errors.len(), 1);
+        assert_eq!(batch.errors[0].0, "B");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn validate_currency_reported_by_provider() {
+        let mut ticker = Ticker {
+            id: Some(1),
+            asset: 1,
+            name: "VOD.LSE".to_string(),
+            currency: Currency::from_str("GBP").unwrap(),
+            source: "eodhistdata".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        };
+        let provider = MockProvider::with_days(Vec::new()).currency(Currency::from_str("GBX").unwrap());
+        assert!(matches!(
+            validate_quote_currency(&provider, &ticker).await,
+            Err(MarketQuoteError::CurrencyMismatch { .. })
+        ));
+        // prices are explicitly converted from pence into pounds
+        ticker.factor = 0.01;
+        assert!(validate_quote_currency(&provider, &ticker).await.is_ok());
+        ticker.factor = 1.0;
+        ticker.currency = Currency::from_str("GBX").unwrap();
+        assert!(validate_quote_currency(&provider, &ticker).await.is_ok());
+        // providers not reporting a currency are not checked
+        assert!(validate_quote_currency(&DummyProvider {}, &ticker).await.is_ok());
+
+        // quotes in the wrong currency are not stored
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let db = Arc::new(db);
+        let mut ticker = prepare_db(db.clone()).await;
+        ticker.currency = Currency::from_str("GBP").unwrap();
+        assert!(matches!(
+            update_ticker(&provider, &ticker, db.clone()).await,
+            Err(MarketQuoteError::CurrencyMismatch { .. })
+        ));
+        let start = Local.ymd(2020, 1, 2).and_hms_milli(0, 0, 0, 0);
+        let end = Local.ymd(2020, 1, 3).and_hms_milli(23, 59, 59, 999);
+        assert!(matches!(
+            update_ticker_history(&provider, &ticker, db.clone(), start, end).await,
+            Err(MarketQuoteError::CurrencyMismatch { .. })
+        ));
+        assert!(db.get_all_quotes_for_ticker(ticker.id.unwrap()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn apply_ticker_factor_to_stored_quotes() {
+        let tol = 1.0e-10;
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let db = Arc::new(db);
+        let mut ticker = prepare_db(db.clone()).await;
+        // provider delivers prices in pence, ticker currency is GBP
+        ticker.currency = Currency::from_str("GBP").unwrap();
+        ticker.factor = 0.01;
+        let provider = DummyProvider {};
+        update_ticker(&provider, &ticker, db.clone()).await.unwrap();
+        let start = Local.ymd(2020, 1, 2).and_hms_milli(0, 0, 0, 0);
+        let end = Local.ymd(2020, 1, 3).and_hms_milli(23, 59, 59, 999);
+        update_ticker_history(&provider, &ticker, db.clone(), start, end).await.unwrap();
+        let quotes = db.get_all_quotes_for_ticker(ticker.id.unwrap()).await.unwrap();
+        assert_eq!(quotes.len(), 3);
+        assert_fuzzy_eq!(quotes[0].price, 0.0123, tol);
+        assert_fuzzy_eq!(quotes[1].price, 0.0123, tol);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn test_fetch_quote_history() {
         let tol = 1.0e-6;
@@ -281,4 +468,26 @@ mod tests {
         assert_eq!(quotes.len(), 31);
         assert_fuzzy_eq!(quotes[0].price, 1.23, tol);
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn update_overlapping_quote_history() {
+        let tol = 1.0e-10;
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let db = Arc::new(db);
+        let ticker = prepare_db(db.clone()).await;
+        let provider = DummyProvider {};
+        let start = Local.ymd(2020, 1, 1).and_hms_milli(0, 0, 0, 0);
+        let end = Local.ymd(2020, 1, 10).and_hms_milli(23, 59, 59, 999);
+        update_ticker_history(&provider, &ticker, db.clone(), start, end).await.unwrap();
+        // the quote of January 10 is delivered again
+        let start = Local.ymd(2020, 1, 10).and_hms_milli(0, 0, 0, 0);
+        let end = Local.ymd(2020, 1, 20).and_hms_milli(23, 59, 59, 999);
+        update_ticker_history(&provider, &ticker, db.clone(), start, end).await.unwrap();
+        let quotes = db.get_all_quotes_for_ticker(ticker.id.unwrap()).await.unwrap();
+        assert_eq!(quotes.len(), 20);
+        assert_eq!(quotes[9].time, start);
+        assert_fuzzy_eq!(quotes[9].price, 1.23, tol);
+    }
 }
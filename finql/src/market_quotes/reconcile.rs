@@ -0,0 +1,149 @@
+//! Reconciliation of stored quotes against a market data provider
+//! Stored quotes of a ticker are compared with a freshly fetched quote history
+//! to detect missing, extra or diverging quotes, e.g. for data quality audits.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Local};
+
+use finql_data::{QuoteHandler, Ticker};
+
+use super::{MarketQuoteError, MarketQuoteProvider};
+
+/// Difference between a stored quote and the quote delivered by the provider
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuoteDiff {
+    /// Quote delivered by the provider, but not stored
+    Missing { time: DateTime<Local>, provider_price: f64 },
+    /// Quote stored, but not delivered by the provider
+    Extra { time: DateTime<Local>, stored_price: f64 },
+    /// Stored price differs from the provider's price by more than the tolerance
+    Divergent { time: DateTime<Local>, stored_price: f64, provider_price: f64 },
+}
+
+/// Compare the stored quotes of `ticker` between `start` and `end` with the quote history
+/// fetched from `provider`. Quotes are aligned by their time stamp, provider prices are
+/// scaled by the ticker's factor and prices are considered equal if their absolute difference
+/// does not exceed `tol`. The differences found are returned sorted by time.
+pub async fn reconcile(
+    provider: &(dyn MarketQuoteProvider + Send + Sync),
+    handler: Arc<dyn QuoteHandler + Send + Sync>,
+    ticker: &Ticker,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    tol: f64,
+) -> Result<Vec<QuoteDiff>, MarketQuoteError> {
+    let ticker_id = ticker.id.ok_or_else(|| {
+        MarketQuoteError::UnexpectedError("ticker is not yet stored to database".to_string())
+    })?;
+    let stored: BTreeMap<DateTime<Local>, f64> = handler
+        .get_all_quotes_for_ticker(ticker_id)
+        .await?
+        .into_iter()
+        .filter(|q| q.time >= start && q.time <= end)
+        .map(|q| (q.time, q.price))
+        .collect();
+    let fetched: BTreeMap<DateTime<Local>, f64> = provider
+        .fetch_quote_history(ticker, start, end)
+        .await?
+        .into_iter()
+        .filter(|q| q.time >= start && q.time <= end)
+        .map(|q| (q.time, q.price * ticker.factor))
+        .collect();
+
+    let mut diffs = Vec::new();
+    for (time, provider_price) in &fetched {
+        match stored.get(time) {
+            None => diffs.push(QuoteDiff::Missing {
+                time: *time,
+                provider_price: *provider_price,
+            }),
+            Some(stored_price) if (stored_price - provider_price).abs() > tol => {
+                diffs.push(QuoteDiff::Divergent {
+                    time: *time,
+                    stored_price: *stored_price,
+                    provider_price: *provider_price,
+                })
+            }
+            _ => {}
+        }
+    }
+    for (time, stored_price) in &stored {
+        if !fetched.contains_key(time) {
+            diffs.push(QuoteDiff::Extra {
+                time: *time,
+                stored_price: *stored_price,
+            });
+        }
+    }
+    diffs.sort_by_key(|diff| match diff {
+        QuoteDiff::Missing { time, .. }
+        | QuoteDiff::Extra { time, .. }
+        | QuoteDiff::Divergent { time, .. } => *time,
+    });
+    Ok(diffs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    use finql_data::{Asset, Currency, Quote};
+    use finql_data::date_time_helper::make_time;
+    use finql_sqlite::SqliteDBPool;
+
+    use crate::market_quotes::mock::MockProvider;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn reconcile_corrupted_quote() {
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let db: Arc<dyn QuoteHandler + Send + Sync> = Arc::new(db);
+        let asset_id = db
+            .insert_asset(&Asset { id: None, name: "AUDIT".to_string(), wkn: None, isin: None, note: None })
+            .await.unwrap();
+        let mut ticker = Ticker {
+            id: None,
+            asset: asset_id,
+            name: "AUDIT".to_string(),
+            currency: Currency::from_str("EUR").unwrap(),
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        };
+        ticker.id = Some(db.insert_ticker(&ticker).await.unwrap());
+        for (day, price) in [(8, 10.0), (9, 10.0), (10, 12.5), (11, 10.0), (12, 10.0)] {
+            db.insert_quote(&Quote {
+                id: None,
+                ticker: ticker.id.unwrap(),
+                price,
+                time: make_time(2021, 11, day, 18, 0, 0).unwrap(),
+                volume: None,
+                bid: None,
+                ask: None,
+            }).await.unwrap();
+        }
+        let start = make_time(2021, 11, 8, 0, 0, 0).unwrap();
+        let end = make_time(2021, 11, 12, 23, 0, 0).unwrap();
+
+        let provider = MockProvider::with_days(vec![8, 9, 10, 11, 12]);
+        let diffs = reconcile(&provider, db.clone(), &ticker, start, end, 1e-6).await.unwrap();
+        assert_eq!(diffs, vec![QuoteDiff::Divergent {
+            time: make_time(2021, 11, 10, 18, 0, 0).unwrap(),
+            stored_price: 12.5,
+            provider_price: 10.0,
+        }]);
+
+        let provider = MockProvider::with_days(vec![9, 10, 11, 12]);
+        let diffs = reconcile(&provider, db, &ticker, start, end, 5.0).await.unwrap();
+        assert_eq!(diffs, vec![QuoteDiff::Extra {
+            time: make_time(2021, 11, 8, 18, 0, 0).unwrap(),
+            stored_price: 10.0,
+        }]);
+    }
+}
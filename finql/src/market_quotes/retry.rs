@@ -0,0 +1,143 @@
+//! Retry of failed requests to market data providers
+//! Vendor APIs occasionally reject requests due to rate limits or overload. The
+//! `RetryingProvider` wraps any provider and retries requests failing with
+//! `MarketQuoteError::Transient`, waiting exponentially longer between attempts.
+
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+
+use finql_data::{CashFlow, Currency, Quote, Ticker};
+
+use super::{MarketQuoteError, MarketQuoteProvider, QuoteBatch};
+
+/// Provider retrying transient failures of the wrapped provider
+pub struct RetryingProvider<P: MarketQuoteProvider> {
+    provider: P,
+    /// Maximum number of attempts per request, including the first one
+    max_attempts: u32,
+    /// Waiting time before the first retry, doubled for each further retry
+    initial_backoff: Duration,
+}
+
+impl<P: MarketQuoteProvider> RetryingProvider<P> {
+    pub fn new(provider: P, max_attempts: u32, initial_backoff: Duration) -> RetryingProvider<P> {
+        RetryingProvider {
+            provider,
+            max_attempts: max_attempts.max(1),
+            initial_backoff,
+        }
+    }
+
+    /// Call `request` until it succeeds, fails with a non-transient error or the
+    /// maximum number of attempts is reached
+    async fn retry<T, F, Fut>(&self, mut request: F) -> Result<T, MarketQuoteError>
+    where
+        F: FnMut() -> Fut + Send,
+        Fut: Future<Output = Result<T, MarketQuoteError>> + Send,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match request().await {
+                Err(MarketQuoteError::Transient(_)) if attempt < self.max_attempts => {
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<P: MarketQuoteProvider> MarketQuoteProvider for RetryingProvider<P> {
+    async fn validate(&self) -> Result<(), MarketQuoteError> {
+        self.retry(|| self.provider.validate()).await
+    }
+
+    async fn fetch_latest_quote(&self, ticker: &Ticker) -> Result<Quote, MarketQuoteError> {
+        self.retry(|| self.provider.fetch_latest_quote(ticker)).await
+    }
+
+    /// Fetch the latest quotes with the wrapped provider's batch method. Only a failure of the
+    /// batch as a whole is retried, errors reported for single tickers are passed on as is.
+    async fn fetch_latest_quotes(&self, tickers: &[Ticker]) -> Result<QuoteBatch, MarketQuoteError> {
+        self.retry(|| self.provider.fetch_latest_quotes(tickers)).await
+    }
+
+    async fn fetch_quote_history(
+        &self,
+        ticker: &Ticker,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<Quote>, MarketQuoteError> {
+        self.retry(|| self.provider.fetch_quote_history(ticker, start, end)).await
+    }
+
+    async fn fetch_quote_currency(&self, ticker: &Ticker) -> Result<Option<Currency>, MarketQuoteError> {
+        self.retry(|| self.provider.fetch_quote_currency(ticker)).await
+    }
+
+    async fn fetch_dividend_history(
+        &self,
+        ticker: &Ticker,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<CashFlow>, MarketQuoteError> {
+        self.retry(|| self.provider.fetch_dividend_history(ticker, start, end)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::atomic::Ordering;
+
+    use crate::market_quotes::mock::{MockProvider, MOCK_PRICE};
+
+    fn flaky(failures: usize, transient: bool) -> RetryingProvider<MockProvider> {
+        RetryingProvider::new(
+            MockProvider::with_days(Vec::new()).failing(failures, transient),
+            3,
+            Duration::from_millis(1),
+        )
+    }
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        let ticker = Ticker {
+            id: Some(1),
+            asset: 1,
+            name: "FLAKY".to_string(),
+            currency: Currency::from_str("EUR").unwrap(),
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+        };
+
+        let provider = flaky(2, true);
+        assert_eq!(provider.fetch_latest_quote(&ticker).await.unwrap().price, MOCK_PRICE);
+        assert_eq!(provider.provider.latest_requests.load(Ordering::SeqCst), 3);
+
+        let provider = flaky(3, true);
+        assert!(matches!(
+            provider.fetch_latest_quote(&ticker).await,
+            Err(MarketQuoteError::Transient(_))
+        ));
+        assert_eq!(provider.provider.latest_requests.load(Ordering::SeqCst), 3);
+
+        let provider = flaky(1, false);
+        assert!(matches!(
+            provider.fetch_latest_quote(&ticker).await,
+            Err(MarketQuoteError::UnexpectedError(_))
+        ));
+        assert_eq!(provider.provider.latest_requests.load(Ordering::SeqCst), 1);
+    }
+}
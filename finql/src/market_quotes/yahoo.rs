@@ -20,6 +20,8 @@ impl MarketQuoteProvider for Yahoo {
             price: quote.close,
             time: unix_to_date_time(quote.timestamp),
             volume: Some(quote.volume as f64),
+            bid: None,
+            ask: None,
         })
     }
     /// Fetch historic quotes between start and end date
@@ -43,6 +45,8 @@ impl MarketQuoteProvider for Yahoo {
                 price: quote.close,
                 time,
                 volume,
+                bid: None,
+                ask: None,
             })
         }
         Ok(quotes)
@@ -9,7 +9,7 @@ use chrono::{NaiveDate,DateTime, Local};
 use chrono::offset::TimeZone;
 
 use finql_data::{AssetHandler, QuoteHandler, DataError,Transaction, 
-    TransactionType, Currency, CurrencyConverter};
+    TransactionType, CashAmount, Currency, CurrencyConverter};
 use crate::Market;
 
 /// Errors related to position calculation
@@ -128,7 +128,7 @@ impl Position {
         } else {
             // No asset ID, must be some technical account, set price to 1.0
             self.last_quote = Some(1.0);
-            self.last_quote_time = Some(Local::now());
+            self.last_quote_time = Some(time);
         };
     }
 }
@@ -405,6 +405,23 @@ pub async fn calculate_position_for_period(currency: Currency, transactions: &[T
     Ok((position, totals))
 }
 
+/// Value a single position of an asset at the given time in base currency.
+/// The position is valued with the last quote before `time`, which is stored already scaled
+/// by the ticker's factor, and converted to the base currency with the last fx rate before `time`.
+/// If no quote is found, `DataError::NotFound` is returned, if no fx rate is found,
+/// `DataError::FxRateMissing` is returned.
+pub async fn value_position(asset_id: usize, quantity: f64, time: DateTime<Local>,
+        handler: Arc<dyn QuoteHandler+Send+Sync>, base: Currency) -> Result<CashAmount, DataError> {
+    let (quote, currency) = handler.get_last_quote_before_by_id(asset_id, time).await?;
+    let market = Market::new(handler);
+    let fx_rate = market.fx_rate(currency, base, time).await
+        .map_err(|_| DataError::FxRateMissing(format!("{}/{}", currency, base)))?;
+    Ok(CashAmount {
+        amount: quantity * quote.price * fx_rate,
+        currency: base,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -693,6 +710,8 @@ mod tests {
                 price: 12.34,
                 time,
                 volume: None,
+                bid: None,
+                ask: None,
             })
             .await.unwrap();
         let _ = db
@@ -702,6 +721,8 @@ mod tests {
                 price: 43.21,
                 time,
                 volume: None,
+                bid: None,
+                ask: None,
             })
             .await.unwrap();
         let mut eur_position = Position::new(Some(eur_id), eur);
@@ -729,4 +750,100 @@ mod tests {
             "2019-12-30 10:00:00"
         );
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_value_position() {
+        let tol = 1e-11;
+        let db_pool = SqliteDBPool::in_memory().await.unwrap();
+        let db = db_pool.get_conection().await.unwrap();
+        db.init().await.unwrap();
+        let db: Arc<dyn QuoteHandler+Send+Sync> = Arc::new(db);
+        let eur = Currency::from_str("EUR").unwrap();
+        let usd = Currency::from_str("USD").unwrap();
+        let jpy = Currency::from_str("JPY").unwrap();
+        let us_id = db
+            .insert_asset(&Asset {
+                id: None,
+                name: "US Stock".to_string(),
+                wkn: None,
+                isin: None,
+                note: None,
+            })
+            .await.unwrap();
+        let us_ticker_id = db
+            .insert_ticker(&Ticker {
+                id: None,
+                name: "US_STOCK".to_string(),
+                asset: us_id,
+                priority: 10,
+                currency: usd,
+                source: "manual".to_string(),
+                factor: 1.0,
+                tz: None,
+                cal: None,
+            })
+            .await.unwrap();
+        let time = make_time(2019, 12, 30, 10, 0, 0).unwrap();
+        db.insert_quote(&Quote {
+                id: None,
+                ticker: us_ticker_id,
+                price: 56.78,
+                time,
+                volume: None,
+                bid: None,
+                ask: None,
+            })
+            .await.unwrap();
+        crate::fx_rates::insert_fx_quote(0.9, usd, eur, time, db.clone()).await.unwrap();
+
+        let value_time = make_time(2019, 12, 31, 10, 0, 0).unwrap();
+        let value = value_position(us_id, 10.0, value_time, db.clone(), eur).await.unwrap();
+        assert_eq!(value.currency, eur);
+        assert_fuzzy_eq!(value.amount, 10.0 * 56.78 * 0.9, tol);
+        let value = value_position(us_id, 10.0, value_time, db.clone(), usd).await.unwrap();
+        assert_fuzzy_eq!(value.amount, 567.8, tol);
+
+        assert!(matches!(value_position(us_id, 10.0, value_time, db.clone(), jpy).await,
+            Err(DataError::FxRateMissing(_))));
+        let before_quote = make_time(2019, 12, 29, 10, 0, 0).unwrap();
+        assert!(matches!(value_position(us_id, 10.0, before_quote, db.clone(), eur).await,
+            Err(DataError::NotFound(_))));
+
+        // quotes in pence are stored in pounds, i.e. already multiplied by the factor
+        let gbp = Currency::from_str("GBP").unwrap();
+        let uk_id = db
+            .insert_asset(&Asset {
+                id: None,
+                name: "UK Stock".to_string(),
+                wkn: None,
+                isin: None,
+                note: None,
+            })
+            .await.unwrap();
+        let uk_ticker_id = db
+            .insert_ticker(&Ticker {
+                id: None,
+                name: "UK_STOCK".to_string(),
+                asset: uk_id,
+                priority: 10,
+                currency: gbp,
+                source: "manual".to_string(),
+                factor: 0.01,
+                tz: None,
+                cal: None,
+            })
+            .await.unwrap();
+        db.insert_quote(&Quote {
+                id: None,
+                ticker: uk_ticker_id,
+                price: 1234.0 * 0.01,
+                time,
+                volume: None,
+                bid: None,
+                ask: None,
+            })
+            .await.unwrap();
+        let value = value_position(uk_id, 100.0, value_time, db.clone(), gbp).await.unwrap();
+        assert_fuzzy_eq!(value.amount, 1234.0, tol);
+    }
 }
@@ -1,10 +1,16 @@
-use chrono::NaiveDate;
-use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use argmin::prelude::*;
+use argmin::solver::brent::Brent;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use finql_data::currency::Currency;
 use finql_data::cash_flow::{CashAmount, CashFlow};
 
 use crate::day_count_conv::DayCountConv;
+use crate::interpolation::{InterpolationError, Interpolator, Linear};
+use crate::time_period::TimePeriod;
 
 /// Methods for compounding interest rates
 #[derive(Deserialize, Serialize, Clone, Copy, Debug)]
@@ -23,16 +29,116 @@ pub enum Compounding {
     Continuous,
 }
 
+impl Compounding {
+    /// Growth factor of one unit of currency invested for one year at `rate`
+    fn annual_growth(&self, rate: f64) -> f64 {
+        match self {
+            Compounding::Simple | Compounding::Annual => 1. + rate,
+            Compounding::SemiAnnual => (1. + 0.5 * rate).powi(2),
+            Compounding::Quarterly => (1. + 0.25 * rate).powi(4),
+            Compounding::Monthly => (1. + rate / 12.).powi(12),
+            Compounding::Continuous => rate.exp(),
+        }
+    }
+
+    /// Discount factor for the year fraction `yf` at `rate` compounded with this method
+    pub fn discount_factor(&self, rate: f64, yf: f64) -> f64 {
+        match self {
+            Compounding::Simple => 1. / (1. + rate * yf),
+            Compounding::Annual => (1. + rate).powf(-yf),
+            Compounding::SemiAnnual => (1. + 0.5 * rate).powf(-2. * yf),
+            Compounding::Quarterly => (1. + 0.25 * rate).powf(-4. * yf),
+            Compounding::Monthly => (1. + rate / 12.).powf(-12. * yf),
+            Compounding::Continuous => (-rate * yf).exp(),
+        }
+    }
+
+    /// Rate compounded with this method that gives the discount factor `df` for the
+    /// year fraction `yf`, i.e. the inverse of `discount_factor`
+    pub fn implied_rate(&self, df: f64, yf: f64) -> f64 {
+        match self {
+            Compounding::Simple => (1. / df - 1.) / yf,
+            Compounding::Annual => df.powf(-1. / yf) - 1.,
+            Compounding::SemiAnnual => 2. * (df.powf(-1. / (2. * yf)) - 1.),
+            Compounding::Quarterly => 4. * (df.powf(-1. / (4. * yf)) - 1.),
+            Compounding::Monthly => 12. * (df.powf(-1. / (12. * yf)) - 1.),
+            Compounding::Continuous => -df.ln() / yf,
+        }
+    }
+
+    /// Convert a rate given with this compounding method into the equivalent rate with
+    /// compounding method `to`, i.e. the rate that gives the same growth over one year.
+    pub fn convert_rate(&self, rate: f64, to: Compounding) -> f64 {
+        let growth = self.annual_growth(rate);
+        match to {
+            Compounding::Simple | Compounding::Annual => growth - 1.,
+            Compounding::SemiAnnual => 2. * (growth.powf(0.5) - 1.),
+            Compounding::Quarterly => 4. * (growth.powf(0.25) - 1.),
+            Compounding::Monthly => 12. * (growth.powf(1. / 12.) - 1.),
+            Compounding::Continuous => growth.ln(),
+        }
+    }
+}
+
 /// Error related to market data object
-#[derive(Debug)]
-pub struct DiscountError;
+#[derive(Debug, PartialEq)]
+pub enum DiscountError {
+    /// The cash flow currency does not match the discounter currency
+    CurrencyMismatch { expected: Currency, found: Currency },
+    /// All cash flows have the same sign, i.e. there is no rate that gives zero value
+    NoSignChange,
+    /// The initial cash flow and the net of all future cash flows don't have opposite signs
+    InvalidSignConvention,
+    /// Calculation of a rate did not converge within the given number of iterations
+    NotConverged { iters: u64 },
+    /// The rate to be calculated does not lie within the search interval
+    RootNotBracketed { lower: f64, upper: f64 },
+    /// The period has no positive length with respect to the day count convention
+    InvalidPeriod,
+    /// There are no cash flows to discount
+    NoCashFlows,
+    /// There are no cash flows after the date of the initial cash flow
+    NoFutureCashFlows,
+}
 
 impl std::fmt::Display for DiscountError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "discount error: the cash flow currency does not match the discounter currency"
-        )
+        match self {
+            DiscountError::CurrencyMismatch { expected, found } => write!(
+                f,
+                "discount error: the cash flow currency {} does not match the discounter currency {}",
+                found, expected
+            ),
+            DiscountError::NoSignChange => write!(
+                f,
+                "discount error: all cash flows have the same sign, there is no rate giving zero value"
+            ),
+            DiscountError::InvalidSignConvention => write!(
+                f,
+                "discount error: the initial cash flow and the net of all future cash flows must have \
+                opposite signs, e.g. a purchase must be given as negative cash flow"
+            ),
+            DiscountError::NotConverged { iters } => write!(
+                f,
+                "discount error: rate calculation did not converge within {} iterations",
+                iters
+            ),
+            DiscountError::RootNotBracketed { lower, upper } => write!(
+                f,
+                "discount error: the rate does not lie between {} and {}",
+                lower, upper
+            ),
+            DiscountError::InvalidPeriod => write!(
+                f,
+                "discount error: the period must have a positive year fraction"
+            ),
+            DiscountError::NoCashFlows => write!(f, "discount error: no cash flows given"),
+            DiscountError::NoFutureCashFlows => write!(
+                f,
+                "discount error: there are no cash flows after the date of the initial cash flow \
+                that could be discounted"
+            ),
+        }
     }
 }
 
@@ -65,7 +171,10 @@ pub trait Discounter {
                 currency: cf.amount.currency,
             })
         } else {
-            Err(DiscountError)
+            Err(DiscountError::CurrencyMismatch {
+                expected: self.currency(),
+                found: cf.amount.currency,
+            })
         }
     }
 
@@ -83,13 +192,44 @@ pub trait Discounter {
             if self.currency() == cf.amount.currency {
                 amount.amount += self.discount_factor(today, cf.date) * cf.amount.amount;
             } else {
-                return Err(DiscountError);
+                return Err(DiscountError::CurrencyMismatch {
+                    expected: self.currency(),
+                    found: cf.amount.currency,
+                });
             }
         }
         Ok(amount)
     }
 }
 
+/// References to discounters are discounters as well, such that functions expecting
+/// a `Discounter` may be passed a borrowed one
+impl<D: Discounter + ?Sized> Discounter for &D {
+    fn discount_factor(&self, today: NaiveDate, pay_date: NaiveDate) -> f64 {
+        (**self).discount_factor(today, pay_date)
+    }
+
+    fn currency(&self) -> Currency {
+        (**self).currency()
+    }
+
+    fn discount_cash_flow(
+        &self,
+        cf: &CashFlow,
+        today: NaiveDate,
+    ) -> Result<CashAmount, DiscountError> {
+        (**self).discount_cash_flow(cf, today)
+    }
+
+    fn discount_cash_flow_stream(
+        &self,
+        cf_stream: &[CashFlow],
+        today: NaiveDate,
+    ) -> Result<CashAmount, DiscountError> {
+        (**self).discount_cash_flow_stream(cf_stream, today)
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Copy, Debug)]
 pub struct FlatRate {
     pub rate: f64,
@@ -113,6 +253,31 @@ impl FlatRate {
             currency,
         }
     }
+
+    /// Equivalent rate with the compounding method `target`, keeping day count convention
+    /// and currency. Simple compounding is treated as simple interest over one year.
+    pub fn to_compounding(&self, target: Compounding) -> FlatRate {
+        FlatRate {
+            rate: self.compounding.convert_rate(self.rate, target),
+            compounding: target,
+            ..*self
+        }
+    }
+
+    /// Forward rate between `start` and `end` implied by this rate, given with the same
+    /// compounding method and day count convention
+    pub fn forward_rate(&self, start: NaiveDate, end: NaiveDate) -> Result<f64, DiscountError> {
+        let yf = self
+            .day_count_conv
+            .year_fraction(start, end, None, None)
+            .map_err(|_| DiscountError::InvalidPeriod)?;
+        if yf <= 0. {
+            return Err(DiscountError::InvalidPeriod);
+        }
+        Ok(self
+            .compounding
+            .implied_rate(self.discount_factor(start, end), yf))
+    }
 }
 
 impl Discounter for FlatRate {
@@ -121,14 +286,197 @@ impl Discounter for FlatRate {
             .day_count_conv
             .year_fraction(today, pay_date, None, None)
             .unwrap();
-        match self.compounding {
-            Compounding::Simple => 1. / (1. + self.rate * yf),
-            Compounding::Annual => (1. + self.rate).powf(-yf),
-            Compounding::SemiAnnual => (1. + 0.5 * self.rate).powf(-2. * yf),
-            Compounding::Quarterly => (1. + 0.25 * self.rate).powf(-4. * yf),
-            Compounding::Monthly => (1. + self.rate / 12.).powf(-12. * yf),
-            Compounding::Continuous => (-self.rate * yf).exp(),
+        self.compounding.discount_factor(self.rate, yf)
+    }
+
+    fn currency(&self) -> Currency {
+        self.currency
+    }
+}
+
+/// Zero rate curve given by zero rates at a set of pivot dates. Zero rates between pivots are
+/// interpolated linearly in time, beyond the first and the last pivot the zero rate is flat.
+#[derive(Clone, Debug)]
+pub struct ZeroCurve {
+    pivots: Vec<(NaiveDate, f64)>,
+    day_count_conv: DayCountConv,
+    compounding: Compounding,
+    currency: Currency,
+}
+
+impl ZeroCurve {
+    /// Constructor of `ZeroCurve`, the pivots are sorted by date and must not contain
+    /// any date twice
+    pub fn new(
+        mut pivots: Vec<(NaiveDate, f64)>,
+        day_count_conv: DayCountConv,
+        compounding: Compounding,
+        currency: Currency,
+    ) -> Result<ZeroCurve, InterpolationError> {
+        if pivots.is_empty() {
+            return Err(InterpolationError::NoKnots);
+        }
+        pivots.sort_by_key(|(date, _)| *date);
+        if pivots.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(InterpolationError::UnsortedKnots);
+        }
+        Ok(ZeroCurve {
+            pivots,
+            day_count_conv,
+            compounding,
+            currency,
+        })
+    }
+
+    /// Bootstrap a zero curve from par rates of instruments with annual coupons, given as pairs
+    /// of maturity and par rate. Starting with the shortest maturity, the zero rate at each
+    /// maturity is solved for, such that the instrument issued at `settle` prices to par.
+    /// Coupon dates are rolled back annually from the maturity, with a short first period
+    /// starting at `settle`.
+    pub fn bootstrap(
+        instruments: &[(NaiveDate, f64)],
+        settle: NaiveDate,
+        day_count: DayCountConv,
+        compounding: Compounding,
+        currency: Currency,
+    ) -> Result<ZeroCurve, DiscountError> {
+        let mut instruments = instruments.to_vec();
+        instruments.sort_by_key(|(maturity, _)| *maturity);
+        if instruments.is_empty() {
+            return Err(DiscountError::NoCashFlows);
+        }
+        if instruments[0].0 <= settle || instruments.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(DiscountError::InvalidPeriod);
+        }
+        let mut curve = ZeroCurve {
+            pivots: Vec::new(),
+            day_count_conv: day_count,
+            compounding,
+            currency,
+        };
+        for (maturity, par_rate) in instruments {
+            let mut payments = Vec::new();
+            let mut end = maturity;
+            let mut years = 1;
+            loop {
+                // a period of whole years is always valid
+                let start = TimePeriod::from_str(&format!("{}Y", years))
+                    .unwrap()
+                    .sub_from(maturity, None)
+                    .max(settle);
+                let yf = day_count
+                    .year_fraction(start, end, None, None)
+                    .map_err(|_| DiscountError::InvalidPeriod)?;
+                payments.push((end, par_rate * yf));
+                if start == settle {
+                    break;
+                }
+                end = start;
+                years += 1;
+            }
+            payments[0].1 += 1.;
+            let pricer = ParInstrumentPricer {
+                curve: &curve,
+                maturity,
+                payments: &payments,
+                settle,
+            };
+            let (lower, upper) = (-0.5, 0.5);
+            if pricer.value(lower) * pricer.value(upper) > 0. {
+                return Err(DiscountError::RootNotBracketed { lower, upper });
+            }
+            let solver = Brent::new(lower, upper, 1e-12);
+            let res = Executor::new(pricer, solver, 0.).max_iters(100).run();
+            let zero_rate = match res {
+                Ok(val) if val.state.termination_reason != TerminationReason::MaxItersReached => {
+                    val.state.get_param()
+                }
+                _ => return Err(DiscountError::NotConverged { iters: 100 }),
+            };
+            curve.pivots.push((maturity, zero_rate));
         }
+        Ok(curve)
+    }
+
+    /// Zero rate interpolated at `date`
+    pub fn zero_rate(&self, date: NaiveDate) -> f64 {
+        let knots: Vec<(f64, f64)> = self
+            .pivots
+            .iter()
+            .map(|(date, rate)| (date.num_days_from_ce() as f64, *rate))
+            .collect();
+        // pivots are non-empty and strictly sorted by construction
+        Linear
+            .interpolate(&knots, date.num_days_from_ce() as f64)
+            .unwrap()
+    }
+}
+
+/// Price of an instrument relative to par, depending on the zero rate at its maturity
+#[derive(Clone)]
+struct ParInstrumentPricer<'a> {
+    curve: &'a ZeroCurve,
+    maturity: NaiveDate,
+    payments: &'a [(NaiveDate, f64)],
+    settle: NaiveDate,
+}
+
+impl<'a> ParInstrumentPricer<'a> {
+    fn value(&self, zero_rate: f64) -> f64 {
+        let mut curve = self.curve.clone();
+        curve.pivots.push((self.maturity, zero_rate));
+        self.payments
+            .iter()
+            .map(|(date, amount)| amount * curve.discount_factor(self.settle, *date))
+            .sum::<f64>()
+            - 1.
+    }
+}
+
+impl<'a> ArgminOp for ParInstrumentPricer<'a> {
+    // one dimensional problem, no vector needed
+    type Float = f64;
+    type Param = f64;
+    type Output = f64;
+    type Hessian = ();
+    type Jacobian = ();
+
+    fn apply(&self, p: &Self::Param) -> Result<Self::Output, Error> {
+        Ok(self.value(*p))
+    }
+}
+
+/// Dummy implementation of Serialize
+impl<'a> Serialize for ParInstrumentPricer<'a> {
+    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Err(serde::ser::Error::custom(
+            "serialization is disabled".to_string()
+        ))
+    }
+}
+
+/// Dummy implementation of Deserialize
+impl<'de> Deserialize<'de> for ParInstrumentPricer<'de> {
+    fn deserialize<D>(_deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Err(serde::de::Error::custom(
+            "deserialization is disabled".to_string()
+        ))
+    }
+}
+
+impl Discounter for ZeroCurve {
+    fn discount_factor(&self, today: NaiveDate, pay_date: NaiveDate) -> f64 {
+        let yf = self
+            .day_count_conv
+            .year_fraction(today, pay_date, None, None)
+            .unwrap();
+        self.compounding.discount_factor(self.zero_rate(pay_date), yf)
     }
 
     fn currency(&self) -> Currency {
@@ -139,9 +487,7 @@ impl Discounter for FlatRate {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::time_period::TimePeriod;
     use std::f64;
-    use std::str::FromStr;
 
     #[test]
     fn compounding_methods() {
@@ -230,6 +576,148 @@ mod tests {
         );
     }
 
+    #[test]
+    fn convert_flat_rate_compounding() {
+        let curr = Currency::from_str("EUR").unwrap();
+        let annual = FlatRate::new(0.05, DayCountConv::Act360, Compounding::Annual, curr);
+        let continuous = annual.to_compounding(Compounding::Continuous);
+        assert_fuzzy_eq!(continuous.rate, 1.05_f64.ln(), 1e-12);
+        assert_eq!(continuous.currency(), curr);
+        assert!(matches!(continuous.day_count_conv, DayCountConv::Act360));
+        assert_fuzzy_eq!(continuous.to_compounding(Compounding::Annual).rate, 0.05, 1e-12);
+
+        // equivalent rates give the same discount factors
+        let today = NaiveDate::from_ymd(2021, 1, 4);
+        let pay_date = NaiveDate::from_ymd(2023, 6, 30);
+        let semi_annual = annual.to_compounding(Compounding::SemiAnnual);
+        assert_fuzzy_eq!(
+            semi_annual.discount_factor(today, pay_date),
+            annual.discount_factor(today, pay_date),
+            1e-12
+        );
+    }
+
+    #[test]
+    fn flat_forward_rate() {
+        let tol = 1e-11;
+        let curr = Currency::from_str("EUR").unwrap();
+        let start = NaiveDate::from_ymd(2021, 3, 15);
+        for compounding in [
+            Compounding::Simple,
+            Compounding::Annual,
+            Compounding::SemiAnnual,
+            Compounding::Quarterly,
+            Compounding::Monthly,
+            Compounding::Continuous,
+        ] {
+            let rate = FlatRate::new(0.03, DayCountConv::Act365, compounding, curr);
+            for end in [
+                NaiveDate::from_ymd(2021, 4, 15),
+                NaiveDate::from_ymd(2022, 3, 15),
+                NaiveDate::from_ymd(2030, 12, 31),
+            ] {
+                assert_fuzzy_eq!(rate.forward_rate(start, end).unwrap(), 0.03, tol);
+            }
+            assert_eq!(rate.forward_rate(start, start), Err(DiscountError::InvalidPeriod));
+        }
+    }
+
+    #[test]
+    fn zero_curve_discounting() {
+        let tol = 1e-11;
+        let curr = Currency::from_str("EUR").unwrap();
+        let today = NaiveDate::from_ymd(2021, 1, 1);
+        let curve = ZeroCurve::new(
+            vec![
+                (NaiveDate::from_ymd(2023, 1, 1), 0.03),
+                (NaiveDate::from_ymd(2022, 1, 1), 0.01),
+            ],
+            DayCountConv::Act365,
+            Compounding::Annual,
+            curr,
+        )
+        .unwrap();
+        assert_fuzzy_eq!(curve.zero_rate(NaiveDate::from_ymd(2021, 7, 1)), 0.01, tol);
+        let zero_rate = 0.01 + 0.02 * 182. / 365.;
+        assert_fuzzy_eq!(curve.zero_rate(NaiveDate::from_ymd(2022, 7, 2)), zero_rate, tol);
+        assert_fuzzy_eq!(curve.zero_rate(NaiveDate::from_ymd(2030, 1, 1)), 0.03, tol);
+
+        let pay_date = NaiveDate::from_ymd(2022, 7, 2);
+        let yf = (pay_date - today).num_days() as f64 / 365.;
+        assert_fuzzy_eq!(curve.discount_factor(today, pay_date), (1. + zero_rate).powf(-yf), tol);
+        let cf = CashFlow::new(100., curr, pay_date);
+        assert_fuzzy_eq!(
+            curve.discount_cash_flow(&cf, today).unwrap().amount,
+            100. * (1. + zero_rate).powf(-yf),
+            tol
+        );
+
+        assert!(matches!(
+            ZeroCurve::new(Vec::new(), DayCountConv::Act365, Compounding::Annual, curr),
+            Err(InterpolationError::NoKnots)
+        ));
+        assert!(matches!(
+            ZeroCurve::new(vec![(today, 0.01), (today, 0.02)], DayCountConv::Act365, Compounding::Annual, curr),
+            Err(InterpolationError::UnsortedKnots)
+        ));
+    }
+
+    #[test]
+    fn bootstrap_zero_curve() {
+        let tol = 1e-10;
+        let curr = Currency::from_str("EUR").unwrap();
+        let settle = NaiveDate::from_ymd(2021, 1, 4);
+        let maturity = |years: i32| NaiveDate::from_ymd(2021 + years, 1, 4);
+
+        // flat par rates give a flat zero curve
+        let instruments: Vec<(NaiveDate, f64)> = (1..=5).map(|y| (maturity(y), 0.03)).collect();
+        let curve = ZeroCurve::bootstrap(&instruments, settle, DayCountConv::D30_360, Compounding::Annual, curr)
+            .unwrap();
+        for y in 1..=5 {
+            assert_fuzzy_eq!(curve.zero_rate(maturity(y)), 0.03, tol);
+        }
+
+        // each instrument prices to par on an upward sloping curve
+        let instruments = vec![(maturity(3), 0.02), (maturity(1), 0.01), (maturity(2), 0.015)];
+        let curve = ZeroCurve::bootstrap(&instruments, settle, DayCountConv::Act365, Compounding::Annual, curr)
+            .unwrap();
+        assert!(curve.zero_rate(maturity(1)) < curve.zero_rate(maturity(2)));
+        assert!(curve.zero_rate(maturity(2)) < curve.zero_rate(maturity(3)));
+        for (m, par_rate) in instruments {
+            let mut price = 0.;
+            let mut start = settle;
+            for y in 1..=(m.year() - 2021) {
+                let end = maturity(y);
+                let yf = DayCountConv::Act365.year_fraction(start, end, None, None).unwrap();
+                price += par_rate * yf * curve.discount_factor(settle, end);
+                start = end;
+            }
+            price += curve.discount_factor(settle, m);
+            assert_fuzzy_eq!(price, 1., tol);
+        }
+
+        assert_eq!(
+            ZeroCurve::bootstrap(&[(settle, 0.01)], settle, DayCountConv::Act365, Compounding::Annual, curr)
+                .unwrap_err(),
+            DiscountError::InvalidPeriod
+        );
+    }
+
+    #[test]
+    fn simple_discount_bill() {
+        let tol = 1e-11;
+        let curr = Currency::from_str("USD").unwrap();
+        let rate = FlatRate::new(0.05, DayCountConv::Act360, Compounding::Simple, curr);
+        let today = NaiveDate::from_ymd(2021, 1, 4);
+        let maturity = today + chrono::Duration::days(90);
+        let bill = CashFlow::new(100., curr, maturity);
+        assert_fuzzy_eq!(
+            rate.discount_cash_flow(&bill, today).unwrap().amount,
+            100. / (1. + 0.05 * 90. / 360.),
+            tol
+        );
+    }
+
     #[test]
     fn discounting() {
         let tol = 1e-11;
@@ -288,4 +776,25 @@ mod tests {
             tol
         );
     }
+
+    /// Generic function taking ownership of a discounter
+    fn discounted_value<D: Discounter>(discounter: D, cash_flows: &[CashFlow], today: NaiveDate) -> f64 {
+        discounter.discount_cash_flow_stream(cash_flows, today).unwrap().amount
+    }
+
+    #[test]
+    fn discount_with_borrowed_discounter() {
+        let curr = Currency::from_str("EUR").unwrap();
+        let rate = FlatRate::new(0.03, DayCountConv::Act365, Compounding::Annual, curr);
+        let today = NaiveDate::from_ymd(2020, 10, 1);
+        let cash_flows = vec![
+            CashFlow::new(30., curr, NaiveDate::from_ymd(2021, 10, 1)),
+            CashFlow::new(1030., curr, NaiveDate::from_ymd(2022, 10, 1)),
+        ];
+        let by_value = discounted_value(rate, &cash_flows, today);
+        let borrowed_rate: &FlatRate = &rate;
+        assert_eq!(discounted_value(borrowed_rate, &cash_flows, today), by_value);
+        let dyn_discounter: &dyn Discounter = &rate;
+        assert_eq!(discounted_value(dyn_discounter, &cash_flows, today), by_value);
+    }
 }
@@ -0,0 +1,184 @@
+//! Generation of periodic date schedules, e.g. coupon dates of bonds
+//! The schedule is rolled out either forward from the effective date or backward
+//! from maturity. If the period between the two dates is not a multiple of the
+//! frequency, a short stub period remains at the end or at the start, respectively.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::Calendar;
+use crate::day_adjust::DayAdjust;
+use crate::fixed_income::Frequency;
+use crate::time_period::TimePeriod;
+
+/// Time period of `n` regular periods of the given frequency
+fn periods(frequency: Frequency, n: i32) -> TimePeriod {
+    format!("{}M", n * frequency.months()).parse().unwrap()
+}
+
+/// Direction in which the schedule is rolled out
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateGeneration {
+    /// Roll forward from the effective date, a stub period may remain before maturity
+    #[serde(rename = "forward")]
+    Forward,
+    /// Roll backward from maturity, a stub period may remain after the effective date
+    #[serde(rename = "backward")]
+    Backward,
+}
+
+/// Builder of a schedule of periodic dates between an effective date and maturity
+pub struct Schedule<'a> {
+    effective: NaiveDate,
+    maturity: NaiveDate,
+    frequency: Frequency,
+    calendar: &'a Calendar,
+    adjust: DayAdjust,
+    generation: DateGeneration,
+}
+
+impl<'a> Schedule<'a> {
+    /// Create new schedule, rolled out backward from maturity by default
+    pub fn new(
+        effective: NaiveDate,
+        maturity: NaiveDate,
+        frequency: Frequency,
+        calendar: &'a Calendar,
+        adjust: DayAdjust,
+    ) -> Schedule<'a> {
+        Schedule {
+            effective,
+            maturity,
+            frequency,
+            calendar,
+            adjust,
+            generation: DateGeneration::Backward,
+        }
+    }
+
+    /// Set the direction in which the schedule is rolled out
+    pub fn generation(mut self, generation: DateGeneration) -> Schedule<'a> {
+        self.generation = generation;
+        self
+    }
+
+    /// Unadjusted dates of the schedule in ascending order, starting with the effective date
+    /// and ending with maturity. Each date is calculated from the anchor date (the effective date
+    /// or maturity) directly to avoid day shifts at the end of months.
+    pub fn unadjusted_dates(&self) -> Vec<NaiveDate> {
+        if self.maturity <= self.effective {
+            return vec![self.effective];
+        }
+        let mut dates = vec![self.effective];
+        match self.generation {
+            DateGeneration::Forward => {
+                let mut n = 1;
+                loop {
+                    let date = periods(self.frequency, n).add_to(self.effective, None);
+                    if date >= self.maturity {
+                        break;
+                    }
+                    dates.push(date);
+                    n += 1;
+                }
+                dates.push(self.maturity);
+            }
+            DateGeneration::Backward => {
+                let mut rolled = Vec::new();
+                let mut n = 1;
+                loop {
+                    let date = periods(self.frequency, n).sub_from(self.maturity, None);
+                    if date <= self.effective {
+                        break;
+                    }
+                    rolled.push(date);
+                    n += 1;
+                }
+                dates.extend(rolled.into_iter().rev());
+                dates.push(self.maturity);
+            }
+        }
+        dates
+    }
+
+    /// Schedule dates adjusted to business days of the calendar
+    pub fn dates(&self) -> Vec<NaiveDate> {
+        self.unadjusted_dates()
+            .into_iter()
+            .map(|date| self.calendar.adjust(date, self.adjust))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::Holiday;
+    use chrono::Weekday;
+
+    #[test]
+    fn generate_schedules_with_stubs() {
+        let holidays = vec![
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2020, 2023);
+        let effective = NaiveDate::from_ymd(2020, 3, 15);
+        let maturity = NaiveDate::from_ymd(2022, 1, 31);
+
+        let schedule = Schedule::new(effective, maturity, Frequency::SemiAnnual, &cal, DayAdjust::Modified);
+        assert_eq!(
+            schedule.unadjusted_dates(),
+            vec![
+                effective,
+                NaiveDate::from_ymd(2020, 7, 31),
+                NaiveDate::from_ymd(2021, 1, 31),
+                NaiveDate::from_ymd(2021, 7, 31),
+                maturity,
+            ]
+        );
+        // Sunday 2021-01-31 and Saturday 2021-07-31 are moved back into their month
+        assert_eq!(
+            schedule.dates(),
+            vec![
+                NaiveDate::from_ymd(2020, 3, 16),
+                NaiveDate::from_ymd(2020, 7, 31),
+                NaiveDate::from_ymd(2021, 1, 29),
+                NaiveDate::from_ymd(2021, 7, 30),
+                maturity,
+            ]
+        );
+
+        let schedule = schedule.generation(DateGeneration::Forward);
+        assert_eq!(
+            schedule.unadjusted_dates(),
+            vec![
+                effective,
+                NaiveDate::from_ymd(2020, 9, 15),
+                NaiveDate::from_ymd(2021, 3, 15),
+                NaiveDate::from_ymd(2021, 9, 15),
+                maturity,
+            ]
+        );
+
+        // regular schedule without stub, end of month is kept
+        let schedule = Schedule::new(
+            NaiveDate::from_ymd(2021, 1, 31),
+            NaiveDate::from_ymd(2021, 5, 31),
+            Frequency::Monthly,
+            &cal,
+            DayAdjust::None,
+        )
+        .generation(DateGeneration::Forward);
+        assert_eq!(
+            schedule.dates(),
+            vec![
+                NaiveDate::from_ymd(2021, 1, 31),
+                NaiveDate::from_ymd(2021, 2, 28),
+                NaiveDate::from_ymd(2021, 3, 31),
+                NaiveDate::from_ymd(2021, 4, 30),
+                NaiveDate::from_ymd(2021, 5, 31),
+            ]
+        );
+    }
+}
@@ -1,18 +1,28 @@
 use std::error::Error;
 use std::fmt;
-use chrono::{DateTime, NaiveDate, Local};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Local, TimeZone};
+use chrono_tz::Tz;
 use crate::calendar::Calendar;
-use std::collections::HashSet;
+use crate::clock::{Clock, SystemClock};
+use crate::interpolation::{Interpolator, Linear, StepPrevious};
+use std::collections::{BTreeMap, HashSet};
+use finql_data::Quote;
 
 #[derive(Debug)]
 pub enum TimeSeriesError {
     IsEmpty,
+    ZeroValue,
+    NegativeValue,
+    SpanTooShort,
 }
 
 impl fmt::Display for TimeSeriesError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             TimeSeriesError::IsEmpty => write!(f, "Time series is empty."),
+            TimeSeriesError::ZeroValue => write!(f, "Time series starts with a zero value."),
+            TimeSeriesError::NegativeValue => write!(f, "Time series starts with a negative value."),
+            TimeSeriesError::SpanTooShort => write!(f, "Time series spans less than one day."),
         }
     }
 }
@@ -36,7 +46,195 @@ pub struct TimeSeries {
     pub title: String,
 }
 
+/// Rule to resolve values of two time series with equal time stamps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeStrategy {
+    /// Keep the value of the series merged into
+    PreferSelf,
+    /// Keep the value of the series merged in
+    PreferOther,
+    /// Use the average of both values
+    Average,
+}
+
+/// Method to derive values of a time series between its observations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterpMode {
+    /// Last value at or before the given time
+    StepBackward,
+    /// Linear interpolation in time between the surrounding values
+    Linear,
+}
+
+/// Daily open, high, low and close prices
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ohlc {
+    pub date: NaiveDate,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Total volume of all quotes of the day, if any volume is known
+    pub volume: Option<f64>,
+}
+
+/// Check whether a quote's volume reaches the given minimum volume.
+/// Quotes without volume are excluded if a minimum volume is set.
+fn has_min_volume(quote: &Quote, min_volume: Option<f64>) -> bool {
+    match min_volume {
+        None => true,
+        Some(min_volume) => quote.volume.is_some_and(|volume| volume >= min_volume),
+    }
+}
+
+/// Aggregate quotes to daily OHLC bars, ignoring all quotes with less than `min_volume`.
+/// Quotes are assigned to the calendar day in the exchange's time zone `tz`.
+pub fn to_ohlc(quotes: &[Quote], min_volume: Option<f64>, tz: Tz) -> Vec<Ohlc> {
+    let mut quotes: Vec<&Quote> = quotes
+        .iter()
+        .filter(|q| has_min_volume(q, min_volume))
+        .collect();
+    quotes.sort_by_key(|q| q.time);
+    let mut bars: Vec<Ohlc> = Vec::new();
+    for quote in quotes {
+        let date = quote.time.with_timezone(&tz).date_naive();
+        match bars.last_mut() {
+            Some(bar) if bar.date == date => {
+                bar.high = bar.high.max(quote.price);
+                bar.low = bar.low.min(quote.price);
+                bar.close = quote.price;
+                bar.volume = match (bar.volume, quote.volume) {
+                    (Some(total), Some(volume)) => Some(total + volume),
+                    (total, volume) => total.or(volume),
+                };
+            }
+            _ => bars.push(Ohlc {
+                date,
+                open: quote.price,
+                high: quote.price,
+                low: quote.price,
+                close: quote.price,
+                volume: quote.volume,
+            }),
+        }
+    }
+    bars
+}
+
+/// Running statistics of a stream of values, updated incrementally with each new value
+/// using Welford's algorithm, e.g. to monitor quotes as they arrive from a feed.
+#[derive(Debug, Clone, Default)]
+pub struct StreamingStats {
+    count: usize,
+    mean: f64,
+    /// Sum of squared deviations from the current mean
+    m2: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+    last: Option<f64>,
+}
+
+impl StreamingStats {
+    pub fn new() -> StreamingStats {
+        StreamingStats::default()
+    }
+
+    /// Add a new value to the statistics
+    pub fn update(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+        self.last = Some(value);
+    }
+
+    /// Number of values seen so far
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Mean of all values, or `None` if no value has been seen yet
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.mean)
+        }
+    }
+
+    /// Sample variance of all values, or `None` if less than two values have been seen yet
+    pub fn variance(&self) -> Option<f64> {
+        if self.count < 2 {
+            None
+        } else {
+            Some(self.m2 / (self.count - 1) as f64)
+        }
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        self.max
+    }
+
+    /// Most recent value
+    pub fn last(&self) -> Option<f64> {
+        self.last
+    }
+}
+
 impl TimeSeries {
+    /// Build time series of quoted prices sorted by time, ignoring all quotes
+    /// with less than `min_volume`
+    pub fn from_quotes(title: &str, quotes: &[Quote], min_volume: Option<f64>) -> TimeSeries {
+        let mut series: Vec<TimeValue> = quotes
+            .iter()
+            .filter(|q| has_min_volume(q, min_volume))
+            .map(|q| TimeValue {
+                time: q.time,
+                value: q.price,
+            })
+            .collect();
+        series.sort_by_key(|v| v.time);
+        TimeSeries {
+            series,
+            title: title.to_string(),
+        }
+    }
+
+    /// Insert a value at its position in time, keeping the series sorted.
+    /// Values with equal time are kept in the order of insertion.
+    pub fn push_sorted(&mut self, tv: TimeValue) {
+        let idx = self.series.partition_point(|v| v.time <= tv.time);
+        self.series.insert(idx, tv);
+    }
+
+    /// Merge values into the series, keeping the series sorted. If `others` is
+    /// already sorted by time, both runs are merged in linear time.
+    pub fn extend_sorted(&mut self, mut others: Vec<TimeValue>) {
+        if !others.windows(2).all(|w| w[0].time <= w[1].time) {
+            others.sort_by_key(|v| v.time);
+        }
+        let mut series = Vec::with_capacity(self.series.len() + others.len());
+        let mut left = std::mem::take(&mut self.series).into_iter().peekable();
+        let mut right = others.into_iter().peekable();
+        loop {
+            let take_left = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) => l.time <= r.time,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let next = if take_left { left.next() } else { right.next() };
+            series.extend(next);
+        }
+        self.series = series;
+    }
+
     pub fn min_max(&self) -> Result<(NaiveDate, NaiveDate, f64, f64), TimeSeriesError> {
         if self.series.is_empty() {
             return Err(TimeSeriesError::IsEmpty)
@@ -56,16 +254,250 @@ impl TimeSeries {
        Ok((min_time.naive_local().date(), max_time.naive_local().date(), min_val, max_val))
     }
 
+    /// Combine the values of both series into a new series sorted by time, keeping the title
+    /// of `self`. Values of both series at the same time are resolved according to `strategy`.
+    pub fn merge(&self, other: &TimeSeries, strategy: MergeStrategy) -> TimeSeries {
+        let mut values: BTreeMap<DateTime<Local>, f64> =
+            self.series.iter().map(|v| (v.time, v.value)).collect();
+        for v in &other.series {
+            values
+                .entry(v.time)
+                .and_modify(|value| match strategy {
+                    MergeStrategy::PreferSelf => {}
+                    MergeStrategy::PreferOther => *value = v.value,
+                    MergeStrategy::Average => *value = (*value + v.value) / 2.0,
+                })
+                .or_insert(v.value);
+        }
+        TimeSeries {
+            series: values
+                .into_iter()
+                .map(|(time, value)| TimeValue { time, value })
+                .collect(),
+            title: self.title.clone(),
+        }
+    }
+
+    /// Value of the series at the given time, derived according to `mode`. Returns `None`
+    /// if `time` is before the first value. After the last value, the last value is returned.
+    /// Of several values with the same time, the last one in the series is used.
+    pub fn value_at(&self, time: DateTime<Local>, mode: InterpMode) -> Option<f64> {
+        let mut values: Vec<&TimeValue> = self.series.iter().collect();
+        values.sort_by_key(|v| v.time);
+        if values.first()?.time > time {
+            return None;
+        }
+        let mut knots: Vec<(f64, f64)> = Vec::with_capacity(values.len());
+        for v in values {
+            let x = v.time.timestamp() as f64;
+            match knots.last_mut() {
+                Some(last) if last.0 == x => last.1 = v.value,
+                _ => knots.push((x, v.value)),
+            }
+        }
+        let x = time.timestamp() as f64;
+        match mode {
+            InterpMode::StepBackward => StepPrevious.interpolate(&knots, x),
+            InterpMode::Linear => Linear.interpolate(&knots, x),
+        }
+        .ok()
+    }
+
+    /// Maximum drawdown, i.e. the largest relative decline from a running maximum to a
+    /// subsequent value. Returns the dates of the peak and the trough and the drawdown as
+    /// positive fraction of the peak value. Without any decline, the drawdown is zero and
+    /// peak and trough are both the date of the first value.
+    pub fn max_drawdown(&self) -> Result<(NaiveDate, NaiveDate, f64), TimeSeriesError> {
+        let first = self.series.first().ok_or(TimeSeriesError::IsEmpty)?;
+        if first.value == 0.0 {
+            return Err(TimeSeriesError::ZeroValue);
+        }
+        if first.value < 0.0 {
+            return Err(TimeSeriesError::NegativeValue);
+        }
+        let mut peak = first;
+        let (mut max_peak, mut max_trough, mut max_drawdown) = (first, first, 0.0);
+        for v in &self.series {
+            if v.value > peak.value {
+                peak = v;
+            }
+            let drawdown = 1.0 - v.value / peak.value;
+            if drawdown > max_drawdown {
+                max_peak = peak;
+                max_trough = v;
+                max_drawdown = drawdown;
+            }
+        }
+        Ok((
+            max_peak.time.naive_local().date(),
+            max_trough.time.naive_local().date(),
+            max_drawdown,
+        ))
+    }
+
+    /// Total return over the whole series, i.e. last value divided by first value minus one
+    pub fn total_return(&self) -> Result<f64, TimeSeriesError> {
+        let first = self.series.first().ok_or(TimeSeriesError::IsEmpty)?.value;
+        let last = self.series.last().ok_or(TimeSeriesError::IsEmpty)?.value;
+        if first == 0.0 {
+            return Err(TimeSeriesError::ZeroValue);
+        }
+        Ok(last / first - 1.0)
+    }
+
+    /// Compound annual growth rate, i.e. the total return annualized over
+    /// the time elapsed between the first and the last value
+    pub fn cagr(&self) -> Result<f64, TimeSeriesError> {
+        let first = self.series.first().ok_or(TimeSeriesError::IsEmpty)?;
+        let last = self.series.last().ok_or(TimeSeriesError::IsEmpty)?;
+        if first.value == 0.0 {
+            return Err(TimeSeriesError::ZeroValue);
+        }
+        if first.value < 0.0 {
+            return Err(TimeSeriesError::NegativeValue);
+        }
+        let days = (last.time - first.time).num_seconds() as f64 / 86400.;
+        if days < 1.0 {
+            return Err(TimeSeriesError::SpanTooShort);
+        }
+        Ok((last.value / first.value).powf(365. / days) - 1.0)
+    }
+
+    /// Calculate period-over-period returns, the time of each return is the later of
+    /// the two observations. Dividends given as pairs of ex-date and amount are added
+    /// back to the value on the ex-date, so that the drop in price on that date is not
+    /// mistaken for a loss. Pairs where the earlier value is zero are skipped.
+    pub fn returns(&self, dividends: &[(NaiveDate, f64)]) -> Result<TimeSeries, TimeSeriesError> {
+        if self.series.len() < 2 {
+            return Err(TimeSeriesError::IsEmpty);
+        }
+        let mut series = Vec::new();
+        for w in self.series.windows(2) {
+            if w[0].value == 0.0 {
+                continue;
+            }
+            let date = w[1].time.naive_local().date();
+            let dividend: f64 = dividends
+                .iter()
+                .filter(|(ex_date, _)| *ex_date == date)
+                .map(|(_, amount)| amount)
+                .sum();
+            series.push(TimeValue {
+                time: w[1].time,
+                value: (w[1].value + dividend) / w[0].value - 1.0,
+            });
+        }
+        Ok(TimeSeries {
+            series,
+            title: format!("{} returns", self.title),
+        })
+    }
+
+    /// Calculate period-over-period simple returns like `returns`, but without dividends
+    pub fn simple_returns(&self) -> Result<TimeSeries, TimeSeriesError> {
+        self.returns(&[])
+    }
+
+    /// Calculate period-over-period logarithmic returns, i.e. the logarithm of the ratio
+    /// of consecutive values. Pairs where the earlier value is zero are skipped, a drop
+    /// to zero gives a log return of negative infinity.
+    pub fn log_returns(&self) -> Result<TimeSeries, TimeSeriesError> {
+        let mut returns = self.simple_returns()?;
+        for v in returns.series.iter_mut() {
+            v.value = v.value.ln_1p();
+        }
+        returns.title = format!("{} log returns", self.title);
+        Ok(returns)
+    }
+
+    /// Annualization factor derived from the average spacing of the observations.
+    /// Spacings typical for daily (business day), weekly, monthly, quarterly or annual data
+    /// are mapped to the conventional factors 252, 52, 12, 4 and 1, any other spacing
+    /// to the number of average spacings per calendar year.
+    pub fn infer_periods_per_year(&self) -> Result<f64, TimeSeriesError> {
+        if self.series.len() < 2 {
+            return Err(TimeSeriesError::IsEmpty);
+        }
+        let first = self.series.first().unwrap();
+        let last = self.series.last().unwrap();
+        let days = (last.time - first.time).num_seconds() as f64 / 86400.;
+        if days <= 0.0 {
+            return Err(TimeSeriesError::SpanTooShort);
+        }
+        let spacing = days / (self.series.len() - 1) as f64;
+        let periods = match spacing {
+            s if (0.9..2.0).contains(&s) => 252.,
+            s if (6.0..8.0).contains(&s) => 52.,
+            s if (27.0..35.0).contains(&s) => 12.,
+            s if (85.0..97.0).contains(&s) => 4.,
+            s if (358.0..373.0).contains(&s) => 1.,
+            s => 365.25 / s,
+        };
+        Ok(periods)
+    }
+
+    /// Annualized volatility, i.e. the sample standard deviation of the values (typically
+    /// a series of returns) scaled by the square root of the number of periods per year.
+    /// If `periods_per_year` is not given, it is inferred from the spacing of the observations.
+    pub fn volatility(&self, periods_per_year: Option<f64>) -> Result<f64, TimeSeriesError> {
+        let periods_per_year = match periods_per_year {
+            Some(periods) => periods,
+            None => self.infer_periods_per_year()?,
+        };
+        let mut stats = StreamingStats::new();
+        for v in &self.series {
+            stats.update(v.value);
+        }
+        let variance = stats.variance().ok_or(TimeSeriesError::IsEmpty)?;
+        Ok((variance * periods_per_year).sqrt())
+    }
+
+    /// Resample the series to one value per business day between the first and the last value.
+    /// Each business day gets the last value known at the end of that day, i.e. days without any
+    /// value repeat the previous value and of several values on the same day, the latest is kept.
+    pub fn resample_bdays(&self, cal: &Calendar) -> TimeSeries {
+        let mut values: Vec<&TimeValue> = self.series.iter().collect();
+        values.sort_by_key(|v| v.time);
+        let mut series = Vec::new();
+        if let (Some(first), Some(last)) = (values.first(), values.last()) {
+            let mut values = values.iter().peekable();
+            let mut current: Option<&TimeValue> = None;
+            for date in cal.business_days(first.time.naive_local().date(), last.time.naive_local().date()) {
+                while let Some(v) = values.next_if(|v| v.time.naive_local().date() <= date) {
+                    current = Some(v);
+                }
+                if let Some(v) = current {
+                    series.push(TimeValue {
+                        time: v.time + Duration::days((date - v.time.naive_local().date()).num_days()),
+                        value: v.value,
+                    });
+                }
+            }
+        }
+        TimeSeries {
+            series,
+            title: format!("{} (resampled)", self.title),
+        }
+    }
+
+    /// Find all business days since the begin of the series up to today without any value
     pub fn find_gaps(&self, cal: &Calendar) -> Result<Vec<(NaiveDate,NaiveDate)>, TimeSeriesError> {
+        self.find_gaps_with_clock(cal, &SystemClock)
+    }
+
+    /// Find all business days since the begin of the series up to the current date
+    /// of the given clock without any value
+    pub fn find_gaps_with_clock(&self, cal: &Calendar, clock: &dyn Clock) -> Result<Vec<(NaiveDate,NaiveDate)>, TimeSeriesError> {
+        self.find_gaps_until(cal, clock.today())
+    }
+
+    /// Find all business days since the begin of the series up to `end` without any value
+    pub fn find_gaps_until(&self, cal: &Calendar, end: NaiveDate) -> Result<Vec<(NaiveDate,NaiveDate)>, TimeSeriesError> {
         let mut gaps = Vec::new();
         let (min_date, _, _, _) = self.min_max()?;
-        let today = Local::now().naive_local().date();
-        println!("series: {:?}", self.series);
         let dates: HashSet<NaiveDate> = self.series.iter().map(|t| t.time.naive_local().date() ).collect();
         let mut gap_begin = None;
-        let mut date = min_date;
-        while date <= today {
-            println!("contains {}: {}", date, dates.contains(&date));
+        for date in cal.business_days(min_date, end) {
             match gap_begin {
                 None => {
                     if ! dates.contains(&date) {
@@ -80,15 +512,53 @@ impl TimeSeries {
                     }
                 }
             }
-            date = cal.next_bday(date);
         }
 
         if let Some(d) = gap_begin {
-            gaps.push((d, today));
+            gaps.push((d, end));
         }
 
         Ok(gaps)
     }
+
+    /// Find all windows within the trading session of each business day between the first
+    /// and the last value of the series, where no value arrived for longer than `expected_interval`.
+    /// The session is given by its start and end time in the local time zone.
+    pub fn find_intraday_gaps(
+        &self,
+        expected_interval: Duration,
+        session: (NaiveTime, NaiveTime),
+        cal: &Calendar,
+    ) -> Vec<(DateTime<Local>, DateTime<Local>)> {
+        let mut gaps = Vec::new();
+        let (first, last) = match (self.series.first(), self.series.last()) {
+            (Some(first), Some(last)) => (first.time, last.time),
+            _ => return gaps,
+        };
+        let mut times: Vec<DateTime<Local>> = self.series.iter().map(|v| v.time).collect();
+        times.sort();
+        for date in cal.business_days(first.naive_local().date(), last.naive_local().date()) {
+            let (session_start, session_end) = match (
+                Local.from_local_datetime(&date.and_time(session.0)).single(),
+                Local.from_local_datetime(&date.and_time(session.1)).single(),
+            ) {
+                (Some(start), Some(end)) => (start, end),
+                _ => continue,
+            };
+            let mut previous = session_start;
+            for time in times
+                .iter()
+                .filter(|t| **t >= session_start && **t <= session_end)
+                .chain(std::iter::once(&session_end))
+            {
+                if *time - previous > expected_interval {
+                    gaps.push((previous, *time));
+                }
+                previous = *time;
+            }
+        }
+        gaps
+    }
 }
 
 
@@ -96,8 +566,9 @@ impl TimeSeries {
 mod tests {
     use super::*;
     use crate::calendar::Holiday;
+    use crate::clock::FixedClock;
     use chrono::{Datelike, Weekday};
-    use finql_data::date_time_helper::make_time;
+    use finql_data::date_time_helper::{make_time, naive_date_to_date_time};
 
     #[test]
     fn finding_gaps() {
@@ -129,4 +600,453 @@ mod tests {
         assert_eq!(gaps[2].0, NaiveDate::from_ymd(2021, 11, 10));
         assert_eq!(gaps[2].1, today);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn total_return_since_inception() {
+        let mut ts = TimeSeries{
+            title: "test".to_string(),
+            series: Vec::new()
+        };
+        assert!(ts.total_return().is_err());
+        ts.series.push( TimeValue{ time: make_time(2021, 10, 28, 20, 0, 0).unwrap(), value: 100.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 10, 29, 20, 0, 0).unwrap(), value: 120.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 1, 20, 0, 0).unwrap(), value: 150.0 } );
+        assert_fuzzy_eq!(ts.total_return().unwrap(), 0.5, 1e-11);
+
+        ts.series[0].value = 0.0;
+        assert!(ts.total_return().is_err());
+    }
+
+    #[test]
+    fn compound_annual_growth_rate() {
+        let mut ts = TimeSeries{
+            title: "test".to_string(),
+            series: Vec::new()
+        };
+        assert!(ts.cagr().is_err());
+        ts.series.push( TimeValue{ time: make_time(2021, 1, 4, 20, 0, 0).unwrap(), value: 100.0 } );
+        assert!(ts.cagr().is_err());
+        ts.series.push( TimeValue{ time: make_time(2021, 7, 5, 20, 0, 0).unwrap(), value: 130.0 } );
+        ts.series.push( TimeValue{ time: make_time(2022, 1, 4, 20, 0, 0).unwrap(), value: 200.0 } );
+        assert_fuzzy_eq!(ts.cagr().unwrap(), 1.0, 1e-11);
+
+        ts.series[0].value = -100.0;
+        assert!(ts.cagr().is_err());
+    }
+
+    #[test]
+    fn merge_time_series() {
+        let mut ts = TimeSeries{
+            title: "vendor 1".to_string(),
+            series: Vec::new()
+        };
+        ts.series.push( TimeValue{ time: make_time(2021, 10, 28, 20, 0, 0).unwrap(), value: 100.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 1, 20, 0, 0).unwrap(), value: 110.0 } );
+        let mut other = TimeSeries{
+            title: "vendor 2".to_string(),
+            series: Vec::new()
+        };
+        other.series.push( TimeValue{ time: make_time(2021, 11, 1, 20, 0, 0).unwrap(), value: 120.0 } );
+        other.series.push( TimeValue{ time: make_time(2021, 10, 29, 20, 0, 0).unwrap(), value: 105.0 } );
+
+        let values = |ts: TimeSeries| -> Vec<f64> { ts.series.iter().map(|v| v.value).collect() };
+        let merged = ts.merge(&other, MergeStrategy::PreferSelf);
+        assert_eq!(merged.title, "vendor 1");
+        assert_eq!(merged.series[1].time, make_time(2021, 10, 29, 20, 0, 0).unwrap());
+        assert_eq!(values(merged), vec![100.0, 105.0, 110.0]);
+        assert_eq!(values(ts.merge(&other, MergeStrategy::PreferOther)), vec![100.0, 105.0, 120.0]);
+        assert_eq!(values(ts.merge(&other, MergeStrategy::Average)), vec![100.0, 105.0, 115.0]);
+    }
+
+    #[test]
+    fn value_at_arbitrary_time() {
+        let mut ts = TimeSeries{
+            title: "test".to_string(),
+            series: Vec::new()
+        };
+        assert_eq!(ts.value_at(make_time(2021, 11, 1, 20, 0, 0).unwrap(), InterpMode::Linear), None);
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 1, 20, 0, 0).unwrap(), value: 100.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 2, 20, 0, 0).unwrap(), value: 110.0 } );
+
+        let tol = 1e-11;
+        let before = make_time(2021, 11, 1, 19, 0, 0).unwrap();
+        let between = make_time(2021, 11, 2, 8, 0, 0).unwrap();
+        let after = make_time(2021, 11, 3, 20, 0, 0).unwrap();
+        assert_eq!(ts.value_at(before, InterpMode::StepBackward), None);
+        assert_eq!(ts.value_at(before, InterpMode::Linear), None);
+        assert_fuzzy_eq!(ts.value_at(between, InterpMode::StepBackward).unwrap(), 100.0, tol);
+        assert_fuzzy_eq!(ts.value_at(between, InterpMode::Linear).unwrap(), 105.0, tol);
+        assert_fuzzy_eq!(ts.value_at(ts.series[1].time, InterpMode::StepBackward).unwrap(), 110.0, tol);
+        assert_fuzzy_eq!(ts.value_at(after, InterpMode::StepBackward).unwrap(), 110.0, tol);
+        assert_fuzzy_eq!(ts.value_at(after, InterpMode::Linear).unwrap(), 110.0, tol);
+    }
+
+    #[test]
+    fn maximum_drawdown() {
+        let mut ts = TimeSeries{
+            title: "test".to_string(),
+            series: Vec::new()
+        };
+        assert!(matches!(ts.max_drawdown(), Err(TimeSeriesError::IsEmpty)));
+        ts.series.push( TimeValue{ time: make_time(2021, 10, 28, 20, 0, 0).unwrap(), value: 100.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 10, 29, 20, 0, 0).unwrap(), value: 110.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 1, 20, 0, 0).unwrap(), value: 120.0 } );
+        assert_eq!(ts.max_drawdown().unwrap(), (
+            NaiveDate::from_ymd(2021, 10, 28),
+            NaiveDate::from_ymd(2021, 10, 28),
+            0.0
+        ));
+
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 2, 20, 0, 0).unwrap(), value: 90.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 3, 20, 0, 0).unwrap(), value: 150.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 4, 20, 0, 0).unwrap(), value: 135.0 } );
+        let (peak, trough, drawdown) = ts.max_drawdown().unwrap();
+        assert_eq!(peak, NaiveDate::from_ymd(2021, 11, 1));
+        assert_eq!(trough, NaiveDate::from_ymd(2021, 11, 2));
+        assert_fuzzy_eq!(drawdown, 0.25, 1e-11);
+    }
+
+    #[test]
+    fn returns_with_dividends() {
+        let mut ts = TimeSeries{
+            title: "test".to_string(),
+            series: Vec::new()
+        };
+        ts.series.push( TimeValue{ time: make_time(2021, 10, 28, 20, 0, 0).unwrap(), value: 100.0 } );
+        assert!(ts.returns(&[]).is_err());
+        ts.series.push( TimeValue{ time: make_time(2021, 10, 29, 20, 0, 0).unwrap(), value: 110.0 } );
+        // ex-dividend date with a dividend of 10.0
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 1, 20, 0, 0).unwrap(), value: 100.1 } );
+
+        let tol = 1e-11;
+        let returns = ts.returns(&[]).unwrap();
+        assert_eq!(returns.series.len(), 2);
+        assert_fuzzy_eq!(returns.series[0].value, 0.1, tol);
+        assert_fuzzy_eq!(returns.series[1].value, 100.1 / 110.0 - 1.0, tol);
+        assert_eq!(returns.series[1].time, ts.series[2].time);
+
+        let returns = ts.returns(&[(NaiveDate::from_ymd(2021, 11, 1), 10.0)]).unwrap();
+        assert_eq!(returns.series.len(), 2);
+        assert_fuzzy_eq!(returns.series[0].value, 0.1, tol);
+        assert_fuzzy_eq!(returns.series[1].value, 110.1 / 110.0 - 1.0, tol);
+    }
+
+    #[test]
+    fn simple_and_log_returns() {
+        let mut ts = TimeSeries{
+            title: "test".to_string(),
+            series: Vec::new()
+        };
+        ts.series.push( TimeValue{ time: make_time(2021, 10, 28, 20, 0, 0).unwrap(), value: 100.0 } );
+        assert!(matches!(ts.simple_returns(), Err(TimeSeriesError::IsEmpty)));
+        assert!(matches!(ts.log_returns(), Err(TimeSeriesError::IsEmpty)));
+        ts.series.push( TimeValue{ time: make_time(2021, 10, 29, 20, 0, 0).unwrap(), value: 0.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 1, 20, 0, 0).unwrap(), value: 50.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 2, 20, 0, 0).unwrap(), value: 75.0 } );
+
+        let tol = 1e-11;
+        // the return after the zero value is skipped
+        let returns = ts.simple_returns().unwrap();
+        assert_eq!(returns.series.len(), 2);
+        assert_fuzzy_eq!(returns.series[0].value, -1.0, tol);
+        assert_fuzzy_eq!(returns.series[1].value, 0.5, tol);
+        assert_eq!(returns.series[1].time, ts.series[3].time);
+
+        let log_returns = ts.log_returns().unwrap();
+        assert_eq!(log_returns.title, "test log returns");
+        assert_eq!(log_returns.series[0].value, f64::NEG_INFINITY);
+        assert_fuzzy_eq!(log_returns.series[1].value, 1.5_f64.ln(), tol);
+    }
+
+    #[test]
+    fn resample_to_business_days() {
+        let holidays = vec![
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2021, 2021);
+
+        let mut ts = TimeSeries{
+            title: "test".to_string(),
+            series: Vec::new()
+        };
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 4, 20, 0, 0).unwrap(), value: 1.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 4, 10, 0, 0).unwrap(), value: 0.5 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 6, 12, 0, 0).unwrap(), value: 2.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 10, 9, 0, 0).unwrap(), value: 3.0 } );
+
+        let resampled = ts.resample_bdays(&cal);
+        assert_eq!(resampled.title, "test (resampled)");
+        let dates: Vec<NaiveDate> = resampled.series.iter().map(|v| v.time.naive_local().date()).collect();
+        let values: Vec<f64> = resampled.series.iter().map(|v| v.value).collect();
+        assert_eq!(dates, vec![
+            NaiveDate::from_ymd(2021, 11, 4),
+            NaiveDate::from_ymd(2021, 11, 5),
+            NaiveDate::from_ymd(2021, 11, 8),
+            NaiveDate::from_ymd(2021, 11, 9),
+            NaiveDate::from_ymd(2021, 11, 10),
+        ]);
+        assert_eq!(values, vec![1.0, 1.0, 2.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn finding_gaps_until_end_date() {
+        let holidays = vec![
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2020, 2021);
+
+        let mut ts = TimeSeries{
+            title: "test".to_string(),
+            series: Vec::new()
+        };
+        ts.series.push( TimeValue{ time: make_time(2020, 12, 21, 20, 0, 0).unwrap(), value: 1.0 } );
+        ts.series.push( TimeValue{ time: make_time(2020, 12, 23, 20, 0, 0).unwrap(), value: 1.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 1, 4, 20, 0, 0).unwrap(), value: 1.0 } );
+
+        let gaps = ts.find_gaps_until(&cal, NaiveDate::from_ymd(2020, 12, 31)).unwrap();
+        assert_eq!(gaps, vec![
+            (NaiveDate::from_ymd(2020, 12, 22), NaiveDate::from_ymd(2020, 12, 22)),
+            (NaiveDate::from_ymd(2020, 12, 24), NaiveDate::from_ymd(2020, 12, 31)),
+        ]);
+    }
+
+    #[test]
+    fn finding_gaps_with_fixed_clock() {
+        let holidays = vec![
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2021, 2021);
+        let clock = FixedClock::new(make_time(2021, 11, 12, 12, 0, 0).unwrap());
+
+        let mut ts = TimeSeries{
+            title: "test".to_string(),
+            series: Vec::new()
+        };
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 8, 20, 0, 0).unwrap(), value: 1.0 } );
+        ts.series.push( TimeValue{ time: make_time(2021, 11, 9, 20, 0, 0).unwrap(), value: 1.0 } );
+
+        let gaps = ts.find_gaps_with_clock(&cal, &clock).unwrap();
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].0, NaiveDate::from_ymd(2021, 11, 10));
+        assert_eq!(gaps[0].1, NaiveDate::from_ymd(2021, 11, 12));
+    }
+
+    #[test]
+    fn filter_quotes_by_volume() {
+        let quote = |day, hour, price, volume| Quote {
+            id: None,
+            ticker: 1,
+            price,
+            time: make_time(2021, 11, day, hour, 0, 0).unwrap(),
+            volume,
+            bid: None,
+            ask: None,
+        };
+        let quotes = vec![
+            quote(8, 10, 100.0, Some(5000.0)),
+            quote(8, 12, 90.0, Some(10.0)),
+            quote(8, 14, 104.0, Some(2000.0)),
+            quote(8, 16, 102.0, None),
+            quote(9, 10, 110.0, Some(1.0)),
+            quote(10, 10, 105.0, Some(3000.0)),
+        ];
+
+        let ts = TimeSeries::from_quotes("test", &quotes, None);
+        assert_eq!(ts.series.len(), 6);
+        let ts = TimeSeries::from_quotes("test", &quotes, Some(1000.0));
+        assert_eq!(ts.series.len(), 3);
+        assert_eq!(ts.series[0].value, 100.0);
+        assert_eq!(ts.series[1].value, 104.0);
+        assert_eq!(ts.series[2].value, 105.0);
+
+        let bars = to_ohlc(&quotes, None, chrono_tz::UTC);
+        assert_eq!(bars.len(), 3);
+        assert_eq!(bars[0].low, 90.0);
+        assert_eq!(bars[0].close, 102.0);
+        assert_eq!(bars[0].volume, Some(7010.0));
+
+        let bars = to_ohlc(&quotes, Some(1000.0), chrono_tz::UTC);
+        assert_eq!(bars.len(), 2);
+        assert_eq!(
+            bars[0],
+            Ohlc {
+                date: NaiveDate::from_ymd(2021, 11, 8),
+                open: 100.0,
+                high: 104.0,
+                low: 100.0,
+                close: 104.0,
+                volume: Some(7000.0),
+            }
+        );
+        assert_eq!(bars[1].date, NaiveDate::from_ymd(2021, 11, 10));
+    }
+
+    #[test]
+    fn annualization_factor() {
+        let holidays = vec![
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2021, 2021);
+        let daily = TimeSeries {
+            title: "daily".to_string(),
+            series: cal
+                .business_days(NaiveDate::from_ymd(2021, 1, 4), NaiveDate::from_ymd(2021, 12, 31))
+                .enumerate()
+                .map(|(i, date)| TimeValue {
+                    time: naive_date_to_date_time(&date, 18, None).unwrap(),
+                    value: if i % 2 == 0 { 0.01 } else { -0.01 },
+                })
+                .collect(),
+        };
+        assert_eq!(daily.infer_periods_per_year().unwrap(), 252.);
+        let tol = 1e-11;
+        assert_fuzzy_eq!(daily.volatility(None).unwrap(), daily.volatility(Some(252.)).unwrap(), tol);
+
+        let monthly = TimeSeries {
+            title: "monthly".to_string(),
+            series: (1..=12)
+                .map(|month| TimeValue {
+                    time: make_time(2021, month, 1, 18, 0, 0).unwrap(),
+                    value: 0.0,
+                })
+                .collect(),
+        };
+        assert_eq!(monthly.infer_periods_per_year().unwrap(), 12.);
+        assert_eq!(monthly.volatility(None).unwrap(), 0.);
+
+        let irregular = TimeSeries {
+            title: "irregular".to_string(),
+            series: (0..5)
+                .map(|i| TimeValue {
+                    time: make_time(2021, 1, 1, 12, 0, 0).unwrap() + Duration::days(10 * i),
+                    value: 0.0,
+                })
+                .collect(),
+        };
+        assert_fuzzy_eq!(irregular.infer_periods_per_year().unwrap(), 36.525, tol);
+    }
+
+    #[test]
+    fn sorted_insertion() {
+        let tv = |day, value| TimeValue {
+            time: make_time(2021, 11, day, 18, 0, 0).unwrap(),
+            value,
+        };
+        let is_sorted = |ts: &TimeSeries| ts.series.windows(2).all(|w| w[0].time <= w[1].time);
+        let mut ts = TimeSeries {
+            title: "test".to_string(),
+            series: Vec::new(),
+        };
+        for (day, value) in [(10, 1.0), (5, 2.0), (20, 3.0), (10, 4.0), (1, 5.0)] {
+            ts.push_sorted(tv(day, value));
+            assert!(is_sorted(&ts));
+        }
+        let values: Vec<f64> = ts.series.iter().map(|v| v.value).collect();
+        assert_eq!(values, vec![5.0, 2.0, 1.0, 4.0, 3.0]);
+
+        ts.extend_sorted(vec![tv(2, 6.0), tv(10, 7.0), tv(25, 8.0)]);
+        assert!(is_sorted(&ts));
+        let values: Vec<f64> = ts.series.iter().map(|v| v.value).collect();
+        assert_eq!(values, vec![5.0, 6.0, 2.0, 1.0, 4.0, 7.0, 3.0, 8.0]);
+
+        // unsorted values are sorted before merging
+        ts.extend_sorted(vec![tv(30, 9.0), tv(3, 10.0)]);
+        assert!(is_sorted(&ts));
+        assert_eq!(ts.series.len(), 10);
+        assert_eq!(ts.series[2].value, 10.0);
+    }
+
+    #[test]
+    fn streaming_statistics() {
+        let tol = 1e-11;
+        let values = [101.0, 99.5, 102.25, 98.0, 100.0, 103.5];
+        let mut stats = StreamingStats::new();
+        assert_eq!(stats.mean(), None);
+        stats.update(values[0]);
+        assert_eq!(stats.variance(), None);
+        for value in &values[1..] {
+            stats.update(*value);
+        }
+
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        assert_eq!(stats.count(), 6);
+        assert_fuzzy_eq!(stats.mean().unwrap(), mean, tol);
+        assert_fuzzy_eq!(stats.variance().unwrap(), variance, tol);
+        assert_eq!(stats.min(), Some(98.0));
+        assert_eq!(stats.max(), Some(103.5));
+        assert_eq!(stats.last(), Some(103.5));
+    }
+
+    #[test]
+    fn ohlc_in_exchange_time_zone() {
+        let quote = |day, hour, minute, price| Quote {
+            id: None,
+            ticker: 1,
+            price,
+            time: chrono::Utc.ymd(2021, 11, day).and_hms(hour, minute, 0).with_timezone(&Local),
+            volume: None,
+            bid: None,
+            ask: None,
+        };
+        // 23:30 UTC on Monday is already Tuesday morning in Tokyo
+        let quotes = vec![
+            quote(8, 6, 0, 100.0),
+            quote(8, 23, 30, 101.0),
+            quote(9, 5, 0, 103.0),
+        ];
+        let bars = to_ohlc(&quotes, None, chrono_tz::Asia::Tokyo);
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0].date, NaiveDate::from_ymd(2021, 11, 8));
+        assert_eq!(bars[0].close, 100.0);
+        assert_eq!(bars[1].date, NaiveDate::from_ymd(2021, 11, 9));
+        assert_eq!(bars[1].open, 101.0);
+        assert_eq!(bars[1].close, 103.0);
+
+        let bars = to_ohlc(&quotes, None, chrono_tz::UTC);
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0].close, 101.0);
+    }
+
+    #[test]
+    fn finding_intraday_gaps() {
+        let holidays = vec![
+            Holiday::WeekDay(Weekday::Sat),
+            Holiday::WeekDay(Weekday::Sun),
+        ];
+        let cal = Calendar::calc_calendar(&holidays, 2021, 2021);
+        let mut ts = TimeSeries{
+            title: "test".to_string(),
+            series: Vec::new()
+        };
+        // Friday and Monday with quotes every 30 minutes from 9:00 to 17:30,
+        // but nothing between 10:30 and 13:00 on Monday
+        for day in [5, 8] {
+            for half_hours in 0..18 {
+                let hour = 9 + half_hours / 2;
+                let minute = 30 * (half_hours % 2);
+                if day == 8 && (hour == 11 || hour == 12) {
+                    continue;
+                }
+                ts.series.push( TimeValue{ time: make_time(2021, 11, day, hour, minute, 0).unwrap(), value: 1.0 } );
+            }
+        }
+        let session = (NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(17, 30, 0));
+        let gaps = ts.find_intraday_gaps(Duration::minutes(30), session, &cal);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].0, make_time(2021, 11, 8, 10, 30, 0).unwrap());
+        assert_eq!(gaps[0].1, make_time(2021, 11, 8, 13, 0, 0).unwrap());
+
+        // session extending beyond last quote of the day
+        let session = (NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(18, 30, 0));
+        let gaps = ts.find_intraday_gaps(Duration::minutes(30), session, &cal);
+        assert_eq!(gaps.len(), 3);
+        assert_eq!(gaps[0].0, make_time(2021, 11, 5, 17, 30, 0).unwrap());
+        assert_eq!(gaps[0].1, make_time(2021, 11, 5, 18, 30, 0).unwrap());
+    }
+}